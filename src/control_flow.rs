@@ -0,0 +1,48 @@
+use crate::{Channels, Format, Protocol};
+use core::ops::ControlFlow;
+use futures::{
+    future::{Either, MapOk},
+    TryFutureExt,
+};
+
+fn from_either<B, C>(item: Either<B, C>) -> ControlFlow<B, C> {
+    match item {
+        Either::Left(item) => ControlFlow::Break(item),
+        Either::Right(item) => ControlFlow::Continue(item),
+    }
+}
+
+impl<F: ?Sized, Ctx, B, C> Protocol<F, Ctx> for ControlFlow<B, C>
+where
+    Either<B, C>: Protocol<F, Ctx>,
+{
+    type Unravel = <Either<B, C> as Protocol<F, Ctx>>::Unravel;
+    type UnravelError = <Either<B, C> as Protocol<F, Ctx>>::UnravelError;
+    type UnravelFuture = <Either<B, C> as Protocol<F, Ctx>>::UnravelFuture;
+    type Coalesce = <Either<B, C> as Protocol<F, Ctx>>::Coalesce;
+    type CoalesceError = <Either<B, C> as Protocol<F, Ctx>>::CoalesceError;
+    type CoalesceFuture = MapOk<
+        <Either<B, C> as Protocol<F, Ctx>>::CoalesceFuture,
+        fn(Either<B, C>) -> ControlFlow<B, C>,
+    >;
+
+    fn unravel(self, channel: Ctx::Unravel) -> Self::UnravelFuture
+    where
+        Ctx: Channels<Self::Unravel, Self::Coalesce>,
+        F: Format<Self::Unravel> + Format<Self::Coalesce>,
+    {
+        match self {
+            ControlFlow::Break(item) => Either::Left(item),
+            ControlFlow::Continue(item) => Either::Right(item),
+        }
+        .unravel(channel)
+    }
+
+    fn coalesce(channel: Ctx::Coalesce) -> Self::CoalesceFuture
+    where
+        Ctx: Channels<Self::Unravel, Self::Coalesce>,
+        F: Format<Self::Unravel> + Format<Self::Coalesce>,
+    {
+        <Either<B, C> as Protocol<F, Ctx>>::coalesce(channel).map_ok(from_either as fn(_) -> _)
+    }
+}
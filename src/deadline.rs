@@ -0,0 +1,379 @@
+use crate::director::{Embed, Timer};
+use crate::{Bottom, Channels, ContextError, Dispatch, Join, Pass, Protocol, Spawn};
+use core::{
+    convert::TryFrom,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures::{
+    future::{ready, Ready},
+    ready,
+    stream::{once, Forward, IntoStream, Once, StreamFuture},
+    Sink, StreamExt, TryFuture, TryStream, TryStreamExt,
+};
+use pin_utils::pin_mut;
+
+#[derive(Debug)]
+pub enum Error<Protocol, Channel> {
+    Protocol(Protocol),
+    Channel(Channel),
+    Elapsed,
+    Terminated,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expiry<H> {
+    millis: u64,
+    handle: H,
+}
+
+impl<H> Expiry<H> {
+    pub fn new(millis: u64, handle: H) -> Self {
+        Expiry { millis, handle }
+    }
+
+    pub fn millis(&self) -> u64 {
+        self.millis
+    }
+
+    pub fn into_handle(self) -> H {
+        self.handle
+    }
+}
+
+impl<H> Embed<Expiry<H>> for Expiry<H> {
+    fn embed(self) -> Self {
+        self
+    }
+
+    fn extract(representation: Self) -> Result<Self, Self> {
+        Ok(representation)
+    }
+}
+
+pub struct Deadline<P, T: Timer> {
+    protocol: P,
+    duration: Duration,
+    expiry: Option<T::Delay>,
+}
+
+impl<P, T: Timer> Deadline<P, T> {
+    pub fn new(protocol: P, duration: Duration) -> Self {
+        Deadline {
+            protocol,
+            duration,
+            expiry: None,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn get_ref(&self) -> &P {
+        &self.protocol
+    }
+
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.protocol
+    }
+
+    pub fn into_inner(self) -> P {
+        self.protocol
+    }
+}
+
+impl<P, T: Timer + Default> Deadline<P, T> {
+    pub fn into_parts(self) -> (P, T::Delay) {
+        let duration = self.duration;
+        let expiry = self
+            .expiry
+            .unwrap_or_else(|| T::default().delay(duration));
+        (self.protocol, expiry)
+    }
+}
+
+type Tag<C> = Expiry<<C as Dispatch>::Handle>;
+
+pub enum Coalesce<
+    C: Channels<Tag<C>, Bottom> + Pass<P, F>,
+    P: Unpin + Protocol<F, <C as Spawn<P, F>>::Target> + Protocol<F, <C as Join<P, F>>::Target>,
+    T: Timer,
+    F: ?Sized,
+> {
+    Next(StreamFuture<IntoStream<C::Coalesce>>),
+    Join(Duration, <C as Join<P, F>>::Output, Option<T::Delay>),
+}
+
+pub enum Unravel<
+    C: Channels<Tag<C>, Bottom> + Pass<P, F>,
+    P: Unpin + Protocol<F, <C as Spawn<P, F>>::Target> + Protocol<F, <C as Join<P, F>>::Target>,
+    F: ?Sized,
+> {
+    Spawn(Option<C::Unravel>, u64, <C as Spawn<P, F>>::Output),
+    Send(Forward<Once<Ready<Result<Tag<C>, <C::Unravel as Sink<Tag<C>>>::Error>>>, C::Unravel>),
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<P, F>,
+        P: Unpin + Protocol<F, <C as Spawn<P, F>>::Target> + Protocol<F, <C as Join<P, F>>::Target>,
+        T: Timer,
+    > Coalesce<C, P, T, F>
+where
+    C::Coalesce: Unpin,
+{
+    fn new(channel: C::Coalesce) -> Self {
+        Coalesce::Next(channel.into_stream().into_future())
+    }
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<P, F>,
+        P: Unpin + Protocol<F, <C as Spawn<P, F>>::Target> + Protocol<F, <C as Join<P, F>>::Target>,
+    > Unravel<C, P, F>
+{
+    fn new(mut channel: C::Unravel, item: P, duration: Duration) -> Self {
+        let millis = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+        let spawn = channel.spawn(item);
+        Unravel::Spawn(Some(channel), millis, spawn)
+    }
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<P, F>,
+        P: Unpin + Protocol<F, <C as Spawn<P, F>>::Target> + Protocol<F, <C as Join<P, F>>::Target>,
+        T: Timer + Default,
+    > Future for Coalesce<C, P, T, F>
+where
+    <C as Dispatch>::Handle: Unpin,
+    <C as Join<P, F>>::Output: Unpin,
+    C::Coalesce: Unpin,
+{
+    type Output = Result<
+        Deadline<P, T>,
+        Error<
+            ContextError<
+                <C as Join<P, F>>::Error,
+                <<P as Protocol<F, <C as Join<P, F>>::Target>>::CoalesceFuture as TryFuture>::Error,
+            >,
+            <C::Coalesce as TryStream>::Error,
+        >,
+    >;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        loop {
+            match &mut *self {
+                Coalesce::Next(next) => {
+                    pin_mut!(next);
+                    let expiry = ready!(next.poll(ctx));
+                    let (expiry, channel) = match expiry {
+                        (Some(expiry), channel) => (expiry, channel),
+                        (None, _) => return Poll::Ready(Err(Error::Terminated)),
+                    };
+                    let expiry = expiry.map_err(Error::Channel)?;
+                    let duration = Duration::from_millis(expiry.millis);
+                    let join = channel.into_inner().join(expiry.handle);
+                    let replacement =
+                        Coalesce::Join(duration, join, Some(T::default().delay(duration)));
+                    self.set(replacement);
+                }
+                Coalesce::Join(duration, join, expiry) => {
+                    if let Poll::Ready(output) = Pin::new(join).poll(ctx) {
+                        let protocol = output.map_err(Error::Protocol)?;
+                        return Poll::Ready(Ok(Deadline {
+                            protocol,
+                            duration: *duration,
+                            expiry: expiry.take(),
+                        }));
+                    }
+                    let delay = expiry
+                        .as_mut()
+                        .expect("Deadline polled after completion");
+                    ready!(Pin::new(delay).poll(ctx));
+                    return Poll::Ready(Err(Error::Elapsed));
+                }
+            };
+        }
+    }
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<P, F>,
+        P: Unpin + Protocol<F, <C as Spawn<P, F>>::Target> + Protocol<F, <C as Join<P, F>>::Target>,
+    > Future for Unravel<C, P, F>
+where
+    <C as Dispatch>::Handle: Unpin,
+    <C as Spawn<P, F>>::Output: Unpin,
+    C::Unravel: Unpin,
+{
+    type Output = Result<
+        (),
+        Error<
+            ContextError<
+                <C as Spawn<P, F>>::Error,
+                <<P as Protocol<F, <C as Spawn<P, F>>::Target>>::UnravelFuture as TryFuture>::Error,
+            >,
+            <C::Unravel as Sink<Tag<C>>>::Error,
+        >,
+    >;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        loop {
+            match &mut *self {
+                Unravel::Spawn(channel, millis, item) => {
+                    let handle = ready!(Pin::new(item).poll(ctx)).map_err(Error::Protocol)?;
+                    let expiry = Expiry::new(*millis, handle);
+                    let replacement =
+                        Unravel::Send(once(ready(Ok(expiry))).forward(channel.take().expect(
+                            "violated invariant in Protocol for Deadline: no channel in Spawn stage",
+                        )));
+                    self.set(replacement);
+                }
+                Unravel::Send(send) => {
+                    pin_mut!(send);
+                    return Poll::Ready(ready!(send.poll(ctx)).map_err(Error::Channel));
+                }
+            };
+        }
+    }
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<P, F>,
+        P: Unpin + Protocol<F, <C as Spawn<P, F>>::Target> + Protocol<F, <C as Join<P, F>>::Target>,
+        T: Timer + Default,
+    > Protocol<F, C> for Deadline<P, T>
+where
+    C::Handle: Unpin,
+    <C as Spawn<P, F>>::Output: Unpin,
+    <C as Join<P, F>>::Output: Unpin,
+    <C as Channels<Tag<C>, Bottom>>::Coalesce: Unpin,
+    <C as Channels<Tag<C>, Bottom>>::Unravel: Unpin,
+{
+    type Unravel = Tag<C>;
+    type UnravelError = <Unravel<C, P, F> as TryFuture>::Error;
+    type UnravelFuture = Unravel<C, P, F>;
+    type Coalesce = Bottom;
+    type CoalesceError = <Coalesce<C, P, T, F> as TryFuture>::Error;
+    type CoalesceFuture = Coalesce<C, P, T, F>;
+
+    fn unravel(self, channel: <C as Channels<Tag<C>, Bottom>>::Unravel) -> Self::UnravelFuture {
+        Unravel::new(channel, self.protocol, self.duration)
+    }
+
+    fn coalesce(channel: <C as Channels<Tag<C>, Bottom>>::Coalesce) -> Self::CoalesceFuture {
+        Coalesce::new(channel)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::{Deadline, Error, Expiry};
+    use crate::{
+        director::{Director, DirectorError, Frame, Handle, Mux, Timer},
+        format::Null,
+        transport::pipe::{channel, Channel},
+        Bottom, Channels, Protocol,
+    };
+    use core::time::Duration;
+    use futures::{
+        executor::block_on,
+        future::{pending, ready, select, Either, Pending, Ready},
+    };
+    use void::Void;
+
+    type Transport = Channel<Frame<Expiry<Handle>>>;
+
+    #[derive(Default)]
+    struct Never;
+
+    impl Timer for Never {
+        type Delay = Pending<()>;
+
+        fn delay(&mut self, _: Duration) -> Pending<()> {
+            pending()
+        }
+    }
+
+    #[derive(Default)]
+    struct Expired;
+
+    impl Timer for Expired {
+        type Delay = Ready<()>;
+
+        fn delay(&mut self, _: Duration) -> Ready<()> {
+            ready(())
+        }
+    }
+
+    struct Stall;
+
+    impl<F: ?Sized, C> Protocol<F, C> for Stall {
+        type Unravel = Bottom;
+        type UnravelError = Void;
+        type UnravelFuture = Ready<Result<(), Void>>;
+        type Coalesce = Bottom;
+        type CoalesceError = Void;
+        type CoalesceFuture = Pending<Result<Stall, Void>>;
+
+        fn unravel(self, _: C::Unravel) -> Self::UnravelFuture
+        where
+            C: Channels<Self::Unravel, Self::Coalesce>,
+        {
+            ready(Ok(()))
+        }
+
+        fn coalesce(_: C::Coalesce) -> Self::CoalesceFuture
+        where
+            C: Channels<Self::Unravel, Self::Coalesce>,
+        {
+            pending()
+        }
+    }
+
+    #[test]
+    fn transmits_duration() {
+        let (left, right) = channel();
+        let deadline = Deadline::<(), Never>::new((), Duration::from_millis(1500));
+        let unravel = <Mux as Director<_, Null, Transport>>::unravel(Mux::new(), deadline, left);
+        let coalesce =
+            <Mux as Director<Deadline<(), Never>, Null, Transport>>::coalesce(Mux::new(), right);
+        let coalesced = match block_on(select(unravel, coalesce)) {
+            Either::Left((unravelled, coalesce)) => {
+                unravelled.unwrap();
+                block_on(coalesce)
+            }
+            Either::Right((coalesced, _)) => coalesced,
+        };
+        assert_eq!(coalesced.unwrap().duration(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn abandons_expired_joins() {
+        let (left, right) = channel();
+        let deadline = Deadline::<Stall, Expired>::new(Stall, Duration::from_millis(10));
+        let unravel = <Mux as Director<_, Null, Transport>>::unravel(Mux::new(), deadline, left);
+        let coalesce = <Mux as Director<Deadline<Stall, Expired>, Null, Transport>>::coalesce(
+            Mux::new(),
+            right,
+        );
+        let coalesced = match block_on(select(unravel, coalesce)) {
+            Either::Left((unravelled, coalesce)) => {
+                unravelled.unwrap();
+                block_on(coalesce)
+            }
+            Either::Right((coalesced, _)) => coalesced,
+        };
+        assert!(matches!(
+            coalesced,
+            Err(DirectorError::Protocol(Error::Elapsed))
+        ));
+    }
+}
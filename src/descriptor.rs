@@ -0,0 +1,266 @@
+use crate::{
+    format::{ByteFormat, Format},
+    transport::Descriptors,
+    Bottom, Channels, Protocol,
+};
+use core::{
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::{
+    future::{ready, Ready},
+    ready,
+    stream::{once, Forward, IntoStream, Once, StreamFuture},
+    Sink, Stream, StreamExt, TryStream, TryStreamExt,
+};
+use std::{fs::File, os::unix::io::OwnedFd};
+
+#[derive(Debug)]
+pub enum Error<Channel> {
+    Channel(Channel),
+    Terminated,
+}
+
+#[derive(Debug)]
+pub struct Descriptor(OwnedFd);
+
+impl Descriptor {
+    pub fn new(descriptor: OwnedFd) -> Self {
+        Descriptor(descriptor)
+    }
+
+    pub fn into_inner(self) -> OwnedFd {
+        self.0
+    }
+
+    pub fn attach<S: Descriptors>(self, transport: &mut S) -> u32 {
+        transport.attach(self.0)
+    }
+
+    pub fn detach<S: Descriptors>(index: u32, transport: &mut S) -> Option<Self> {
+        transport.detach(index).map(Descriptor)
+    }
+}
+
+pub struct Unravel<C: Sink<Descriptor>>(
+    Forward<Once<Ready<Result<Descriptor, C::Error>>>, C>,
+);
+
+impl<C: Sink<Descriptor> + Unpin> Future for Unravel<C> {
+    type Output = Result<(), Error<C::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(ctx).map_err(Error::Channel)
+    }
+}
+
+pub struct Coalesce<C: TryStream<Ok = Descriptor>, T> {
+    next: StreamFuture<IntoStream<C>>,
+    data: PhantomData<fn() -> T>,
+}
+
+impl<C: TryStream<Ok = Descriptor> + Unpin, T: From<OwnedFd>> Future for Coalesce<C, T> {
+    type Output = Result<T, Error<C::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        match ready!(Pin::new(&mut self.next).poll(ctx)).0 {
+            Some(item) => Poll::Ready(
+                item.map(|descriptor| descriptor.0.into())
+                    .map_err(Error::Channel),
+            ),
+            None => Poll::Ready(Err(Error::Terminated)),
+        }
+    }
+}
+
+fn unravel<C: Sink<Descriptor> + Unpin>(descriptor: OwnedFd, channel: C) -> Unravel<C> {
+    Unravel(once(ready(Ok(Descriptor(descriptor)))).forward(channel))
+}
+
+fn coalesce<C: TryStream<Ok = Descriptor> + Unpin, T>(channel: C) -> Coalesce<C, T> {
+    Coalesce {
+        next: channel.into_stream().into_future(),
+        data: PhantomData,
+    }
+}
+
+impl<C: Channels<Descriptor, Bottom>, F: ?Sized> Protocol<F, C> for OwnedFd
+where
+    C::Unravel: Unpin,
+    C::Coalesce: Unpin,
+{
+    type Unravel = Descriptor;
+    type UnravelError = Error<<C::Unravel as Sink<Descriptor>>::Error>;
+    type UnravelFuture = Unravel<C::Unravel>;
+    type Coalesce = Bottom;
+    type CoalesceError = Error<<C::Coalesce as TryStream>::Error>;
+    type CoalesceFuture = Coalesce<C::Coalesce, OwnedFd>;
+
+    fn unravel(self, channel: C::Unravel) -> Self::UnravelFuture {
+        unravel(self, channel)
+    }
+
+    fn coalesce(channel: C::Coalesce) -> Self::CoalesceFuture {
+        coalesce(channel)
+    }
+}
+
+impl<C: Channels<Descriptor, Bottom>, F: ?Sized> Protocol<F, C> for File
+where
+    C::Unravel: Unpin,
+    C::Coalesce: Unpin,
+{
+    type Unravel = Descriptor;
+    type UnravelError = Error<<C::Unravel as Sink<Descriptor>>::Error>;
+    type UnravelFuture = Unravel<C::Unravel>;
+    type Coalesce = Bottom;
+    type CoalesceError = Error<<C::Coalesce as TryStream>::Error>;
+    type CoalesceFuture = Coalesce<C::Coalesce, File>;
+
+    fn unravel(self, channel: C::Unravel) -> Self::UnravelFuture {
+        unravel(self.into(), channel)
+    }
+
+    fn coalesce(channel: C::Coalesce) -> Self::CoalesceFuture {
+        coalesce(channel)
+    }
+}
+
+pub enum IndexError<S: AsyncRead + AsyncWrite> {
+    Read(S::Error),
+    Write(S::WriteError),
+    Flush(S::FlushError),
+    Close(S::CloseError),
+    Missing(u32),
+    Terminated,
+}
+
+impl<S: AsyncRead + AsyncWrite> Debug for IndexError<S>
+where
+    S::Error: Debug,
+    S::WriteError: Debug,
+    S::FlushError: Debug,
+    S::CloseError: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            IndexError::Read(e) => f.debug_tuple("Read").field(e).finish(),
+            IndexError::Write(e) => f.debug_tuple("Write").field(e).finish(),
+            IndexError::Flush(e) => f.debug_tuple("Flush").field(e).finish(),
+            IndexError::Close(e) => f.debug_tuple("Close").field(e).finish(),
+            IndexError::Missing(index) => f.debug_tuple("Missing").field(index).finish(),
+            IndexError::Terminated => f.write_str("Terminated"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Indexed;
+
+impl Format<Descriptor> for Indexed {}
+
+impl<S: AsyncRead + AsyncWrite + Descriptors + Unpin> ByteFormat<Descriptor, S> for Indexed {
+    type Output = Indexing<S>;
+
+    fn wire(self, transport: S) -> Indexing<S> {
+        Indexing {
+            stream: transport,
+            read: [0; 4],
+            filled: 0,
+            write: Vec::new(),
+        }
+    }
+}
+
+pub struct Indexing<S> {
+    stream: S,
+    read: [u8; 4],
+    filled: usize,
+    write: Vec<u8>,
+}
+
+impl<S> Indexing<S> {
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Descriptors + Unpin> Indexing<S> {
+    fn poll_write(&mut self, ctx: &mut Context) -> Poll<Result<(), IndexError<S>>> {
+        while !self.write.is_empty() {
+            let count = ready!(Pin::new(&mut self.stream).poll_write(ctx, &self.write))
+                .map_err(IndexError::Write)?;
+            if count == 0 {
+                return Poll::Ready(Err(IndexError::Terminated));
+            }
+            self.write.drain(..count);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Descriptors + Unpin> Stream for Indexing<S> {
+    type Item = Result<Descriptor, IndexError<S>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        while this.filled < this.read.len() {
+            let count = match ready!(
+                Pin::new(&mut this.stream).poll_read(ctx, &mut this.read[this.filled..])
+            ) {
+                Ok(count) => count,
+                Err(e) => return Poll::Ready(Some(Err(IndexError::Read(e)))),
+            };
+            if count == 0 {
+                return Poll::Ready(if this.filled == 0 {
+                    None
+                } else {
+                    Some(Err(IndexError::Terminated))
+                });
+            }
+            this.filled += count;
+        }
+        this.filled = 0;
+        let index = u32::from_le_bytes(this.read);
+        Poll::Ready(Some(
+            Descriptor::detach(index, &mut this.stream).ok_or(IndexError::Missing(index)),
+        ))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Descriptors + Unpin> Sink<Descriptor> for Indexing<S> {
+    type Error = IndexError<S>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_write(ctx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Descriptor) -> Result<(), Self::Error> {
+        let this = &mut *self;
+        let index = item.attach(&mut this.stream);
+        this.write.extend_from_slice(&index.to_le_bytes());
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_write(ctx))?;
+        Pin::new(&mut self.stream)
+            .poll_flush(ctx)
+            .map_err(IndexError::Flush)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_flush(ctx))?;
+        Pin::new(&mut self.stream)
+            .poll_close(ctx)
+            .map_err(IndexError::Close)
+    }
+}
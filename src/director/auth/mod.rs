@@ -0,0 +1,244 @@
+use super::{Director, DirectorError};
+use crate::Protocol;
+use alloc::rc::Rc;
+use core::{
+    cell::RefCell,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{ready, TryFuture};
+
+#[cfg(feature = "psk")]
+pub mod psk;
+#[cfg(feature = "psk")]
+pub use psk::Psk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Unravel,
+    Coalesce,
+}
+
+pub trait Handshake<T> {
+    type Identity;
+    type Error;
+    type Future: Future<Output = Result<(T, Self::Identity), Self::Error>>;
+
+    fn handshake(self, transport: T, role: Role) -> Self::Future;
+}
+
+#[derive(Debug)]
+pub enum Error<Handshake, Director> {
+    Handshake(Handshake),
+    Director(Director),
+}
+
+fn lift<H, T, U>(error: DirectorError<T, U>) -> DirectorError<Error<H, T>, U> {
+    match error {
+        DirectorError::Director(e) => DirectorError::Director(Error::Director(e)),
+        DirectorError::Protocol(e) => DirectorError::Protocol(e),
+        DirectorError::Dead => DirectorError::Dead,
+    }
+}
+
+pub struct Peer<I>(Rc<RefCell<Option<I>>>);
+
+impl<I> Peer<I> {
+    pub fn new() -> Self {
+        Peer(Rc::new(RefCell::new(None)))
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.0.borrow().is_some()
+    }
+
+    pub fn identity(&self) -> Option<I>
+    where
+        I: Clone,
+    {
+        self.0.borrow().clone()
+    }
+
+    fn set(&self, identity: I) {
+        *self.0.borrow_mut() = Some(identity);
+    }
+}
+
+impl<I> Clone for Peer<I> {
+    fn clone(&self) -> Self {
+        Peer(self.0.clone())
+    }
+}
+
+impl<I> Default for Peer<I> {
+    fn default() -> Self {
+        Peer::new()
+    }
+}
+
+pub struct Unravel<
+    T,
+    H: Handshake<T>,
+    D: Director<P, F, T>,
+    P: Protocol<F, D::Context>,
+    F: ?Sized,
+> {
+    handshake: H::Future,
+    pending: Option<(D, P, Peer<H::Identity>)>,
+    future: Option<D::Unravel>,
+    data: PhantomData<fn(T, &F)>,
+}
+
+pub struct Coalesce<
+    T,
+    H: Handshake<T>,
+    D: Director<P, F, T>,
+    P: Protocol<F, D::Context>,
+    F: ?Sized,
+> {
+    handshake: H::Future,
+    pending: Option<(D, Peer<H::Identity>)>,
+    future: Option<D::Coalesce>,
+    data: PhantomData<fn(T, &F)>,
+}
+
+impl<T, H: Handshake<T>, D: Director<P, F, T>, P: Protocol<F, D::Context>, F: ?Sized> Unpin
+    for Unravel<T, H, D, P, F>
+where
+    H::Future: Unpin,
+    D::Unravel: Unpin,
+{
+}
+
+impl<T, H: Handshake<T>, D: Director<P, F, T>, P: Protocol<F, D::Context>, F: ?Sized> Unpin
+    for Coalesce<T, H, D, P, F>
+where
+    H::Future: Unpin,
+    D::Coalesce: Unpin,
+{
+}
+
+impl<T, H: Handshake<T>, D: Director<P, F, T>, P: Protocol<F, D::Context>, F: ?Sized> Future
+    for Unravel<T, H, D, P, F>
+where
+    H::Future: Unpin,
+    D::Unravel: Unpin,
+{
+    type Output = Result<
+        (),
+        DirectorError<
+            Error<H::Error, D::UnravelError>,
+            <P::UnravelFuture as TryFuture>::Error,
+        >,
+    >;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            if let Some(future) = &mut this.future {
+                return Pin::new(future).poll(ctx).map_err(lift);
+            }
+            let (transport, identity) = ready!(Pin::new(&mut this.handshake).poll(ctx))
+                .map_err(|e| DirectorError::Director(Error::Handshake(e)))?;
+            let (director, protocol, peer) = this
+                .pending
+                .take()
+                .expect("Authenticated polled after completion");
+            peer.set(identity);
+            this.future = Some(director.unravel(protocol, transport));
+        }
+    }
+}
+
+impl<T, H: Handshake<T>, D: Director<P, F, T>, P: Protocol<F, D::Context>, F: ?Sized> Future
+    for Coalesce<T, H, D, P, F>
+where
+    H::Future: Unpin,
+    D::Coalesce: Unpin,
+{
+    type Output = Result<
+        P,
+        DirectorError<
+            Error<H::Error, D::CoalesceError>,
+            <P::CoalesceFuture as TryFuture>::Error,
+        >,
+    >;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            if let Some(future) = &mut this.future {
+                return Pin::new(future).poll(ctx).map_err(lift);
+            }
+            let (transport, identity) = ready!(Pin::new(&mut this.handshake).poll(ctx))
+                .map_err(|e| DirectorError::Director(Error::Handshake(e)))?;
+            let (director, peer) = this
+                .pending
+                .take()
+                .expect("Authenticated polled after completion");
+            peer.set(identity);
+            this.future = Some(director.coalesce(transport));
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Authenticated<D, H, I> {
+    director: D,
+    handshake: H,
+    peer: Peer<I>,
+}
+
+impl<D, H, I> Authenticated<D, H, I> {
+    pub fn new(director: D, handshake: H) -> Self {
+        Authenticated {
+            director,
+            handshake,
+            peer: Peer::new(),
+        }
+    }
+
+    pub fn peer(&self) -> Peer<I> {
+        self.peer.clone()
+    }
+}
+
+impl<
+        F: ?Sized,
+        P: Protocol<F, D::Context>,
+        T,
+        I,
+        H: Handshake<T, Identity = I>,
+        D: Director<P, F, T>,
+    > Director<P, F, T> for Authenticated<D, H, I>
+where
+    H::Future: Unpin,
+    D::Unravel: Unpin,
+    D::Coalesce: Unpin,
+{
+    type Context = D::Context;
+    type UnravelError = Error<H::Error, D::UnravelError>;
+    type Unravel = Unravel<T, H, D, P, F>;
+    type CoalesceError = Error<H::Error, D::CoalesceError>;
+    type Coalesce = Coalesce<T, H, D, P, F>;
+
+    fn unravel(self, protocol: P, transport: T) -> Self::Unravel {
+        Unravel {
+            handshake: self.handshake.handshake(transport, Role::Unravel),
+            pending: Some((self.director, protocol, self.peer)),
+            future: None,
+            data: PhantomData,
+        }
+    }
+
+    fn coalesce(self, transport: T) -> Self::Coalesce {
+        Coalesce {
+            handshake: self.handshake.handshake(transport, Role::Coalesce),
+            pending: Some((self.director, self.peer)),
+            future: None,
+            data: PhantomData,
+        }
+    }
+}
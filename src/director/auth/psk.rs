@@ -0,0 +1,269 @@
+use super::{Handshake, Role};
+use crate::director::Frame;
+use core::{
+    future::Future,
+    mem::replace,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{ready, Sink, TryStream};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+#[derive(Debug)]
+pub enum Error<Stream, Sink> {
+    Stream(Stream),
+    Sink(Sink),
+    Rejected,
+    Unexpected,
+    Terminated,
+}
+
+#[derive(Clone, Copy)]
+pub struct Psk<K = fn(u64) -> Option<[u8; 32]>> {
+    id: u64,
+    key: [u8; 32],
+    keyring: K,
+    entropy: fn() -> u64,
+}
+
+impl<K: Fn(u64) -> Option<[u8; 32]>> Psk<K> {
+    pub fn new(id: u64, key: [u8; 32], keyring: K, entropy: fn() -> u64) -> Self {
+        Psk {
+            id,
+            key,
+            keyring,
+            entropy,
+        }
+    }
+}
+
+fn tag(
+    key: &[u8; 32],
+    role: Role,
+    first: &[u8; 16],
+    second: &[u8; 16],
+    responder: u64,
+    challenger: u64,
+) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+        .expect("violated invariant in Psk: rejected fixed-size key");
+    mac.update(&[role as u8]);
+    mac.update(first);
+    mac.update(second);
+    mac.update(&responder.to_le_bytes());
+    mac.update(&challenger.to_le_bytes());
+    mac
+}
+
+pub struct Challenge<T, R, K> {
+    psk: Psk<K>,
+    role: Role,
+    transport: Option<T>,
+    nonce: [u8; 16],
+    outbound: Option<Frame<R>>,
+    flush: bool,
+    peer: Option<(u64, [u8; 16], [u8; 32])>,
+    responded: bool,
+    verified: bool,
+}
+
+impl<T: Unpin, R, K> Unpin for Challenge<T, R, K> {}
+
+impl<R, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, K: Fn(u64) -> Option<[u8; 32]>>
+    Challenge<T, R, K>
+{
+    fn poll_send(
+        &mut self,
+        ctx: &mut Context,
+    ) -> Poll<Result<(), Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>>> {
+        let transport = self
+            .transport
+            .as_mut()
+            .expect("Challenge polled after completion");
+        if self.outbound.is_some() {
+            ready!(Pin::new(&mut *transport).poll_ready(ctx)).map_err(Error::Sink)?;
+            if let Some(frame) = self.outbound.take() {
+                Pin::new(&mut *transport)
+                    .start_send(frame)
+                    .map_err(Error::Sink)?;
+            }
+            self.flush = true;
+        }
+        if self.flush {
+            ready!(Pin::new(&mut *transport).poll_flush(ctx)).map_err(Error::Sink)?;
+            self.flush = false;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn receive(
+        &mut self,
+        frame: Frame<R>,
+    ) -> Result<(), Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>> {
+        let peer_role = match self.role {
+            Role::Unravel => Role::Coalesce,
+            Role::Coalesce => Role::Unravel,
+        };
+        match (frame, self.peer) {
+            (Frame::Challenge(id, nonce), None) => {
+                if nonce == self.nonce {
+                    return Err(Error::Rejected);
+                }
+                let key = (self.psk.keyring)(id).ok_or(Error::Rejected)?;
+                let tag = tag(&self.psk.key, self.role, &nonce, &self.nonce, self.psk.id, id)
+                    .finalize()
+                    .into_bytes();
+                let mut response = [0; 32];
+                response.copy_from_slice(&tag);
+                self.outbound = Some(Frame::Response(response));
+                self.peer = Some((id, nonce, key));
+                self.responded = true;
+                Ok(())
+            }
+            (Frame::Response(response), Some((id, nonce, key))) if !self.verified => {
+                tag(&key, peer_role, &self.nonce, &nonce, id, self.psk.id)
+                    .verify_slice(&response)
+                    .map_err(|_| Error::Rejected)?;
+                self.verified = true;
+                Ok(())
+            }
+            _ => Err(Error::Unexpected),
+        }
+    }
+}
+
+impl<R, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, K: Fn(u64) -> Option<[u8; 32]>>
+    Future for Challenge<T, R, K>
+{
+    type Output =
+        Result<(T, u64), Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            ready!(this.poll_send(ctx))?;
+            if let (true, true, Some((id, _, _))) = (this.responded, this.verified, this.peer) {
+                let transport = replace(&mut this.transport, None)
+                    .expect("Challenge polled after completion");
+                return Poll::Ready(Ok((transport, id)));
+            }
+            let transport = this
+                .transport
+                .as_mut()
+                .expect("Challenge polled after completion");
+            match ready!(Pin::new(transport).try_poll_next(ctx)) {
+                Some(Ok(frame)) => this.receive(frame)?,
+                Some(Err(e)) => return Poll::Ready(Err(Error::Stream(e))),
+                None => return Poll::Ready(Err(Error::Terminated)),
+            }
+        }
+    }
+}
+
+impl<R, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, K: Fn(u64) -> Option<[u8; 32]>>
+    Handshake<T> for Psk<K>
+{
+    type Identity = u64;
+    type Error = Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>;
+    type Future = Challenge<T, R, K>;
+
+    fn handshake(self, transport: T, role: Role) -> Challenge<T, R, K> {
+        let mut nonce = [0; 16];
+        nonce[..8].copy_from_slice(&(self.entropy)().to_le_bytes());
+        nonce[8..].copy_from_slice(&(self.entropy)().to_le_bytes());
+        Challenge {
+            outbound: Some(Frame::Challenge(self.id, nonce)),
+            psk: self,
+            role,
+            transport: Some(transport),
+            nonce,
+            flush: false,
+            peer: None,
+            responded: false,
+            verified: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Psk};
+    use crate::{
+        director::{
+            auth::{Handshake, Role},
+            Frame,
+        },
+        transport::pipe::{channel, Channel},
+    };
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use futures::{executor::block_on, future::join};
+
+    const ALICE: [u8; 32] = [1; 32];
+    const BOB: [u8; 32] = [2; 32];
+
+    fn keyring(id: u64) -> Option<[u8; 32]> {
+        match id {
+            1 => Some(ALICE),
+            2 => Some(BOB),
+            _ => None,
+        }
+    }
+
+    fn entropy() -> u64 {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    }
+
+    type Outcome = Result<(Channel<Frame<()>>, u64), Error<void::Void, void::Void>>;
+
+    fn run(unravel: Psk, coalesce: Psk) -> (Outcome, Outcome) {
+        let (left, right) = channel::<Frame<()>>();
+        block_on(join(
+            unravel.handshake(left, Role::Unravel),
+            coalesce.handshake(right, Role::Coalesce),
+        ))
+    }
+
+    #[test]
+    fn authenticates() {
+        let (unravel, coalesce) = run(
+            Psk::new(1, ALICE, keyring, entropy),
+            Psk::new(2, BOB, keyring, entropy),
+        );
+        assert_eq!(unravel.unwrap().1, 2);
+        assert_eq!(coalesce.unwrap().1, 1);
+    }
+
+    #[test]
+    fn rejects_claimed_identity() {
+        let (_, coalesce) = run(
+            Psk::new(1, BOB, keyring, entropy),
+            Psk::new(2, BOB, keyring, entropy),
+        );
+        assert!(matches!(coalesce, Err(Error::Rejected)));
+    }
+
+    #[test]
+    fn rejects_unknown_identity() {
+        let (_, coalesce) = run(
+            Psk::new(3, ALICE, keyring, entropy),
+            Psk::new(2, BOB, keyring, entropy),
+        );
+        assert!(matches!(coalesce, Err(Error::Rejected)));
+    }
+
+    #[test]
+    fn rejects_reflected_nonce() {
+        let (mut left, right) = channel::<Frame<()>>();
+        let psk = Psk::new(2, BOB, keyring, || 7);
+        let reflector = async move {
+            use futures::{SinkExt, StreamExt};
+            if let Some(Ok(Frame::Challenge(_, nonce))) = left.next().await {
+                let _ = left.send(Frame::Challenge(1, nonce)).await;
+            }
+        };
+        let (_, outcome) = block_on(join(reflector, psk.handshake(right, Role::Coalesce)));
+        assert!(matches!(outcome, Err(Error::Rejected)));
+    }
+}
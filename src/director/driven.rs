@@ -0,0 +1,60 @@
+use super::DirectorError;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::TryFuture;
+
+pub struct Driven<H: TryFuture, D> {
+    handle: Option<H>,
+    output: Option<H::Ok>,
+    driver: Option<D>,
+    wait: bool,
+}
+
+impl<H: TryFuture, D> Driven<H, D> {
+    pub(crate) fn new(handle: H, driver: D, wait: bool) -> Self {
+        Driven {
+            handle: Some(handle),
+            output: None,
+            driver: Some(driver),
+            wait,
+        }
+    }
+}
+
+impl<H: TryFuture + Unpin, D: Unpin> Unpin for Driven<H, D> {}
+
+impl<
+        E,
+        P,
+        H: TryFuture<Error = DirectorError<E, P>> + Unpin,
+        D: TryFuture<Ok = (), Error = E> + Unpin,
+    > Future for Driven<H, D>
+{
+    type Output = Result<H::Ok, H::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        if let Some(driver) = &mut self.driver {
+            if let Poll::Ready(output) = Pin::new(driver).try_poll(ctx) {
+                self.driver = None;
+                output.map_err(DirectorError::Director)?;
+            }
+        }
+        if let Some(handle) = &mut self.handle {
+            if let Poll::Ready(output) = Pin::new(handle).try_poll(ctx) {
+                self.handle = None;
+                self.output = Some(output?);
+            }
+        }
+        if self.handle.is_none() && (self.driver.is_none() || !self.wait) {
+            Poll::Ready(Ok(self
+                .output
+                .take()
+                .expect("Driven polled after completion")))
+        } else {
+            Poll::Pending
+        }
+    }
+}
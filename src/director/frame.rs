@@ -0,0 +1,95 @@
+use crate::director::Embed;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Handle(pub(crate) u32);
+
+impl Handle {
+    pub(crate) const ROOT: Handle = Handle(0);
+
+    pub fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+impl<R> Embed<R> for Handle
+where
+    u32: Embed<R>,
+{
+    fn embed(self) -> R {
+        self.0.embed()
+    }
+
+    fn extract(representation: R) -> Result<Self, R> {
+        u32::extract(representation).map(Handle)
+    }
+}
+
+pub trait Token: Sized {
+    fn issue(handle: Handle, token: u64) -> Self;
+
+    fn redeem(self) -> (Handle, u64);
+}
+
+impl Token for Handle {
+    fn issue(handle: Handle, _: u64) -> Self {
+        handle
+    }
+
+    fn redeem(self) -> (Handle, u64) {
+        (self, 0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capability {
+    handle: Handle,
+    token: u64,
+}
+
+impl Capability {
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+}
+
+impl Token for Capability {
+    fn issue(handle: Handle, token: u64) -> Self {
+        Capability { handle, token }
+    }
+
+    fn redeem(self) -> (Handle, u64) {
+        (self.handle, self.token)
+    }
+}
+
+impl<R> Embed<R> for Capability
+where
+    (u32, u64): Embed<R>,
+{
+    fn embed(self) -> R {
+        (self.handle.0, self.token).embed()
+    }
+
+    fn extract(representation: R) -> Result<Self, R> {
+        <(u32, u64)>::extract(representation).map(|(id, token)| Capability {
+            handle: Handle(id),
+            token,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Frame<R> {
+    Item(Handle, R),
+    Window(Handle, u32),
+    Close(Handle),
+    Cancel(Handle),
+    Claim(Handle, u64),
+    GoAway,
+    Ping,
+    Pong,
+    Resume(u64, u64),
+    Ack(u64),
+    Challenge(u64, [u8; 16]),
+    Response([u8; 32]),
+}
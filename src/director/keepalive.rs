@@ -0,0 +1,357 @@
+use super::{Director, DirectorError, Frame, Timer};
+use crate::Protocol;
+use alloc::rc::Rc;
+use core::{
+    cell::Cell,
+    future::Future,
+    mem::replace,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures::{ready, Sink, Stream, TryFuture, TryStream};
+
+#[derive(Debug)]
+pub enum Error<Stream, Sink> {
+    Stream(Stream),
+    Sink(Sink),
+    Dead,
+}
+
+pub struct Heartbeat<T, C: Timer> {
+    transport: T,
+    timer: C,
+    interval: Duration,
+    timeout: Duration,
+    idle: C::Delay,
+    tick: C::Delay,
+    ping: bool,
+    pong: bool,
+    flush: bool,
+    dead: Rc<Cell<bool>>,
+}
+
+impl<T: Unpin, C: Timer> Unpin for Heartbeat<T, C> {}
+
+impl<R, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, C: Timer> Heartbeat<T, C> {
+    fn poll_timers(
+        &mut self,
+        ctx: &mut Context,
+    ) -> Result<(), Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>> {
+        if self.dead.get() || Pin::new(&mut self.idle).poll(ctx).is_ready() {
+            self.dead.set(true);
+            return Err(Error::Dead);
+        }
+        if Pin::new(&mut self.tick).poll(ctx).is_ready() {
+            self.tick = self.timer.delay(self.interval);
+            self.ping = true;
+            ctx.waker().wake_by_ref();
+        }
+        Ok(())
+    }
+
+    fn poll_control(
+        &mut self,
+        ctx: &mut Context,
+    ) -> Poll<Result<(), Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>>> {
+        while self.ping || self.pong {
+            ready!(Pin::new(&mut self.transport).poll_ready(ctx)).map_err(Error::Sink)?;
+            let frame = if replace(&mut self.pong, false) {
+                Frame::Pong
+            } else {
+                self.ping = false;
+                Frame::Ping
+            };
+            Pin::new(&mut self.transport)
+                .start_send(frame)
+                .map_err(Error::Sink)?;
+            self.flush = true;
+        }
+        if self.flush {
+            ready!(Pin::new(&mut self.transport).poll_flush(ctx)).map_err(Error::Sink)?;
+            self.flush = false;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, C: Timer> Stream for Heartbeat<T, C> {
+    type Item = Result<Frame<R>, Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Err(e) = this.poll_timers(ctx) {
+                return Poll::Ready(Some(Err(e)));
+            }
+            if let Poll::Ready(Err(e)) = this.poll_control(ctx) {
+                return Poll::Ready(Some(Err(e)));
+            }
+            match ready!(Pin::new(&mut this.transport).try_poll_next(ctx)) {
+                Some(Ok(frame)) => {
+                    this.idle = this.timer.delay(this.timeout);
+                    match frame {
+                        Frame::Ping => this.pong = true,
+                        Frame::Pong => {}
+                        frame => return Poll::Ready(Some(Ok(frame))),
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(Error::Stream(e)))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl<R, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, C: Timer> Sink<Frame<R>>
+    for Heartbeat<T, C>
+{
+    type Error = Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_control(ctx))?;
+        Pin::new(&mut self.transport)
+            .poll_ready(ctx)
+            .map_err(Error::Sink)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Frame<R>) -> Result<(), Self::Error> {
+        Pin::new(&mut self.transport)
+            .start_send(item)
+            .map_err(Error::Sink)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_control(ctx))?;
+        Pin::new(&mut self.transport)
+            .poll_flush(ctx)
+            .map_err(Error::Sink)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_control(ctx))?;
+        Pin::new(&mut self.transport)
+            .poll_close(ctx)
+            .map_err(Error::Sink)
+    }
+}
+
+pub struct Watch<T> {
+    future: T,
+    dead: Rc<Cell<bool>>,
+}
+
+impl<E, U, T: TryFuture<Error = DirectorError<E, U>> + Unpin> Future for Watch<T> {
+    type Output = Result<T::Ok, T::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let output = ready!(Pin::new(&mut self.future).try_poll(ctx));
+        Poll::Ready(output.map_err(|e| match e {
+            DirectorError::Director(_) if self.dead.get() => DirectorError::Dead,
+            e => e,
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keepalive<D, C> {
+    director: D,
+    timer: C,
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl<D, C: Timer> Keepalive<D, C> {
+    pub fn new(director: D, timer: C) -> Self {
+        Keepalive {
+            director,
+            timer,
+            interval: Duration::from_secs(15),
+            timeout: Duration::from_secs(45),
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn wrap<T>(mut self, transport: T) -> (D, Heartbeat<T, C>, Rc<Cell<bool>>) {
+        let dead = Rc::new(Cell::new(false));
+        let heartbeat = Heartbeat {
+            transport,
+            idle: self.timer.delay(self.timeout),
+            tick: self.timer.delay(self.interval),
+            timer: self.timer,
+            interval: self.interval,
+            timeout: self.timeout,
+            ping: false,
+            pong: false,
+            flush: false,
+            dead: dead.clone(),
+        };
+        (self.director, heartbeat, dead)
+    }
+}
+
+impl<
+        F: ?Sized,
+        P: Protocol<F, D::Context>,
+        R,
+        T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>,
+        C: Timer,
+        D: Director<P, F, Heartbeat<T, C>>,
+    > Director<P, F, T> for Keepalive<D, C>
+where
+    D::Unravel: Unpin,
+    D::Coalesce: Unpin,
+{
+    type Context = D::Context;
+    type UnravelError = D::UnravelError;
+    type Unravel = Watch<D::Unravel>;
+    type CoalesceError = D::CoalesceError;
+    type Coalesce = Watch<D::Coalesce>;
+
+    fn unravel(self, protocol: P, transport: T) -> Self::Unravel {
+        let (director, heartbeat, dead) = self.wrap(transport);
+        Watch {
+            future: director.unravel(protocol, heartbeat),
+            dead,
+        }
+    }
+
+    fn coalesce(self, transport: T) -> Self::Coalesce {
+        let (director, heartbeat, dead) = self.wrap(transport);
+        Watch {
+            future: director.coalesce(heartbeat),
+            dead,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Heartbeat, Keepalive};
+    use crate::{
+        director::{Director, DirectorError, Frame, Handle, Mux, Timer},
+        format::Null,
+        transport::pipe::{channel, Channel},
+    };
+    use alloc::rc::Rc;
+    use core::{
+        cell::Cell,
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+        time::Duration,
+    };
+    use futures::{executor::block_on, FutureExt, SinkExt, StreamExt};
+
+    type Transport = Channel<Frame<u32>>;
+
+    #[derive(Clone, Default)]
+    struct Clock(Rc<Cell<Duration>>);
+
+    impl Clock {
+        fn advance(&self, duration: Duration) {
+            self.0.set(self.0.get() + duration);
+        }
+    }
+
+    struct Delay(Rc<Cell<Duration>>, Duration);
+
+    impl Future for Delay {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<()> {
+            if self.0.get() >= self.1 {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+
+    impl Timer for Clock {
+        type Delay = Delay;
+
+        fn delay(&mut self, duration: Duration) -> Delay {
+            Delay(self.0.clone(), self.0.get() + duration)
+        }
+    }
+
+    fn heartbeat(clock: &Clock) -> (Heartbeat<Transport, Clock>, Transport) {
+        let (left, right) = channel();
+        let keepalive = Keepalive::new((), clock.clone())
+            .with_interval(Duration::from_secs(1))
+            .with_timeout(Duration::from_secs(3));
+        let (_, heartbeat, _) = keepalive.wrap(left);
+        (heartbeat, right)
+    }
+
+    #[test]
+    fn answers_pings() {
+        let clock = Clock::default();
+        let (mut heartbeat, mut peer) = heartbeat(&clock);
+        block_on(peer.send(Frame::Ping)).unwrap();
+        block_on(peer.send(Frame::Item(Handle::ROOT, 1))).unwrap();
+        assert!(matches!(
+            heartbeat.next().now_or_never(),
+            Some(Some(Ok(Frame::Item(_, 1))))
+        ));
+        assert!(matches!(
+            peer.next().now_or_never(),
+            Some(Some(Ok(Frame::Pong)))
+        ));
+    }
+
+    #[test]
+    fn pings_every_interval() {
+        let clock = Clock::default();
+        let (mut heartbeat, mut peer) = heartbeat(&clock);
+        assert!(heartbeat.next().now_or_never().is_none());
+        assert!(peer.next().now_or_never().is_none());
+        clock.advance(Duration::from_secs(1));
+        assert!(heartbeat.next().now_or_never().is_none());
+        assert!(matches!(
+            peer.next().now_or_never(),
+            Some(Some(Ok(Frame::Ping)))
+        ));
+    }
+
+    #[test]
+    fn traffic_defers_idle_timeout() {
+        let clock = Clock::default();
+        let (mut heartbeat, mut peer) = heartbeat(&clock);
+        clock.advance(Duration::from_secs(2));
+        block_on(peer.send(Frame::Pong)).unwrap();
+        assert!(heartbeat.next().now_or_never().is_none());
+        clock.advance(Duration::from_secs(2));
+        assert!(heartbeat.next().now_or_never().is_none());
+        clock.advance(Duration::from_secs(1));
+        assert!(matches!(
+            heartbeat.next().now_or_never(),
+            Some(Some(Err(Error::Dead)))
+        ));
+    }
+
+    #[test]
+    fn reports_dead_peers() {
+        let clock = Clock::default();
+        let (left, _right) = channel();
+        let keepalive =
+            Keepalive::new(Mux::new(), clock.clone()).with_timeout(Duration::from_secs(3));
+        let mut coalesce = <_ as Director<Option<()>, Null, Transport>>::coalesce(keepalive, left);
+        assert!((&mut coalesce).now_or_never().is_none());
+        clock.advance(Duration::from_secs(3));
+        assert!(matches!(
+            (&mut coalesce).now_or_never(),
+            Some(Err(DirectorError::Dead))
+        ));
+    }
+}
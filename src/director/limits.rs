@@ -0,0 +1,79 @@
+pub trait Measure<R> {
+    fn measure(&self, item: &R) -> usize;
+}
+
+impl<R> Measure<R> for () {
+    fn measure(&self, _: &R) -> usize {
+        0
+    }
+}
+
+impl<R> Measure<R> for fn(&R) -> usize {
+    fn measure(&self, item: &R) -> usize {
+        self(item)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bytes;
+
+impl<R: AsRef<[u8]>> Measure<R> for Bytes {
+    fn measure(&self, item: &R) -> usize {
+        item.as_ref().len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    channels: Option<usize>,
+    depth: Option<u32>,
+    frame: Option<usize>,
+    buffered: Option<usize>,
+}
+
+impl Limits {
+    pub const fn new() -> Self {
+        Limits {
+            channels: None,
+            depth: None,
+            frame: None,
+            buffered: None,
+        }
+    }
+
+    pub fn with_channels(mut self, channels: usize) -> Self {
+        self.channels = Some(channels);
+        self
+    }
+
+    pub fn with_depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn with_frame_size(mut self, frame: usize) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    pub fn with_buffered(mut self, buffered: usize) -> Self {
+        self.buffered = Some(buffered);
+        self
+    }
+
+    pub fn channels(&self) -> Option<usize> {
+        self.channels
+    }
+
+    pub fn depth(&self) -> Option<u32> {
+        self.depth
+    }
+
+    pub fn frame_size(&self) -> Option<usize> {
+        self.frame
+    }
+
+    pub fn buffered(&self) -> Option<usize> {
+        self.buffered
+    }
+}
@@ -1,17 +1,57 @@
 use crate::{Channels, Protocol};
-use core::future::Future;
-use futures::TryFuture;
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(feature = "alloc")]
+use futures::task::{LocalFutureObj, LocalSpawn, SpawnError};
+use futures::{ready, TryFuture};
 
+#[cfg(feature = "alloc")]
+pub mod auth;
+#[cfg(feature = "alloc")]
+pub use auth::Authenticated;
+mod driven;
+pub use driven::Driven;
+mod frame;
+pub use frame::{Capability, Frame, Handle, Token};
+#[cfg(feature = "alloc")]
+pub mod keepalive;
+#[cfg(feature = "alloc")]
+pub use keepalive::Keepalive;
+mod limits;
+pub use limits::{Bytes, Limits, Measure};
+#[cfg(feature = "alloc")]
+pub mod mux;
+#[cfg(feature = "alloc")]
+pub use mux::Mux;
 mod null;
-pub(crate) use null::Empty;
 pub use null::Null;
-mod trivial;
-pub use trivial::Trivial;
+mod priority;
+pub use priority::{Prioritize, Priority};
+pub mod slab;
+pub use slab::Slab;
+#[cfg(feature = "alloc")]
+pub mod resume;
+#[cfg(feature = "alloc")]
+pub use resume::{Resume, Sessions};
+#[cfg(feature = "tokio")]
+pub mod process;
+#[cfg(feature = "tokio")]
+pub use process::Process;
+mod timer;
+pub use timer::{coalesce_timeout, join_timeout, Timeout, TimeoutError, Timer};
+pub mod trivial;
+pub use trivial::{Embed, Trivial};
 
 #[derive(Debug)]
 pub enum DirectorError<T, U> {
     Director(T),
     Protocol(U),
+    Dead,
 }
 
 pub trait Director<P: Protocol<F, Self::Context>, F: ?Sized, Coalesce, Unravel = Coalesce> {
@@ -35,3 +75,96 @@ pub trait Director<P: Protocol<F, Self::Context>, F: ?Sized, Coalesce, Unravel =
 
     fn coalesce(self, transport: Coalesce) -> Self::Coalesce;
 }
+
+pub struct Detached<T, R> {
+    driver: T,
+    report: Option<R>,
+}
+
+impl<T, R> Detached<T, R> {
+    pub fn new(driver: T, report: R) -> Self {
+        Detached {
+            driver,
+            report: Some(report),
+        }
+    }
+}
+
+impl<E, T: Future<Output = Result<(), E>>, R: FnOnce(E)> Future for Detached<T, R> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<()> {
+        // SAFETY: `driver` is structurally pinned; it is never moved out of `Detached` and
+        // `Detached` has no `Drop` impl. `report` is never pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let driver = unsafe { Pin::new_unchecked(&mut this.driver) };
+        if let Err(e) = ready!(driver.poll(ctx)) {
+            if let Some(report) = this.report.take() {
+                report(e);
+            }
+        }
+        Poll::Ready(())
+    }
+}
+
+pub trait Spawner<T: Future<Output = ()>> {
+    type Error;
+
+    fn spawn(&mut self, future: T) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "alloc")]
+impl<S: LocalSpawn, T: Future<Output = ()> + 'static> Spawner<T> for S {
+    type Error = SpawnError;
+
+    fn spawn(&mut self, future: T) -> Result<(), SpawnError> {
+        self.spawn_local_obj(LocalFutureObj::new(Box::new(future)))
+    }
+}
+
+pub trait Drive<P: Protocol<F, Self::Context>, F: ?Sized, Coalesce, Unravel = Coalesce>:
+    Director<P, F, Coalesce, Unravel> + Sized
+{
+    type DriverError;
+    type Driver: Future<Output = Result<(), Self::DriverError>>;
+    type UnravelHandle: Future<
+        Output = Result<
+            (),
+            DirectorError<Self::UnravelError, <P::UnravelFuture as TryFuture>::Error>,
+        >,
+    >;
+    type CoalesceHandle: Future<
+        Output = Result<
+            P,
+            DirectorError<Self::CoalesceError, <P::CoalesceFuture as TryFuture>::Error>,
+        >,
+    >;
+
+    fn unravel_driven(self, protocol: P, transport: Unravel)
+        -> (Self::UnravelHandle, Self::Driver);
+
+    fn coalesce_driven(self, transport: Coalesce) -> (Self::CoalesceHandle, Self::Driver);
+
+    fn spawn_unravel<R: FnOnce(Self::DriverError), S: Spawner<Detached<Self::Driver, R>>>(
+        self,
+        protocol: P,
+        transport: Unravel,
+        spawner: &mut S,
+        report: R,
+    ) -> Result<Self::UnravelHandle, S::Error> {
+        let (handle, driver) = self.unravel_driven(protocol, transport);
+        spawner.spawn(Detached::new(driver, report))?;
+        Ok(handle)
+    }
+
+    fn spawn_coalesce<R: FnOnce(Self::DriverError), S: Spawner<Detached<Self::Driver, R>>>(
+        self,
+        transport: Coalesce,
+        spawner: &mut S,
+        report: R,
+    ) -> Result<Self::CoalesceHandle, S::Error> {
+        let (handle, driver) = self.coalesce_driven(transport);
+        spawner.spawn(Detached::new(driver, report))?;
+        Ok(handle)
+    }
+}
@@ -0,0 +1,162 @@
+use super::{Error, Frame, Shared, Shutdown, Status};
+use alloc::{boxed::Box, rc::Rc};
+use core::{
+    any::Any,
+    cell::RefCell,
+    future::Future,
+    mem::take,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{Sink, TryStream};
+
+pub struct Driver<T, R> {
+    transport: T,
+    shared: Rc<RefCell<Shared<R>>>,
+}
+
+impl<T, R> Driver<T, R> {
+    pub(super) fn new(transport: T, shared: Rc<RefCell<Shared<R>>>) -> Self {
+        Driver { transport, shared }
+    }
+
+    pub fn shutdown_handle(&self) -> Shutdown<R> {
+        Shutdown::new(self.shared.clone())
+    }
+}
+
+impl<T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, R> Driver<T, R> {
+    fn fail(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+        shared.status = Status::Failed;
+        shared.wake_channels();
+        let attached = shared.attached();
+        if let Some(closing) = &mut shared.closing {
+            closing.aborted.get_or_insert(attached);
+            closing.finish();
+        }
+    }
+
+    fn poll_deadline(&mut self, ctx: &mut Context) -> bool {
+        let mut shared = self.shared.borrow_mut();
+        let closing = match &mut shared.closing {
+            Some(closing) => closing,
+            None => return false,
+        };
+        if closing.aborted.is_some() {
+            return true;
+        }
+        if closing.deadline.as_mut().poll(ctx).is_pending() {
+            return false;
+        }
+        let attached = shared.attached();
+        if let Some(closing) = &mut shared.closing {
+            closing.aborted = Some(attached);
+        }
+        shared.status = Status::Failed;
+        shared.wake_channels();
+        let tasks = take(&mut shared.tasks);
+        drop(shared);
+        drop(tasks);
+        true
+    }
+
+    fn poll_read(
+        &mut self,
+        ctx: &mut Context,
+    ) -> Result<(), Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>> {
+        while self.shared.borrow().status == Status::Open {
+            match Pin::new(&mut self.transport).try_poll_next(ctx) {
+                Poll::Ready(Some(Ok(frame))) => self.shared.borrow_mut().route(frame)?,
+                Poll::Ready(Some(Err(e))) => return Err(Error::Stream(e)),
+                Poll::Ready(None) => {
+                    let mut shared = self.shared.borrow_mut();
+                    shared.status = Status::Terminated;
+                    shared.wake_channels();
+                }
+                Poll::Pending => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_tasks(&mut self, ctx: &mut Context) -> Result<(), Box<dyn Any>> {
+        let mut tasks = take(&mut self.shared.borrow_mut().tasks);
+        let cancelled = take(&mut self.shared.borrow_mut().cancelled);
+        if !cancelled.is_empty() {
+            tasks.retain(|(handle, _)| !cancelled.contains(handle));
+        }
+        let mut failure = None;
+        tasks.retain_mut(|(_, task)| match task.as_mut().poll(ctx) {
+            Poll::Ready(Ok(())) => false,
+            Poll::Ready(Err(e)) => {
+                failure.get_or_insert(e);
+                false
+            }
+            Poll::Pending => true,
+        });
+        let mut shared = self.shared.borrow_mut();
+        if !shared.tasks.is_empty() {
+            ctx.waker().wake_by_ref();
+        }
+        let spawned = take(&mut shared.tasks);
+        tasks.extend(spawned);
+        shared.tasks = tasks;
+        failure.map_or(Ok(()), Err)
+    }
+
+    fn poll_write(&mut self, ctx: &mut Context) -> Poll<Result<(), <T as Sink<Frame<R>>>::Error>> {
+        loop {
+            if self.shared.borrow().outbound.is_empty() {
+                break;
+            }
+            futures::ready!(Pin::new(&mut self.transport).poll_ready(ctx))?;
+            let frame = self.shared.borrow_mut().outbound.pop();
+            if let Some(frame) = frame {
+                Pin::new(&mut self.transport).start_send(frame)?;
+            }
+        }
+        futures::ready!(Pin::new(&mut self.transport).poll_flush(ctx))?;
+        for waker in self.shared.borrow_mut().flushing.drain(..) {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, R> Future for Driver<T, R> {
+    type Output = Result<(), Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        self.shared.borrow_mut().driver = Some(ctx.waker().clone());
+        if let Err(e) = self.poll_read(ctx) {
+            self.fail();
+            return Poll::Ready(Err(e));
+        }
+        if let Err(e) = self.poll_tasks(ctx) {
+            self.fail();
+            return Poll::Ready(Err(Error::Nested(e)));
+        }
+        let aborted = self.poll_deadline(ctx);
+        let written = match self.poll_write(ctx) {
+            Poll::Ready(Ok(())) => true,
+            Poll::Ready(Err(e)) => {
+                self.fail();
+                return Poll::Ready(Err(Error::Sink(e)));
+            }
+            Poll::Pending => false,
+        };
+        let idle = {
+            let shared = self.shared.borrow();
+            shared.live == 0 && shared.tasks.is_empty() && shared.outbound.is_empty()
+        };
+        if written && (idle || aborted) {
+            let closed = futures::ready!(Pin::new(&mut self.transport).poll_close(ctx));
+            if let Some(closing) = &mut self.shared.borrow_mut().closing {
+                closing.finish();
+            }
+            return Poll::Ready(closed.map_err(Error::Sink));
+        }
+        Poll::Pending
+    }
+}
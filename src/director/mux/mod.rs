@@ -0,0 +1,1407 @@
+use super::{
+    Director, DirectorError, Drive, Embed, Limits, Measure, Prioritize, Priority, Token,
+};
+use crate::{Channel, Channels, ContextError, Dispatch, Format, Join, Protocol, Spawn};
+use alloc::{boxed::Box, collections::BTreeMap, collections::VecDeque, rc::Rc, vec::Vec};
+use core::{
+    any::Any,
+    cell::RefCell,
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    marker::PhantomData,
+    mem::replace,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{self, Poll, Waker},
+};
+use futures::{
+    future::{ready, Either, MapErr, Ready},
+    ready, Sink, Stream, TryFuture, TryFutureExt, TryStream,
+};
+
+pub use super::{Capability, Driven, Frame, Handle};
+mod driver;
+pub use driver::Driver;
+mod schedule;
+use schedule::Schedule;
+mod shutdown;
+use shutdown::Closing;
+pub use shutdown::{Drain, Shutdown};
+
+#[derive(Debug)]
+pub enum Error<Stream, Sink> {
+    Stream(Stream),
+    Sink(Sink),
+    Window,
+    Closed,
+    Unknown,
+    Forged,
+    Channels,
+    Oversized,
+    Overflow,
+    Nested(Box<dyn Any>),
+}
+
+#[derive(Debug)]
+pub enum ChannelError {
+    Disconnected,
+    Unexpected,
+    Closed,
+    Cancelled,
+    Shutdown,
+    Unknown,
+    Forged,
+    Channels,
+    Depth,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Open,
+    Terminated,
+    Failed,
+}
+
+type Task = Pin<Box<dyn Future<Output = Result<(), Box<dyn Any>>>>>;
+
+struct Entry<R> {
+    inbound: VecDeque<R>,
+    waker: Option<Waker>,
+    credit: u32,
+    consumed: u32,
+    ready: Option<Waker>,
+    attached: bool,
+    local: bool,
+    remote: bool,
+    cancelled: bool,
+    token: Option<u64>,
+    claimed: bool,
+    joining: bool,
+    abandoned: bool,
+}
+
+impl<R> Entry<R> {
+    fn new(window: Option<u32>) -> Self {
+        Entry {
+            inbound: VecDeque::new(),
+            waker: None,
+            credit: window.unwrap_or(0),
+            consumed: 0,
+            ready: None,
+            attached: false,
+            local: false,
+            remote: false,
+            cancelled: false,
+            token: None,
+            claimed: false,
+            joining: false,
+            abandoned: false,
+        }
+    }
+}
+
+struct Shared<R> {
+    config: Mux,
+    measure: Box<dyn Measure<R>>,
+    buffered: usize,
+    channels: BTreeMap<Handle, Entry<R>>,
+    outbound: Schedule<R>,
+    tasks: Vec<(Handle, Task)>,
+    cancelled: Vec<Handle>,
+    free: Vec<Handle>,
+    next: u32,
+    live: usize,
+    status: Status,
+    closing: Option<Closing>,
+    goaway: bool,
+    driver: Option<Waker>,
+    flushing: Vec<Waker>,
+}
+
+impl<R> Shared<R> {
+    fn new(next: u32, config: Mux, measure: Box<dyn Measure<R>>) -> Self {
+        Shared {
+            config,
+            measure,
+            buffered: 0,
+            channels: BTreeMap::new(),
+            outbound: Schedule::new(),
+            tasks: Vec::new(),
+            cancelled: Vec::new(),
+            free: Vec::new(),
+            next,
+            live: 0,
+            status: Status::Open,
+            closing: None,
+            goaway: false,
+            driver: None,
+            flushing: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self) -> Handle {
+        let outbound = &self.outbound;
+        if let Some(index) = self
+            .free
+            .iter()
+            .position(|handle| !outbound.pending(*handle))
+        {
+            return self.free.swap_remove(index);
+        }
+        let handle = Handle(self.next);
+        self.next += 2;
+        handle
+    }
+
+    fn owned(&self, handle: Handle) -> bool {
+        handle != Handle::ROOT && handle.0 % 2 == self.next % 2
+    }
+
+    fn reclaim(&mut self, handle: Handle) {
+        if let Some(entry) = self.channels.remove(&handle) {
+            let measure = &self.measure;
+            let size: usize = entry.inbound.iter().map(|item| measure.measure(item)).sum();
+            self.buffered -= size;
+        }
+        if self.owned(handle) {
+            self.free.push(handle);
+        }
+    }
+
+    fn admit(&self, handle: Handle) -> Result<(), ChannelError> {
+        match self.channels.get(&handle) {
+            Some(entry) if entry.attached || entry.local => Err(ChannelError::Closed),
+            Some(_) => Ok(()),
+            None if self.owned(handle) => Err(ChannelError::Unknown),
+            None => Ok(()),
+        }
+    }
+
+    fn room(&self, handle: Handle) -> bool {
+        self.channels.contains_key(&handle)
+            || self
+                .config
+                .limits
+                .channels()
+                .map_or(true, |channels| self.channels.len() < channels)
+    }
+
+    fn issue(&mut self, handle: Handle) -> u64 {
+        let token = match &self.config.entropy {
+            Some(entropy) => entropy.generate(),
+            None => return 0,
+        };
+        self.entry(handle).token = Some(token);
+        token
+    }
+
+    fn claim(&mut self, handle: Handle, token: u64, depth: u32) -> Result<(), ChannelError> {
+        if self.config.limits.depth().map_or(false, |limit| depth >= limit) {
+            return Err(ChannelError::Depth);
+        }
+        if !self.room(handle) {
+            return Err(ChannelError::Channels);
+        }
+        if self.config.entropy.is_none() {
+            return self.admit(handle);
+        }
+        if token == 0 || handle == Handle::ROOT || self.owned(handle) {
+            return Err(ChannelError::Forged);
+        }
+        let entry = self.entry(handle);
+        if entry.attached || entry.local || entry.claimed {
+            return Err(ChannelError::Forged);
+        }
+        entry.claimed = true;
+        self.outbound.push(Frame::Claim(handle, token));
+        self.wake_driver();
+        Ok(())
+    }
+
+    fn guard<S, K>(&self, handle: Handle) -> Result<(), Error<S, K>> {
+        if self.config.entropy.is_none() || !self.owned(handle) {
+            return Ok(());
+        }
+        match self.channels.get(&handle) {
+            Some(entry) if entry.claimed => Ok(()),
+            _ => Err(Error::Forged),
+        }
+    }
+
+    fn settle(&mut self, handle: Handle, completed: bool) {
+        let entry = match self.channels.get_mut(&handle) {
+            Some(entry) => entry,
+            None => return,
+        };
+        entry.joining = false;
+        if !replace(&mut entry.abandoned, false) || completed || entry.remote {
+            return;
+        }
+        if self.status != Status::Failed {
+            self.outbound.push(Frame::Cancel(handle));
+            self.wake_driver();
+        }
+    }
+
+    fn draining(&self) -> bool {
+        self.goaway || self.closing.is_some()
+    }
+
+    fn attached(&self) -> Vec<Handle> {
+        self.channels
+            .iter()
+            .filter(|(_, entry)| entry.attached)
+            .map(|(handle, _)| *handle)
+            .collect()
+    }
+
+    fn wake_driver(&mut self) {
+        if let Some(waker) = self.driver.take() {
+            waker.wake();
+        }
+    }
+
+    fn entry(&mut self, handle: Handle) -> &mut Entry<R> {
+        let window = self.config.window;
+        self.channels
+            .entry(handle)
+            .or_insert_with(|| Entry::new(window))
+    }
+
+    fn wake_channels(&mut self) {
+        for entry in self.channels.values_mut() {
+            if let Some(waker) = entry.waker.take() {
+                waker.wake();
+            }
+            if let Some(waker) = entry.ready.take() {
+                waker.wake();
+            }
+        }
+        for waker in self.flushing.drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn route<S, K>(&mut self, frame: Frame<R>) -> Result<(), Error<S, K>> {
+        let window = self.config.window;
+        match frame {
+            Frame::Item(handle, item) => {
+                if self.owned(handle) && !self.channels.contains_key(&handle) {
+                    return Err(Error::Unknown);
+                }
+                self.guard(handle)?;
+                if !self.room(handle) {
+                    return Err(Error::Channels);
+                }
+                let size = self.measure.measure(&item);
+                let limits = self.config.limits;
+                if limits.frame_size().map_or(false, |frame| size > frame) {
+                    return Err(Error::Oversized);
+                }
+                if limits
+                    .buffered()
+                    .map_or(false, |buffered| self.buffered + size > buffered)
+                {
+                    return Err(Error::Overflow);
+                }
+                let entry = self.entry(handle);
+                if entry.remote {
+                    return Err(Error::Closed);
+                }
+                if let Some(window) = window {
+                    if entry.inbound.len() as u64 + entry.consumed as u64 >= window as u64 {
+                        return Err(Error::Window);
+                    }
+                }
+                entry.inbound.push_back(item);
+                if let Some(waker) = entry.waker.take() {
+                    waker.wake();
+                }
+                self.buffered += size;
+            }
+            Frame::Window(handle, credit) => {
+                if self.channels.contains_key(&handle) {
+                    self.guard(handle)?;
+                }
+                if let Some(entry) = self.channels.get_mut(&handle) {
+                    entry.credit = entry.credit.saturating_add(credit);
+                    if let Some(waker) = entry.ready.take() {
+                        waker.wake();
+                    }
+                }
+            }
+            Frame::Close(handle) => {
+                if self.owned(handle) && !self.channels.contains_key(&handle) {
+                    return Err(Error::Unknown);
+                }
+                self.guard(handle)?;
+                if !self.room(handle) {
+                    return Err(Error::Channels);
+                }
+                let entry = self.entry(handle);
+                if entry.remote {
+                    return Err(Error::Closed);
+                }
+                entry.remote = true;
+                if let Some(waker) = entry.waker.take() {
+                    waker.wake();
+                }
+                if entry.local && !entry.attached {
+                    self.reclaim(handle);
+                }
+            }
+            Frame::Cancel(handle) => {
+                if self.channels.contains_key(&handle) {
+                    self.guard(handle)?;
+                }
+                if let Some(entry) = self.channels.get_mut(&handle) {
+                    entry.remote = true;
+                    entry.cancelled = true;
+                    if let Some(waker) = entry.waker.take() {
+                        waker.wake();
+                    }
+                    if let Some(waker) = entry.ready.take() {
+                        waker.wake();
+                    }
+                    if entry.local && !entry.attached {
+                        self.reclaim(handle);
+                    } else {
+                        self.cancelled.push(handle);
+                    }
+                }
+            }
+            Frame::Claim(handle, token) => {
+                if !self.owned(handle) {
+                    return Err(Error::Forged);
+                }
+                match self.channels.get_mut(&handle) {
+                    Some(entry) if entry.token == Some(token) && !entry.claimed => {
+                        entry.claimed = true
+                    }
+                    _ => return Err(Error::Forged),
+                }
+            }
+            Frame::GoAway => self.goaway = true,
+            Frame::Ping => {
+                self.outbound.push(Frame::Pong);
+                self.wake_driver();
+            }
+            Frame::Pong
+            | Frame::Resume(..)
+            | Frame::Ack(..)
+            | Frame::Challenge(..)
+            | Frame::Response(..) => {}
+        }
+        Ok(())
+    }
+}
+
+pub struct Context<R, H = Handle> {
+    shared: Rc<RefCell<Shared<R>>>,
+    priority: Priority,
+    depth: u32,
+    data: PhantomData<fn() -> H>,
+}
+
+impl<R, H> Context<R, H> {
+    fn open<A, B>(&self, handle: Handle) -> Link<R, A, B, H> {
+        let mut shared = self.shared.borrow_mut();
+        shared.entry(handle).attached = true;
+        shared.outbound.assign(handle, self.priority);
+        shared.live += 1;
+        Link {
+            context: Context {
+                shared: self.shared.clone(),
+                priority: self.priority,
+                depth: self.depth + 1,
+                data: PhantomData,
+            },
+            handle,
+            data: PhantomData,
+        }
+    }
+
+    fn open_coalesce<A, B>(&self, handle: Handle) -> Coalesce<R, A, B, H> {
+        let channel = Coalesce(self.open(handle));
+        self.shared.borrow_mut().entry(handle).joining = true;
+        channel
+    }
+}
+
+struct Link<R, A, B, H> {
+    context: Context<R, H>,
+    handle: Handle,
+    data: PhantomData<fn(A, B)>,
+}
+
+impl<R, A, B, H> Link<R, A, B, H> {
+    fn poll_next<I: Embed<R>>(
+        &mut self,
+        ctx: &mut task::Context,
+    ) -> Poll<Option<Result<I, ChannelError>>> {
+        let mut shared = self.context.shared.borrow_mut();
+        let status = shared.status;
+        let threshold = shared.config.threshold();
+        let entry = shared.entry(self.handle);
+        if entry.cancelled {
+            return Poll::Ready(Some(Err(ChannelError::Cancelled)));
+        }
+        if let Some(item) = entry.inbound.pop_front() {
+            if let (Some(threshold), false) = (threshold, entry.remote) {
+                entry.consumed += 1;
+                if entry.consumed >= threshold {
+                    let credit = entry.consumed;
+                    entry.consumed = 0;
+                    shared.outbound.push(Frame::Window(self.handle, credit));
+                    shared.wake_driver();
+                }
+            }
+            let size = shared.measure.measure(&item);
+            shared.buffered -= size;
+            return Poll::Ready(Some(I::extract(item).map_err(|_| ChannelError::Unexpected)));
+        }
+        if entry.remote {
+            return Poll::Ready(None);
+        }
+        match status {
+            Status::Open => {
+                entry.waker = Some(ctx.waker().clone());
+                Poll::Pending
+            }
+            Status::Terminated => Poll::Ready(None),
+            Status::Failed => Poll::Ready(Some(Err(ChannelError::Disconnected))),
+        }
+    }
+
+    fn poll_ready(&mut self, ctx: &mut task::Context) -> Poll<Result<(), ChannelError>> {
+        let mut shared = self.context.shared.borrow_mut();
+        if shared.status == Status::Failed {
+            return Poll::Ready(Err(ChannelError::Disconnected));
+        }
+        let unlimited = shared.config.window.is_none();
+        let entry = shared.entry(self.handle);
+        if entry.cancelled {
+            return Poll::Ready(Err(ChannelError::Cancelled));
+        }
+        if entry.local {
+            return Poll::Ready(Err(ChannelError::Closed));
+        }
+        if unlimited || entry.credit > 0 {
+            Poll::Ready(Ok(()))
+        } else {
+            entry.ready = Some(ctx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn start_send<I: Embed<R>>(&mut self, item: I) -> Result<(), ChannelError> {
+        let mut shared = self.context.shared.borrow_mut();
+        if shared.status == Status::Failed {
+            return Err(ChannelError::Disconnected);
+        }
+        let entry = shared.entry(self.handle);
+        if entry.cancelled {
+            return Err(ChannelError::Cancelled);
+        }
+        if entry.local {
+            return Err(ChannelError::Closed);
+        }
+        entry.credit = entry.credit.saturating_sub(1);
+        shared.outbound.push(Frame::Item(self.handle, item.embed()));
+        shared.wake_driver();
+        Ok(())
+    }
+
+    fn poll_flush(&mut self, ctx: &mut task::Context) -> Poll<Result<(), ChannelError>> {
+        let mut shared = self.context.shared.borrow_mut();
+        if shared.status == Status::Failed {
+            return Poll::Ready(Err(ChannelError::Disconnected));
+        }
+        if shared.outbound.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        shared.flushing.push(ctx.waker().clone());
+        shared.wake_driver();
+        Poll::Pending
+    }
+
+    fn close(&mut self) {
+        let mut shared = self.context.shared.borrow_mut();
+        let entry = shared.entry(self.handle);
+        if entry.local {
+            return;
+        }
+        entry.local = true;
+        if shared.status != Status::Failed {
+            shared.outbound.push(Frame::Close(self.handle));
+            shared.wake_driver();
+        }
+    }
+
+    fn poll_close(&mut self, ctx: &mut task::Context) -> Poll<Result<(), ChannelError>> {
+        self.close();
+        self.poll_flush(ctx)
+    }
+
+    fn cancel(&mut self) {
+        let mut shared = self.context.shared.borrow_mut();
+        let entry = shared.entry(self.handle);
+        if entry.local || entry.remote {
+            return;
+        }
+        entry.local = true;
+        if shared.status != Status::Failed {
+            shared.outbound.push(Frame::Cancel(self.handle));
+            shared.wake_driver();
+        }
+    }
+}
+
+impl<R, A, B, H> Drop for Link<R, A, B, H> {
+    fn drop(&mut self) {
+        self.close();
+        let mut shared = self.context.shared.borrow_mut();
+        shared.outbound.release(self.handle);
+        let entry = shared.entry(self.handle);
+        entry.attached = false;
+        if entry.remote {
+            shared.reclaim(self.handle);
+        }
+        shared.live -= 1;
+        shared.wake_driver();
+    }
+}
+
+pub struct Unravel<R, A, B, H = Handle>(Link<R, A, B, H>);
+
+pub struct Coalesce<R, A, B, H = Handle>(Link<R, A, B, H>);
+
+impl<R, A, B, H> Drop for Coalesce<R, A, B, H> {
+    fn drop(&mut self) {
+        let joining = {
+            let mut shared = self.0.context.shared.borrow_mut();
+            let entry = shared.entry(self.0.handle);
+            entry.abandoned = entry.joining;
+            entry.joining
+        };
+        if !joining {
+            self.0.cancel();
+        }
+    }
+}
+
+pub struct Joining<T, R> {
+    future: T,
+    handle: Handle,
+    shared: Rc<RefCell<Shared<R>>>,
+    done: bool,
+}
+
+impl<T, R> Joining<T, R> {
+    fn new(future: T, handle: Handle, shared: Rc<RefCell<Shared<R>>>) -> Self {
+        Joining {
+            future,
+            handle,
+            shared,
+            done: false,
+        }
+    }
+}
+
+impl<T: TryFuture, R> Future for Joining<T, R> {
+    type Output = Result<T::Ok, T::Error>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut task::Context) -> Poll<Self::Output> {
+        // SAFETY: `future` is structurally pinned; it is never moved out of `Joining` and the
+        // `Drop` impl does not touch it. No other field is pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        let output = ready!(future.try_poll(ctx));
+        this.done = true;
+        this.shared.borrow_mut().settle(this.handle, output.is_ok());
+        Poll::Ready(output)
+    }
+}
+
+impl<T, R> Drop for Joining<T, R> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.shared.borrow_mut().settle(self.handle, false);
+        }
+    }
+}
+
+impl<R, A: Embed<R>, B, H> Sink<A> for Unravel<R, A, B, H> {
+    type Error = ChannelError;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(ctx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: A) -> Result<(), Self::Error> {
+        self.0.start_send(item)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_flush(ctx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_close(ctx)
+    }
+}
+
+impl<R, A, B: Embed<R>, H> Stream for Unravel<R, A, B, H> {
+    type Item = Result<B, ChannelError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut task::Context) -> Poll<Option<Self::Item>> {
+        self.0.poll_next(ctx)
+    }
+}
+
+impl<R, A, B: Embed<R>, H> Sink<B> for Coalesce<R, A, B, H> {
+    type Error = ChannelError;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(ctx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: B) -> Result<(), Self::Error> {
+        self.0.start_send(item)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_flush(ctx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_close(ctx)
+    }
+}
+
+impl<R, A: Embed<R>, B, H> Stream for Coalesce<R, A, B, H> {
+    type Item = Result<A, ChannelError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut task::Context) -> Poll<Option<Self::Item>> {
+        self.0.poll_next(ctx)
+    }
+}
+
+impl<R, A, B, H> Deref for Unravel<R, A, B, H> {
+    type Target = Context<R, H>;
+
+    fn deref(&self) -> &Context<R, H> {
+        &self.0.context
+    }
+}
+
+impl<R, A, B, H> DerefMut for Unravel<R, A, B, H> {
+    fn deref_mut(&mut self) -> &mut Context<R, H> {
+        &mut self.0.context
+    }
+}
+
+impl<R, A, B, H> Deref for Coalesce<R, A, B, H> {
+    type Target = Context<R, H>;
+
+    fn deref(&self) -> &Context<R, H> {
+        &self.0.context
+    }
+}
+
+impl<R, A, B, H> DerefMut for Coalesce<R, A, B, H> {
+    fn deref_mut(&mut self) -> &mut Context<R, H> {
+        &mut self.0.context
+    }
+}
+
+impl<R, A: Embed<R>, B: Embed<R>, H> Channel<B, A, Context<R, H>> for Unravel<R, A, B, H> {}
+
+impl<R, A: Embed<R>, B: Embed<R>, H> Channel<A, B, Context<R, H>> for Coalesce<R, A, B, H> {}
+
+impl<R, A: Embed<R>, B: Embed<R>, H> Channels<A, B> for Context<R, H> {
+    type Unravel = Unravel<R, A, B, H>;
+    type Coalesce = Coalesce<R, A, B, H>;
+}
+
+impl<R, H> Dispatch for Context<R, H> {
+    type Handle = H;
+}
+
+impl<R, H> Prioritize for Context<R, H> {
+    fn prioritize(&mut self, priority: Priority) {
+        self.priority = priority;
+    }
+}
+
+impl<
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        R,
+        H: Token,
+        P: Protocol<F, Context<R, H>>,
+    > Join<P, F> for Context<R, H>
+where
+    P::Unravel: Embed<R>,
+    P::Coalesce: Embed<R>,
+{
+    type Error = ChannelError;
+    type Target = Context<R, H>;
+    type Output = Either<
+        Joining<
+            MapErr<
+                P::CoalesceFuture,
+                fn(P::CoalesceError) -> ContextError<ChannelError, P::CoalesceError>,
+            >,
+            R,
+        >,
+        Ready<Result<P, ContextError<ChannelError, P::CoalesceError>>>,
+    >;
+
+    fn join(&mut self, handle: H) -> Self::Output {
+        let (handle, token) = handle.redeem();
+        if let Err(e) = self.shared.borrow_mut().claim(handle, token, self.depth) {
+            return Either::Right(ready(Err(ContextError::Context(e))));
+        }
+        let future =
+            P::coalesce(self.open_coalesce(handle)).map_err(ContextError::Protocol as fn(_) -> _);
+        Either::Left(Joining::new(future, handle, self.shared.clone()))
+    }
+}
+
+impl<
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        R,
+        H: Token,
+        P: Protocol<F, Context<R, H>>,
+    > Spawn<P, F> for Context<R, H>
+where
+    P::Unravel: Embed<R>,
+    P::Coalesce: Embed<R>,
+    P::UnravelFuture: 'static,
+    P::UnravelError: 'static,
+{
+    type Error = ChannelError;
+    type Target = Context<R, H>;
+    type Output = Ready<Result<H, ContextError<ChannelError, P::UnravelError>>>;
+
+    fn spawn(&mut self, protocol: P) -> Self::Output {
+        if self.shared.borrow().draining() {
+            return ready(Err(ContextError::Context(ChannelError::Shutdown)));
+        }
+        let handle = {
+            let mut shared = self.shared.borrow_mut();
+            let handle = shared.allocate();
+            if !shared.room(handle) {
+                shared.free.push(handle);
+                return ready(Err(ContextError::Context(ChannelError::Channels)));
+            }
+            handle
+        };
+        let token = self.shared.borrow_mut().issue(handle);
+        let child = protocol
+            .unravel(Unravel(self.open(handle)))
+            .map_err(|e| Box::new(e) as Box<dyn Any>);
+        let mut shared = self.shared.borrow_mut();
+        shared.tasks.push((handle, Box::pin(child)));
+        shared.wake_driver();
+        ready(Ok(H::issue(handle, token)))
+    }
+}
+
+#[derive(Clone)]
+struct Entropy(Rc<RefCell<dyn FnMut() -> u64>>);
+
+impl Entropy {
+    fn generate(&self) -> u64 {
+        let mut entropy = self.0.borrow_mut();
+        loop {
+            let token = entropy();
+            if token != 0 {
+                return token;
+            }
+        }
+    }
+}
+
+impl Debug for Entropy {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("Entropy")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Mux<H = Handle, M = ()> {
+    window: Option<u32>,
+    threshold: Option<u32>,
+    entropy: Option<Entropy>,
+    limits: Limits,
+    measure: M,
+    data: PhantomData<fn() -> H>,
+}
+
+impl Mux {
+    pub fn new() -> Self {
+        Mux::default()
+    }
+}
+
+impl Default for Mux {
+    fn default() -> Self {
+        Mux {
+            window: None,
+            threshold: None,
+            entropy: None,
+            limits: Limits::new(),
+            measure: (),
+            data: PhantomData,
+        }
+    }
+}
+
+impl<H, M> Mux<H, M> {
+    pub fn with_capabilities<E: FnMut() -> u64 + 'static>(self, entropy: E) -> Mux<Capability, M> {
+        Mux {
+            window: self.window,
+            threshold: self.threshold,
+            entropy: Some(Entropy(Rc::new(RefCell::new(entropy)))),
+            limits: self.limits,
+            measure: self.measure,
+            data: PhantomData,
+        }
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_measure<N>(self, measure: N) -> Mux<H, N> {
+        Mux {
+            window: self.window,
+            threshold: self.threshold,
+            entropy: self.entropy,
+            limits: self.limits,
+            measure,
+            data: PhantomData,
+        }
+    }
+
+    pub fn with_window(mut self, window: u32) -> Self {
+        self.window = Some(window.max(1));
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: u32) -> Self {
+        self.threshold = Some(threshold.max(1));
+        self
+    }
+
+    fn threshold(&self) -> Option<u32> {
+        self.window
+            .map(|window| self.threshold.unwrap_or(window / 2).max(1).min(window))
+    }
+
+    fn context<R>(self, next: u32) -> Context<R, H>
+    where
+        M: Measure<R> + 'static,
+    {
+        let config = Mux {
+            window: self.window,
+            threshold: self.threshold,
+            entropy: self.entropy,
+            limits: self.limits,
+            measure: (),
+            data: PhantomData,
+        };
+        Context {
+            shared: Rc::new(RefCell::new(Shared::new(next, config, Box::new(self.measure)))),
+            priority: Priority::default(),
+            depth: 0,
+            data: PhantomData,
+        }
+    }
+}
+
+impl<
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        P: Protocol<F, Context<R, H>>,
+        R,
+        H: Token,
+        M: Measure<R> + 'static,
+        T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>,
+    > Drive<P, F, T> for Mux<H, M>
+where
+    P::Unravel: Embed<R>,
+    P::Coalesce: Embed<R>,
+    P::UnravelFuture: Unpin,
+    P::CoalesceFuture: Unpin,
+{
+    type DriverError = Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>;
+    type Driver = Driver<T, R>;
+    type UnravelHandle = MapErr<
+        P::UnravelFuture,
+        fn(P::UnravelError) -> DirectorError<Self::UnravelError, P::UnravelError>,
+    >;
+    type CoalesceHandle = Joining<
+        MapErr<
+            P::CoalesceFuture,
+            fn(P::CoalesceError) -> DirectorError<Self::CoalesceError, P::CoalesceError>,
+        >,
+        R,
+    >;
+
+    fn unravel_driven(self, protocol: P, transport: T) -> (Self::UnravelHandle, Self::Driver) {
+        use DirectorError::Protocol;
+        let context = self.context(2);
+        let channel = Unravel(context.open(Handle::ROOT));
+        (
+            protocol.unravel(channel).map_err(Protocol),
+            Driver::new(transport, context.shared),
+        )
+    }
+
+    fn coalesce_driven(self, transport: T) -> (Self::CoalesceHandle, Self::Driver) {
+        use DirectorError::Protocol;
+        let context = self.context(1);
+        let channel = context.open_coalesce(Handle::ROOT);
+        let future = P::coalesce(channel).map_err(Protocol as fn(_) -> _);
+        (
+            Joining::new(future, Handle::ROOT, context.shared.clone()),
+            Driver::new(transport, context.shared),
+        )
+    }
+}
+
+impl<
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        P: Protocol<F, Context<R, H>>,
+        R,
+        H: Token,
+        M: Measure<R> + 'static,
+        T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>,
+    > Director<P, F, T> for Mux<H, M>
+where
+    P::Unravel: Embed<R>,
+    P::Coalesce: Embed<R>,
+    P::UnravelFuture: Unpin,
+    P::CoalesceFuture: Unpin,
+{
+    type Context = Context<R, H>;
+    type UnravelError = Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>;
+    type Unravel = Driven<<Self as Drive<P, F, T>>::UnravelHandle, Driver<T, R>>;
+    type CoalesceError = Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>;
+    type Coalesce = Driven<<Self as Drive<P, F, T>>::CoalesceHandle, Driver<T, R>>;
+
+    fn unravel(self, protocol: P, transport: T) -> Self::Unravel {
+        let (handle, driver) = Drive::<P, F, T>::unravel_driven(self, protocol, transport);
+        Driven::new(handle, driver, true)
+    }
+
+    fn coalesce(self, transport: T) -> Self::Coalesce {
+        let (handle, driver) = Drive::<P, F, T>::coalesce_driven(self, transport);
+        Driven::new(handle, driver, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Capability, ChannelError, Coalesce, Context, Driver, Error, Frame, Handle, Limits, Measure,
+        Mux, Unravel,
+    };
+    use crate::{
+        director::{Director, DirectorError, Drive, Token},
+        format::Null,
+        transport::pipe::{channel, Channel},
+        Bottom, Channels, ContextError, Join, Protocol, Spawn,
+    };
+    use alloc::{rc::Rc, vec, vec::Vec};
+    use core::{cell::Cell, pin::Pin};
+    use futures::{
+        executor::block_on,
+        future::{join, pending, poll_fn, ready, Pending, Ready},
+        FutureExt, Sink, SinkExt, StreamExt,
+    };
+    use void::Void;
+
+    type Transport = Channel<Frame<u32>>;
+
+    fn session<H, M: Measure<u32> + 'static>(
+        mux: Mux<H, M>,
+        next: u32,
+    ) -> (Context<u32, H>, Driver<Transport, u32>, Transport) {
+        let (left, right) = channel();
+        let context = mux.context(next);
+        let driver = Driver::new(left, context.shared.clone());
+        (context, driver, right)
+    }
+
+    fn frames(peer: &mut Transport) -> Vec<Frame<u32>> {
+        let mut frames = Vec::new();
+        while let Some(Some(Ok(frame))) = peer.next().now_or_never() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    fn poll_ready<S: Sink<u32> + Unpin>(sink: &mut S) -> Option<Result<(), S::Error>> {
+        poll_fn(|ctx| Pin::new(&mut *sink).poll_ready(ctx)).now_or_never()
+    }
+
+    #[test]
+    fn round_trips_spawned_children() {
+        let (left, right) = channel();
+        let unravel =
+            <Mux as Director<_, Null, Transport>>::unravel(Mux::new(), Some(Some(())), left);
+        let coalesce =
+            <Mux as Director<Option<Option<()>>, Null, Transport>>::coalesce(Mux::new(), right);
+        let (unravelled, coalesced) = block_on(join(unravel, coalesce));
+        unravelled.unwrap();
+        assert_eq!(coalesced.unwrap(), Some(Some(())));
+    }
+
+    #[derive(Debug)]
+    struct Refused;
+
+    struct Refuse;
+
+    impl<F: ?Sized, C> Protocol<F, C> for Refuse {
+        type Unravel = Bottom;
+        type UnravelError = Refused;
+        type UnravelFuture = Ready<Result<(), Refused>>;
+        type Coalesce = Bottom;
+        type CoalesceError = Void;
+        type CoalesceFuture = Pending<Result<Refuse, Void>>;
+
+        fn unravel(self, _: C::Unravel) -> Self::UnravelFuture
+        where
+            C: Channels<Self::Unravel, Self::Coalesce>,
+        {
+            ready(Err(Refused))
+        }
+
+        fn coalesce(_: C::Coalesce) -> Self::CoalesceFuture
+        where
+            C: Channels<Self::Unravel, Self::Coalesce>,
+        {
+            pending()
+        }
+    }
+
+    #[test]
+    fn reports_spawned_child_errors() {
+        let (left, _right) = channel();
+        let unravel =
+            <Mux as Director<_, Null, Transport>>::unravel(Mux::new(), Some(Refuse), left);
+        match block_on(unravel) {
+            Err(DirectorError::Director(Error::Nested(error))) => assert!(error.is::<Refused>()),
+            _ => panic!("child error was discarded"),
+        }
+    }
+
+    #[test]
+    fn waits_for_credit() {
+        let (context, mut driver, mut peer) = session(Mux::new().with_window(2), 2);
+        let mut channel = Unravel::<u32, u32, u32>(context.open(Handle::ROOT));
+        block_on(channel.feed(1)).unwrap();
+        block_on(channel.feed(2)).unwrap();
+        assert!(poll_ready(&mut channel).is_none());
+        block_on(peer.send(Frame::Window(Handle::ROOT, 1))).unwrap();
+        assert!((&mut driver).now_or_never().is_none());
+        assert!(matches!(poll_ready(&mut channel), Some(Ok(()))));
+    }
+
+    #[test]
+    fn grants_credit_after_threshold() {
+        let (context, mut driver, mut peer) =
+            session(Mux::new().with_window(4).with_threshold(2), 2);
+        let mut channel = Coalesce::<u32, u32, u32>(context.open(Handle::ROOT));
+        block_on(peer.send(Frame::Item(Handle::ROOT, 1))).unwrap();
+        block_on(peer.send(Frame::Item(Handle::ROOT, 2))).unwrap();
+        assert!((&mut driver).now_or_never().is_none());
+        assert_eq!(block_on(channel.next()).unwrap().unwrap(), 1);
+        assert_eq!(block_on(channel.next()).unwrap().unwrap(), 2);
+        assert!((&mut driver).now_or_never().is_none());
+        assert!(matches!(
+            frames(&mut peer)[..],
+            [Frame::Window(Handle::ROOT, 2)]
+        ));
+    }
+
+    #[test]
+    fn rejects_items_beyond_window() {
+        let (context, mut driver, mut peer) = session(Mux::new().with_window(1), 2);
+        let _channel = Coalesce::<u32, u32, u32>(context.open(Handle::ROOT));
+        block_on(peer.send(Frame::Item(Handle::ROOT, 1))).unwrap();
+        block_on(peer.send(Frame::Item(Handle::ROOT, 2))).unwrap();
+        assert!(matches!(
+            (&mut driver).now_or_never(),
+            Some(Err(Error::Window))
+        ));
+    }
+
+    #[test]
+    fn receives_after_half_close() {
+        let (context, mut driver, mut peer) = session(Mux::new(), 2);
+        let mut channel = Unravel::<u32, u32, u32>(context.open(Handle::ROOT));
+        assert!(SinkExt::close(&mut channel).now_or_never().is_none());
+        assert!((&mut driver).now_or_never().is_none());
+        assert!(matches!(
+            frames(&mut peer)[..],
+            [Frame::Close(Handle::ROOT)]
+        ));
+        assert!(matches!(
+            poll_ready(&mut channel),
+            Some(Err(ChannelError::Closed))
+        ));
+        block_on(peer.send(Frame::Item(Handle::ROOT, 1))).unwrap();
+        assert!((&mut driver).now_or_never().is_none());
+        assert_eq!(block_on(channel.next()).unwrap().unwrap(), 1);
+    }
+
+    #[test]
+    fn reclaims_handles_closed_on_both_sides() {
+        let (context, mut driver, mut peer) = session(Mux::new(), 2);
+        let _root = Unravel::<u32, u32, u32>(context.open(Handle::ROOT));
+        let handle = context.shared.borrow_mut().allocate();
+        drop(Unravel::<u32, u32, u32>(context.open(handle)));
+        assert!((&mut driver).now_or_never().is_none());
+        assert!(matches!(frames(&mut peer)[..], [Frame::Close(closed)] if closed == handle));
+        assert_ne!(context.shared.borrow_mut().allocate(), handle);
+        block_on(peer.send(Frame::Close(handle))).unwrap();
+        assert!((&mut driver).now_or_never().is_none());
+        assert_eq!(context.shared.borrow_mut().allocate(), handle);
+    }
+
+    #[test]
+    fn rejects_items_after_remote_close() {
+        let (context, mut driver, mut peer) = session(Mux::new(), 2);
+        let _channel = Unravel::<u32, u32, u32>(context.open(Handle::ROOT));
+        block_on(peer.send(Frame::Close(Handle::ROOT))).unwrap();
+        block_on(peer.send(Frame::Item(Handle::ROOT, 1))).unwrap();
+        assert!(matches!(
+            (&mut driver).now_or_never(),
+            Some(Err(Error::Closed))
+        ));
+    }
+
+    #[test]
+    fn completed_coalesce_sends_no_cancel() {
+        let (left, mut peer) = channel();
+        let (handle, mut driver) = Drive::<(), Null, Transport>::coalesce_driven(Mux::new(), left);
+        block_on(handle).unwrap();
+        let _ = (&mut driver).now_or_never();
+        let frames = frames(&mut peer);
+        assert!(frames
+            .iter()
+            .any(|frame| matches!(frame, Frame::Close(Handle::ROOT))));
+        assert!(!frames.iter().any(|frame| matches!(frame, Frame::Cancel(_))));
+    }
+
+    #[test]
+    fn cancels_abandoned_coalesce() {
+        let (left, mut peer) = channel();
+        let (mut handle, mut driver) =
+            Drive::<Refuse, Null, Transport>::coalesce_driven(Mux::new(), left);
+        assert!((&mut handle).now_or_never().is_none());
+        drop(handle);
+        let _ = (&mut driver).now_or_never();
+        assert!(frames(&mut peer)
+            .iter()
+            .any(|frame| matches!(frame, Frame::Cancel(Handle::ROOT))));
+    }
+
+    #[test]
+    fn drains_idle_sessions() {
+        let (_context, mut driver, mut peer) = session(Mux::new(), 2);
+        let mut drain = driver.shutdown_handle().initiate(pending());
+        assert!(matches!((&mut driver).now_or_never(), Some(Ok(()))));
+        assert_eq!((&mut drain).now_or_never(), Some(Vec::new()));
+        assert!(matches!(frames(&mut peer)[..], [Frame::GoAway]));
+    }
+
+    #[test]
+    fn waits_for_channels_to_close() {
+        let (context, mut driver, mut peer) = session(Mux::new(), 2);
+        let channel = Unravel::<u32, u32, u32>(context.open(Handle::ROOT));
+        let mut drain = driver.shutdown_handle().initiate(pending());
+        assert!((&mut driver).now_or_never().is_none());
+        assert!((&mut drain).now_or_never().is_none());
+        drop(channel);
+        assert!(matches!((&mut driver).now_or_never(), Some(Ok(()))));
+        assert_eq!((&mut drain).now_or_never(), Some(Vec::new()));
+        assert!(matches!(
+            frames(&mut peer)[..],
+            [Frame::GoAway, Frame::Close(Handle::ROOT)]
+        ));
+    }
+
+    #[test]
+    fn aborts_channels_past_deadline() {
+        let (context, mut driver, _peer) = session(Mux::new(), 2);
+        let mut channel = Unravel::<u32, u32, u32>(context.open(Handle::ROOT));
+        let mut drain = driver.shutdown_handle().initiate(ready(()));
+        assert!(matches!((&mut driver).now_or_never(), Some(Ok(()))));
+        assert_eq!((&mut drain).now_or_never(), Some(vec![Handle::ROOT]));
+        assert!(matches!(
+            poll_ready(&mut channel),
+            Some(Err(ChannelError::Disconnected))
+        ));
+    }
+
+    #[test]
+    fn refuses_spawns_while_draining() {
+        let (mut context, driver, _peer) = session(Mux::new(), 2);
+        let _drain = driver.shutdown_handle().initiate(pending());
+        assert!(matches!(
+            Spawn::<(), Null>::spawn(&mut context, ()).now_or_never(),
+            Some(Err(ContextError::Context(ChannelError::Shutdown)))
+        ));
+    }
+
+    #[test]
+    fn round_trips_with_capabilities() {
+        type Transport = Channel<Frame<(u32, u64)>>;
+        let issued = Rc::new(Cell::new(0));
+        let counter = issued.clone();
+        let entropy = move || {
+            counter.set(counter.get() + 1);
+            counter.get()
+        };
+        let (left, right) = channel();
+        let unravel = <Mux<Capability> as Director<_, Null, Transport>>::unravel(
+            Mux::new().with_capabilities(entropy),
+            Some(Some(())),
+            left,
+        );
+        let coalesce = <Mux<Capability> as Director<Option<Option<()>>, Null, Transport>>::coalesce(
+            Mux::new().with_capabilities(|| 1),
+            right,
+        );
+        let (unravelled, coalesced) = block_on(join(unravel, coalesce));
+        unravelled.unwrap();
+        assert_eq!(coalesced.unwrap(), Some(Some(())));
+        assert_eq!(issued.get(), 2);
+    }
+
+    #[test]
+    fn rejects_forged_and_reused_tokens() {
+        let (mut context, _driver, _peer) = session(Mux::new().with_capabilities(|| 1), 1);
+        let mut claim =
+            |capability| Join::<(), Null>::join(&mut context, capability).now_or_never();
+        assert!(matches!(
+            claim(Capability::issue(Handle(2), 0)),
+            Some(Err(ContextError::Context(ChannelError::Forged)))
+        ));
+        assert!(matches!(
+            claim(Capability::issue(Handle(2), 5)),
+            Some(Ok(()))
+        ));
+        assert!(matches!(
+            claim(Capability::issue(Handle(2), 5)),
+            Some(Err(ContextError::Context(ChannelError::Forged)))
+        ));
+    }
+
+    #[test]
+    fn rejects_claims_with_wrong_tokens() {
+        let (mut context, mut driver, mut peer) = session(Mux::new().with_capabilities(|| 9), 2);
+        let capability = Spawn::<(), Null>::spawn(&mut context, ())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        let (handle, token) = capability.redeem();
+        assert_eq!(token, 9);
+        block_on(peer.send(Frame::Claim(handle, 8))).unwrap();
+        assert!(matches!(
+            (&mut driver).now_or_never(),
+            Some(Err(Error::Forged))
+        ));
+    }
+
+    fn size(item: &u32) -> usize {
+        *item as usize
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let limits = Limits::new().with_frame_size(4);
+        let mux = Mux::new()
+            .with_limits(limits)
+            .with_measure(size as fn(&u32) -> usize);
+        let (_context, mut driver, mut peer) = session(mux, 2);
+        block_on(peer.send(Frame::Item(Handle(1), 4))).unwrap();
+        assert!((&mut driver).now_or_never().is_none());
+        block_on(peer.send(Frame::Item(Handle(1), 5))).unwrap();
+        assert!(matches!(
+            (&mut driver).now_or_never(),
+            Some(Err(Error::Oversized))
+        ));
+    }
+
+    #[test]
+    fn rejects_buffered_overflow() {
+        let limits = Limits::new().with_buffered(6);
+        let mux = Mux::new()
+            .with_limits(limits)
+            .with_measure(size as fn(&u32) -> usize);
+        let (_context, mut driver, mut peer) = session(mux, 2);
+        block_on(peer.send(Frame::Item(Handle(1), 4))).unwrap();
+        assert!((&mut driver).now_or_never().is_none());
+        block_on(peer.send(Frame::Item(Handle(3), 3))).unwrap();
+        assert!(matches!(
+            (&mut driver).now_or_never(),
+            Some(Err(Error::Overflow))
+        ));
+    }
+
+    #[test]
+    fn rejects_excess_channels() {
+        let mux = Mux::new().with_limits(Limits::new().with_channels(1));
+        let (mut context, mut driver, mut peer) = session(mux, 2);
+        block_on(peer.send(Frame::Item(Handle(1), 1))).unwrap();
+        assert!((&mut driver).now_or_never().is_none());
+        assert!(matches!(
+            Spawn::<(), Null>::spawn(&mut context, ()).now_or_never(),
+            Some(Err(ContextError::Context(ChannelError::Channels)))
+        ));
+        block_on(peer.send(Frame::Item(Handle(3), 1))).unwrap();
+        assert!(matches!(
+            (&mut driver).now_or_never(),
+            Some(Err(Error::Channels))
+        ));
+    }
+
+    #[test]
+    fn rejects_joins_beyond_depth() {
+        let mux = Mux::new().with_limits(Limits::new().with_depth(1));
+        let (mut context, _driver, _peer) = session(mux, 2);
+        let mut channel = Coalesce::<u32, u32, u32>(context.open(Handle(1)));
+        assert!(matches!(
+            Join::<(), Null>::join(&mut *channel, Handle(3)).now_or_never(),
+            Some(Err(ContextError::Context(ChannelError::Depth)))
+        ));
+        assert!(matches!(
+            Join::<(), Null>::join(&mut context, Handle(5)).now_or_never(),
+            Some(Ok(()))
+        ));
+    }
+}
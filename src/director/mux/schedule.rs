@@ -0,0 +1,193 @@
+use super::{Frame, Handle};
+use crate::director::Priority;
+use alloc::collections::{BTreeMap, VecDeque};
+use core::ops::Bound::{Excluded, Unbounded};
+
+struct Queue<R> {
+    frames: VecDeque<Frame<R>>,
+    weight: u16,
+}
+
+struct Level<R> {
+    queues: BTreeMap<Handle, Queue<R>>,
+    cursor: Option<Handle>,
+    served: u16,
+}
+
+impl<R> Level<R> {
+    fn new() -> Self {
+        Level {
+            queues: BTreeMap::new(),
+            cursor: None,
+            served: 0,
+        }
+    }
+
+    fn advance(&mut self) -> Option<Handle> {
+        let next = self
+            .cursor
+            .and_then(|cursor| self.queues.range((Excluded(cursor), Unbounded)).next())
+            .or_else(|| self.queues.iter().next())
+            .map(|(handle, _)| *handle)?;
+        self.cursor = Some(next);
+        self.served = 0;
+        Some(next)
+    }
+
+    fn pop(&mut self) -> Option<Frame<R>> {
+        let handle = match self.cursor {
+            Some(cursor)
+                if self
+                    .queues
+                    .get(&cursor)
+                    .map_or(false, |queue| self.served < queue.weight) =>
+            {
+                cursor
+            }
+            _ => self.advance()?,
+        };
+        let queue = self
+            .queues
+            .get_mut(&handle)
+            .expect("violated invariant in Mux: scheduled queue missing");
+        let frame = queue.frames.pop_front();
+        self.served += 1;
+        if queue.frames.is_empty() {
+            self.queues.remove(&handle);
+        }
+        frame
+    }
+}
+
+pub(super) struct Schedule<R> {
+    control: VecDeque<Frame<R>>,
+    levels: BTreeMap<u8, Level<R>>,
+    priorities: BTreeMap<Handle, Priority>,
+    len: usize,
+}
+
+impl<R> Schedule<R> {
+    pub(super) fn new() -> Self {
+        Schedule {
+            control: VecDeque::new(),
+            levels: BTreeMap::new(),
+            priorities: BTreeMap::new(),
+            len: 0,
+        }
+    }
+
+    pub(super) fn assign(&mut self, handle: Handle, priority: Priority) {
+        self.priorities.insert(handle, priority);
+    }
+
+    pub(super) fn release(&mut self, handle: Handle) {
+        self.priorities.remove(&handle);
+    }
+
+    pub(super) fn pending(&self, handle: Handle) -> bool {
+        self.levels
+            .values()
+            .any(|level| level.queues.contains_key(&handle))
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(super) fn push(&mut self, frame: Frame<R>) {
+        self.len += 1;
+        let handle = match &frame {
+            Frame::Item(handle, _) | Frame::Close(handle) => *handle,
+            _ => return self.control.push_back(frame),
+        };
+        let priority = self.priorities.get(&handle).copied().unwrap_or_default();
+        self.levels
+            .entry(priority.level())
+            .or_insert_with(Level::new)
+            .queues
+            .entry(handle)
+            .or_insert_with(|| Queue {
+                frames: VecDeque::new(),
+                weight: priority.weight(),
+            })
+            .frames
+            .push_back(frame);
+    }
+
+    pub(super) fn pop(&mut self) -> Option<Frame<R>> {
+        let frame = match self.control.pop_front() {
+            Some(frame) => Some(frame),
+            None => self
+                .levels
+                .values_mut()
+                .rev()
+                .find(|level| !level.queues.is_empty())?
+                .pop(),
+        };
+        if frame.is_some() {
+            self.len -= 1;
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Schedule;
+    use crate::director::{Frame, Handle, Priority};
+    use alloc::vec::Vec;
+
+    fn order(schedule: &mut Schedule<u32>) -> Vec<u32> {
+        let mut order = Vec::new();
+        while let Some(frame) = schedule.pop() {
+            order.push(match frame {
+                Frame::Item(handle, _) | Frame::Close(handle) => handle.id(),
+                _ => u32::MAX,
+            });
+        }
+        order
+    }
+
+    #[test]
+    fn serves_control_frames_first() {
+        let mut schedule = Schedule::new();
+        schedule.push(Frame::Item(Handle(1), 0));
+        schedule.push(Frame::Ping);
+        assert_eq!(order(&mut schedule), [u32::MAX, 1]);
+        assert!(schedule.is_empty());
+    }
+
+    #[test]
+    fn serves_higher_levels_first() {
+        let mut schedule = Schedule::new();
+        schedule.assign(Handle(1), Priority::BULK);
+        schedule.assign(Handle(3), Priority::CONTROL);
+        schedule.push(Frame::Item(Handle(1), 0));
+        schedule.push(Frame::Item(Handle(5), 0));
+        schedule.push(Frame::Item(Handle(3), 0));
+        assert_eq!(order(&mut schedule), [3, 5, 1]);
+    }
+
+    #[test]
+    fn weighs_channels_within_a_level() {
+        let mut schedule = Schedule::new();
+        schedule.assign(Handle(1), Priority::new(1, 2));
+        schedule.assign(Handle(3), Priority::new(1, 1));
+        for _ in 0..3 {
+            schedule.push(Frame::Item(Handle(1), 0));
+            schedule.push(Frame::Item(Handle(3), 0));
+        }
+        assert_eq!(order(&mut schedule), [1, 1, 3, 1, 3, 3]);
+    }
+
+    #[test]
+    fn keeps_close_behind_items() {
+        let mut schedule = Schedule::new();
+        schedule.push(Frame::Item(Handle(1), 0));
+        schedule.push(Frame::Close(Handle(1)));
+        assert!(schedule.pending(Handle(1)));
+        assert!(matches!(schedule.pop(), Some(Frame::Item(..))));
+        assert!(matches!(schedule.pop(), Some(Frame::Close(..))));
+        assert!(!schedule.pending(Handle(1)));
+    }
+}
@@ -0,0 +1,81 @@
+use super::{Frame, Handle, Shared};
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+pub(super) struct Closing {
+    pub(super) deadline: Pin<Box<dyn Future<Output = ()>>>,
+    pub(super) aborted: Option<Vec<Handle>>,
+    pub(super) closed: bool,
+    waker: Option<Waker>,
+}
+
+impl Closing {
+    pub(super) fn finish(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Shutdown<R> {
+    shared: Rc<RefCell<Shared<R>>>,
+}
+
+impl<R> Shutdown<R> {
+    pub(super) fn new(shared: Rc<RefCell<Shared<R>>>) -> Self {
+        Shutdown { shared }
+    }
+
+    pub fn initiate<D: Future<Output = ()> + 'static>(&self, deadline: D) -> Drain<R> {
+        let mut shared = self.shared.borrow_mut();
+        if shared.closing.is_none() {
+            shared.closing = Some(Closing {
+                deadline: Box::pin(deadline),
+                aborted: None,
+                closed: false,
+                waker: None,
+            });
+            shared.outbound.push(Frame::GoAway);
+            shared.wake_driver();
+        }
+        Drain {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<R> Clone for Shutdown<R> {
+    fn clone(&self) -> Self {
+        Shutdown {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+pub struct Drain<R> {
+    shared: Rc<RefCell<Shared<R>>>,
+}
+
+impl<R> Future for Drain<R> {
+    type Output = Vec<Handle>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Vec<Handle>> {
+        let mut shared = self.shared.borrow_mut();
+        let closing = shared
+            .closing
+            .as_mut()
+            .expect("violated invariant in Mux: Drain without shutdown");
+        if closing.closed {
+            Poll::Ready(closing.aborted.take().unwrap_or_default())
+        } else {
+            closing.waker = Some(ctx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
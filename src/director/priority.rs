@@ -0,0 +1,35 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Priority {
+    level: u8,
+    weight: u16,
+}
+
+impl Priority {
+    pub const BULK: Priority = Priority::new(0, 16);
+    pub const CONTROL: Priority = Priority::new(u8::MAX, 1);
+
+    pub const fn new(level: u8, weight: u16) -> Self {
+        Priority {
+            level,
+            weight: if weight == 0 { 1 } else { weight },
+        }
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::new(u8::MAX / 2, 16)
+    }
+}
+
+pub trait Prioritize {
+    fn prioritize(&mut self, priority: Priority);
+}
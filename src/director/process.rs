@@ -0,0 +1,269 @@
+use super::{Director, DirectorError};
+use crate::{
+    session::{self, Message, Session},
+    transport::tokio::Stdio,
+    Protocol,
+};
+use ::tokio::process::Command;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{future::ready, ready, Sink, TryFuture, TryStream};
+use std::{
+    io,
+    process::{self, ExitStatus},
+};
+
+#[derive(Debug)]
+pub enum Error<T> {
+    Spawn(io::Error),
+    Wait(io::Error),
+    Exit(ExitStatus),
+    Director(T),
+}
+
+type Wait = Pin<Box<dyn Future<Output = io::Result<ExitStatus>> + Send>>;
+
+fn spawn(command: process::Command) -> io::Result<(Stdio, Wait)> {
+    let mut command = Command::from(command);
+    command
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = command.spawn()?;
+    let stdio = match (child.stdin.take(), child.stdout.take()) {
+        (Some(stdin), Some(stdout)) => Stdio::new(stdout, stdin),
+        _ => return Err(io::Error::new(io::ErrorKind::BrokenPipe, "child stdio unavailable")),
+    };
+    Ok((stdio, Box::pin(async move { child.wait().await })))
+}
+
+pub struct Child {
+    exit: Wait,
+}
+
+impl Future for Child {
+    type Output = io::Result<ExitStatus>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        self.exit.as_mut().poll(ctx)
+    }
+}
+
+fn detach(child: Child) {
+    drop(::tokio::spawn(child));
+}
+
+pub struct Supervised<T: TryFuture> {
+    future: Option<T>,
+    exit: Option<Wait>,
+    exited: Option<ExitStatus>,
+    failure: Option<io::Error>,
+}
+
+impl<T: TryFuture> Supervised<T> {
+    fn new(command: process::Command, run: impl FnOnce(Stdio) -> T) -> Self {
+        match spawn(command) {
+            Ok((stdio, exit)) => Supervised {
+                future: Some(run(stdio)),
+                exit: Some(exit),
+                exited: None,
+                failure: None,
+            },
+            Err(e) => Supervised {
+                future: None,
+                exit: None,
+                exited: None,
+                failure: Some(e),
+            },
+        }
+    }
+}
+
+impl<T: TryFuture + Unpin> Unpin for Supervised<T> {}
+
+impl<T: TryFuture + Unpin> Future for Supervised<T> {
+    type Output = Result<(T::Ok, Child), Error<T::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        if let Some(e) = self.failure.take() {
+            return Poll::Ready(Err(Error::Spawn(e)));
+        }
+        let future = self
+            .future
+            .as_mut()
+            .expect("Supervised polled after completion");
+        let output = Pin::new(future).try_poll(ctx);
+        if self.exited.is_none() {
+            let exit = self.exit.as_mut().unwrap();
+            if let Poll::Ready(status) = exit.as_mut().poll(ctx) {
+                let status = status.map_err(Error::Wait)?;
+                if !status.success() {
+                    self.future = None;
+                    return Poll::Ready(Err(Error::Exit(status)));
+                }
+                self.exited = Some(status);
+            }
+        }
+        let output = match output {
+            Poll::Ready(output) => output.map_err(Error::Director)?,
+            Poll::Pending => return Poll::Pending,
+        };
+        self.future = None;
+        let exit: Wait = match self.exited.take() {
+            Some(status) => Box::pin(ready(Ok(status))),
+            None => self.exit.take().unwrap(),
+        };
+        Poll::Ready(Ok((output, Child { exit })))
+    }
+}
+
+pub struct Supervisor<T: TryFuture, S> {
+    supervised: Supervised<T>,
+    supervise: Option<S>,
+}
+
+impl<T: TryFuture + Unpin, S> Unpin for Supervisor<T, S> {}
+
+impl<E, U, T: TryFuture<Error = DirectorError<E, U>> + Unpin, S: FnOnce(Child)> Future
+    for Supervisor<T, S>
+{
+    type Output = Result<T::Ok, DirectorError<Error<E>, U>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let (output, child) = ready!(Pin::new(&mut self.supervised).poll(ctx)).map_err(lift)?;
+        let supervise = self
+            .supervise
+            .take()
+            .expect("Supervisor polled after completion");
+        supervise(child);
+        Poll::Ready(Ok(output))
+    }
+}
+
+fn lift<T, U>(error: Error<DirectorError<T, U>>) -> DirectorError<Error<T>, U> {
+    match error {
+        Error::Spawn(e) => DirectorError::Director(Error::Spawn(e)),
+        Error::Wait(e) => DirectorError::Director(Error::Wait(e)),
+        Error::Exit(status) => DirectorError::Director(Error::Exit(status)),
+        Error::Director(DirectorError::Director(e)) => DirectorError::Director(Error::Director(e)),
+        Error::Director(DirectorError::Protocol(e)) => DirectorError::Protocol(e),
+        Error::Director(DirectorError::Dead) => DirectorError::Dead,
+    }
+}
+
+pub struct Process<D, W, S = fn(Child)> {
+    director: D,
+    wire: W,
+    supervise: S,
+}
+
+impl<D, W> Process<D, W> {
+    pub fn new(director: D, wire: W) -> Self {
+        Process {
+            director,
+            wire,
+            supervise: detach,
+        }
+    }
+}
+
+impl<D, W, S> Process<D, W, S> {
+    pub fn with_supervisor<T: FnOnce(Child)>(self, supervise: T) -> Process<D, W, T> {
+        Process {
+            director: self.director,
+            wire: self.wire,
+            supervise,
+        }
+    }
+
+    pub fn exchange<
+        P: Protocol<F, <D as Director<P, F, session::Coalesce<T, A, B>, session::Unravel<T, A, B>>>::Context>,
+        Q: Protocol<F, <D as Director<Q, F, session::Coalesce<T, A, B>, session::Unravel<T, A, B>>>::Context>,
+        F: ?Sized,
+        T: TryStream<Ok = Message<A, B>> + Sink<Message<A, B>>,
+        A,
+        B,
+    >(
+        self,
+        command: process::Command,
+        host: P,
+    ) -> Supervised<
+        session::Exchange<
+            <D as Director<P, F, session::Coalesce<T, A, B>, session::Unravel<T, A, B>>>::Unravel,
+            <D as Director<Q, F, session::Coalesce<T, A, B>, session::Unravel<T, A, B>>>::Coalesce,
+        >,
+    >
+    where
+        D: Clone
+            + Director<P, F, session::Coalesce<T, A, B>, session::Unravel<T, A, B>>
+            + Director<Q, F, session::Coalesce<T, A, B>, session::Unravel<T, A, B>>,
+        <D as Director<P, F, session::Coalesce<T, A, B>, session::Unravel<T, A, B>>>::Unravel:
+            Unpin,
+        <D as Director<Q, F, session::Coalesce<T, A, B>, session::Unravel<T, A, B>>>::Coalesce:
+            Unpin,
+        W: FnOnce(Stdio) -> T,
+    {
+        let Process { director, wire, .. } = self;
+        Supervised::new(command, move |stdio| {
+            let unravel = director.clone();
+            Session::new(wire(stdio)).exchange(
+                |transport| {
+                    Director::<P, F, session::Coalesce<T, A, B>, _>::unravel(
+                        unravel, host, transport,
+                    )
+                },
+                |transport| {
+                    Director::<Q, F, _, session::Unravel<T, A, B>>::coalesce(director, transport)
+                },
+            )
+        })
+    }
+}
+
+impl<
+        P: Protocol<F, <D as Director<P, F, T>>::Context>,
+        F: ?Sized,
+        D: Director<P, F, T>,
+        T,
+        W: FnOnce(Stdio) -> T,
+        S: FnOnce(Child),
+    > Director<P, F, process::Command> for Process<D, W, S>
+where
+    D::Unravel: Unpin,
+    D::Coalesce: Unpin,
+{
+    type Context = D::Context;
+    type UnravelError = Error<D::UnravelError>;
+    type Unravel = Supervisor<D::Unravel, S>;
+    type CoalesceError = Error<D::CoalesceError>;
+    type Coalesce = Supervisor<D::Coalesce, S>;
+
+    fn unravel(self, protocol: P, command: process::Command) -> Self::Unravel {
+        let Process {
+            director,
+            wire,
+            supervise,
+        } = self;
+        Supervisor {
+            supervised: Supervised::new(command, move |stdio| {
+                director.unravel(protocol, wire(stdio))
+            }),
+            supervise: Some(supervise),
+        }
+    }
+
+    fn coalesce(self, command: process::Command) -> Self::Coalesce {
+        let Process {
+            director,
+            wire,
+            supervise,
+        } = self;
+        Supervisor {
+            supervised: Supervised::new(command, move |stdio| director.coalesce(wire(stdio))),
+            supervise: Some(supervise),
+        }
+    }
+}
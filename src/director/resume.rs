@@ -0,0 +1,668 @@
+use super::{Director, Frame};
+use crate::Protocol;
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    rc::{Rc, Weak},
+};
+use core::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use futures::{ready, Sink, Stream, TryStream};
+use void::Void;
+
+pub trait Connect {
+    type Transport;
+    type Error;
+    type Future: Future<Output = Result<Self::Transport, Self::Error>> + Unpin;
+
+    fn connect(&mut self) -> Self::Future;
+}
+
+impl<T, E, U: Future<Output = Result<T, E>> + Unpin, F: FnMut() -> U> Connect for F {
+    type Transport = T;
+    type Error = E;
+    type Future = U;
+
+    fn connect(&mut self) -> U {
+        self()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error<Connect> {
+    Connect(Connect),
+    Session,
+    Exhausted,
+}
+
+pub struct Resumable<C: Connect, R> {
+    connect: C,
+    transport: Option<C::Transport>,
+    connecting: Option<C::Future>,
+    session: u64,
+    retries: u32,
+    attempts: u32,
+    capacity: usize,
+    batch: u64,
+    control: VecDeque<Frame<R>>,
+    buffer: VecDeque<Frame<R>>,
+    cursor: usize,
+    acked: u64,
+    received: u64,
+    reported: u64,
+    waiting: Option<Waker>,
+    greeted: bool,
+    resumed: bool,
+    closing: bool,
+}
+
+impl<C: Connect, R> Unpin for Resumable<C, R> where C::Transport: Unpin {}
+
+impl<C: Connect, R> Resumable<C, R> {
+    fn new(
+        connect: C,
+        transport: C::Transport,
+        session: u64,
+        retries: u32,
+        capacity: usize,
+        batch: u64,
+    ) -> Self {
+        Resumable {
+            connect,
+            transport: Some(transport),
+            connecting: None,
+            session,
+            retries,
+            attempts: 0,
+            capacity,
+            batch,
+            control: VecDeque::new(),
+            buffer: VecDeque::new(),
+            cursor: 0,
+            acked: 0,
+            received: 0,
+            reported: 0,
+            waiting: None,
+            greeted: false,
+            resumed: false,
+            closing: false,
+        }
+    }
+}
+
+impl<R: Clone, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, C: Connect<Transport = T>>
+    Resumable<C, R>
+{
+    fn disconnect(&mut self) {
+        self.transport = None;
+        self.control.clear();
+        self.cursor = 0;
+        self.greeted = false;
+        self.resumed = false;
+    }
+
+    fn release(&mut self, received: u64) -> Result<(), Error<C::Error>> {
+        if received < self.acked || received - self.acked > self.buffer.len() as u64 {
+            return Err(Error::Session);
+        }
+        while self.acked < received {
+            self.buffer.pop_front();
+            self.cursor = self.cursor.saturating_sub(1);
+            self.acked += 1;
+        }
+        if self.buffer.len() < self.capacity {
+            if let Some(waker) = self.waiting.take() {
+                waker.wake();
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_link(&mut self, ctx: &mut Context) -> Poll<Result<bool, Error<C::Error>>> {
+        loop {
+            if self.transport.is_none() {
+                if self.closing {
+                    return Poll::Ready(Ok(false));
+                }
+                if self.connecting.is_none() {
+                    if self.attempts > self.retries {
+                        return Poll::Ready(Err(Error::Exhausted));
+                    }
+                    self.attempts += 1;
+                    self.connecting = Some(self.connect.connect());
+                }
+                let connecting = self.connecting.as_mut().unwrap();
+                let transport = ready!(Pin::new(connecting).poll(ctx));
+                self.connecting = None;
+                match transport {
+                    Ok(transport) => self.transport = Some(transport),
+                    Err(e) if self.attempts > self.retries => {
+                        return Poll::Ready(Err(Error::Connect(e)))
+                    }
+                    Err(_) => continue,
+                }
+            }
+            let transport = self.transport.as_mut().unwrap();
+            if !self.greeted {
+                match ready!(Pin::new(&mut *transport).poll_ready(ctx)) {
+                    Ok(()) => {}
+                    Err(_) => {
+                        self.disconnect();
+                        continue;
+                    }
+                }
+                let hello = Frame::Resume(self.session, self.received);
+                if Pin::new(&mut *transport).start_send(hello).is_err() {
+                    self.disconnect();
+                    continue;
+                }
+                self.reported = self.received;
+                self.greeted = true;
+            }
+            if self.resumed {
+                return Poll::Ready(Ok(true));
+            }
+            if let Poll::Ready(Err(_)) = Pin::new(&mut *transport).poll_flush(ctx) {
+                self.disconnect();
+                continue;
+            }
+            match ready!(Pin::new(&mut *transport).try_poll_next(ctx)) {
+                Some(Ok(Frame::Resume(session, received))) if session == self.session => {
+                    self.release(received)?;
+                    self.cursor = 0;
+                    self.attempts = 0;
+                    self.resumed = true;
+                }
+                Some(Ok(_)) => return Poll::Ready(Err(Error::Session)),
+                Some(Err(_)) | None => self.disconnect(),
+            }
+        }
+    }
+
+    fn poll_write(
+        &mut self,
+        ctx: &mut Context,
+        flush: bool,
+    ) -> Poll<Result<bool, Error<C::Error>>> {
+        loop {
+            if !ready!(self.poll_link(ctx))? {
+                return Poll::Ready(Ok(false));
+            }
+            let unreported = self.received - self.reported;
+            let acknowledge = unreported >= self.batch || (flush && unreported > 0);
+            let frame = if acknowledge {
+                Frame::Ack(self.received)
+            } else if let Some(frame) = self.control.front() {
+                frame.clone()
+            } else if let Some(frame) = self.buffer.get(self.cursor) {
+                frame.clone()
+            } else {
+                return Poll::Ready(Ok(true));
+            };
+            let transport = self.transport.as_mut().unwrap();
+            let sent = match ready!(Pin::new(&mut *transport).poll_ready(ctx)) {
+                Ok(()) => Pin::new(&mut *transport).start_send(frame).is_ok(),
+                Err(_) => false,
+            };
+            if !sent {
+                self.disconnect();
+                continue;
+            }
+            if acknowledge {
+                self.reported = self.received;
+            } else if self.control.pop_front().is_none() {
+                self.cursor += 1;
+            }
+        }
+    }
+}
+
+impl<R: Clone, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, C: Connect<Transport = T>>
+    Stream for Resumable<C, R>
+{
+    type Item = Result<Frame<R>, Error<C::Error>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            match ready!(this.poll_link(ctx)) {
+                Ok(true) => {}
+                Ok(false) => return Poll::Ready(None),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+            let transport = this.transport.as_mut().unwrap();
+            match ready!(Pin::new(transport).try_poll_next(ctx)) {
+                Some(Ok(Frame::Ack(received))) => {
+                    if let Err(e) = this.release(received) {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+                Some(Ok(Frame::Resume(..))) => return Poll::Ready(Some(Err(Error::Session))),
+                Some(Ok(frame)) => {
+                    if !matches!(frame, Frame::Ping | Frame::Pong) {
+                        this.received += 1;
+                    }
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+                Some(Err(_)) | None if this.closing => return Poll::Ready(None),
+                Some(Err(_)) | None => this.disconnect(),
+            }
+        }
+    }
+}
+
+impl<R: Clone, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, C: Connect<Transport = T>>
+    Sink<Frame<R>> for Resumable<C, R>
+{
+    type Error = Error<C::Error>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        if let Poll::Ready(Err(e)) = self.poll_write(ctx, false) {
+            return Poll::Ready(Err(e));
+        }
+        if self.buffer.len() < self.capacity {
+            Poll::Ready(Ok(()))
+        } else {
+            self.waiting = Some(ctx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Frame<R>) -> Result<(), Self::Error> {
+        match item {
+            Frame::Ping | Frame::Pong => self.control.push_back(item),
+            Frame::Resume(..) | Frame::Ack(..) => {}
+            item => self.buffer.push_back(item),
+        }
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        loop {
+            if !ready!(self.poll_write(ctx, true))? {
+                return Poll::Ready(Ok(()));
+            }
+            let transport = self.transport.as_mut().unwrap();
+            match ready!(Pin::new(transport).poll_flush(ctx)) {
+                Ok(()) => return Poll::Ready(Ok(())),
+                Err(_) => self.disconnect(),
+            }
+        }
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.closing = true;
+        ready!(self.as_mut().poll_flush(ctx))?;
+        if let Some(transport) = self.transport.as_mut() {
+            let _ = ready!(Pin::new(transport).poll_close(ctx));
+        }
+        self.transport = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Resume<D, C> {
+    director: D,
+    connect: C,
+    session: u64,
+    retries: u32,
+    capacity: usize,
+    batch: u64,
+}
+
+impl<D, C: Connect> Resume<D, C> {
+    pub fn new(director: D, connect: C, session: u64) -> Self {
+        Resume {
+            director,
+            connect,
+            session,
+            retries: 8,
+            capacity: 1024,
+            batch: 32,
+        }
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_batch(mut self, batch: u64) -> Self {
+        self.batch = batch.max(1);
+        self
+    }
+
+    fn wrap<R>(self, transport: C::Transport) -> (D, Resumable<C, R>) {
+        let resumable = Resumable::new(
+            self.connect,
+            transport,
+            self.session,
+            self.retries,
+            self.capacity,
+            self.batch,
+        );
+        (self.director, resumable)
+    }
+}
+
+impl<
+        F: ?Sized,
+        P: Protocol<F, D::Context>,
+        R: Clone,
+        T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>,
+        C: Connect<Transport = T>,
+        D: Director<P, F, Resumable<C, R>>,
+    > Director<P, F, T> for Resume<D, C>
+{
+    type Context = D::Context;
+    type UnravelError = D::UnravelError;
+    type Unravel = D::Unravel;
+    type CoalesceError = D::CoalesceError;
+    type Coalesce = D::Coalesce;
+
+    fn unravel(self, protocol: P, transport: T) -> Self::Unravel {
+        let (director, resumable) = self.wrap(transport);
+        director.unravel(protocol, resumable)
+    }
+
+    fn coalesce(self, transport: T) -> Self::Coalesce {
+        let (director, resumable) = self.wrap(transport);
+        director.coalesce(resumable)
+    }
+}
+
+pub struct Replay<T, R> {
+    transport: T,
+    hello: Option<Frame<R>>,
+}
+
+impl<T: Unpin, R> Unpin for Replay<T, R> {}
+
+impl<T: Unpin + TryStream<Ok = Frame<R>>, R> Stream for Replay<T, R> {
+    type Item = Result<Frame<R>, T::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        if let Some(hello) = self.hello.take() {
+            return Poll::Ready(Some(Ok(hello)));
+        }
+        Pin::new(&mut self.transport).try_poll_next(ctx)
+    }
+}
+
+impl<T: Unpin + Sink<Frame<R>>, R> Sink<Frame<R>> for Replay<T, R> {
+    type Error = T::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.transport).poll_ready(ctx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Frame<R>) -> Result<(), Self::Error> {
+        Pin::new(&mut self.transport).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.transport).poll_flush(ctx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.transport).poll_close(ctx)
+    }
+}
+
+struct Slot<T, R> {
+    transport: Option<Replay<T, R>>,
+    waker: Option<Waker>,
+}
+
+pub struct Rejoin<T, R> {
+    slot: Rc<RefCell<Slot<T, R>>>,
+}
+
+pub struct Rejoining<T, R> {
+    slot: Rc<RefCell<Slot<T, R>>>,
+}
+
+impl<T, R> Unpin for Rejoining<T, R> {}
+
+impl<T, R> Future for Rejoining<T, R> {
+    type Output = Result<Replay<T, R>, Void>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let mut slot = self.slot.borrow_mut();
+        match slot.transport.take() {
+            Some(transport) => Poll::Ready(Ok(transport)),
+            None => {
+                slot.waker = Some(ctx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T, R> Connect for Rejoin<T, R> {
+    type Transport = Replay<T, R>;
+    type Error = Void;
+    type Future = Rejoining<T, R>;
+
+    fn connect(&mut self) -> Rejoining<T, R> {
+        Rejoining {
+            slot: self.slot.clone(),
+        }
+    }
+}
+
+pub struct Sessions<T, R> {
+    sessions: BTreeMap<u64, Weak<RefCell<Slot<T, R>>>>,
+    retries: u32,
+    capacity: usize,
+    batch: u64,
+}
+
+impl<T, R> Sessions<T, R> {
+    pub fn new() -> Self {
+        Sessions {
+            sessions: BTreeMap::new(),
+            retries: 8,
+            capacity: 1024,
+            batch: 32,
+        }
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn with_batch(mut self, batch: u64) -> Self {
+        self.batch = batch.max(1);
+        self
+    }
+
+    pub fn accept(&mut self, transport: T) -> Accept<'_, T, R> {
+        Accept {
+            sessions: self,
+            transport: Some(transport),
+        }
+    }
+
+    fn route(
+        &mut self,
+        session: u64,
+        transport: Replay<T, R>,
+    ) -> Option<Resumable<Rejoin<T, R>, R>> {
+        self.sessions.retain(|_, slot| slot.strong_count() > 0);
+        if let Some(slot) = self.sessions.get(&session).and_then(Weak::upgrade) {
+            let mut slot = slot.borrow_mut();
+            slot.transport = Some(transport);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+            return None;
+        }
+        let slot = Rc::new(RefCell::new(Slot {
+            transport: None,
+            waker: None,
+        }));
+        self.sessions.insert(session, Rc::downgrade(&slot));
+        Some(Resumable::new(
+            Rejoin { slot },
+            transport,
+            session,
+            self.retries,
+            self.capacity,
+            self.batch,
+        ))
+    }
+}
+
+impl<T, R> Default for Sessions<T, R> {
+    fn default() -> Self {
+        Sessions::new()
+    }
+}
+
+pub struct Accept<'a, T, R> {
+    sessions: &'a mut Sessions<T, R>,
+    transport: Option<T>,
+}
+
+impl<'a, T: Unpin, R> Unpin for Accept<'a, T, R> {}
+
+impl<'a, T: Unpin + TryStream<Ok = Frame<R>>, R> Future for Accept<'a, T, R> {
+    type Output = Result<Option<Resumable<Rejoin<T, R>, R>>, Error<T::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let transport = this
+            .transport
+            .as_mut()
+            .expect("Accept polled after completion");
+        let (session, received) = match ready!(Pin::new(transport).try_poll_next(ctx)) {
+            Some(Ok(Frame::Resume(session, received))) => (session, received),
+            Some(Ok(_)) | None => return Poll::Ready(Err(Error::Session)),
+            Some(Err(e)) => return Poll::Ready(Err(Error::Connect(e))),
+        };
+        let transport = Replay {
+            transport: this.transport.take().unwrap(),
+            hello: Some(Frame::Resume(session, received)),
+        };
+        Poll::Ready(Ok(this.sessions.route(session, transport)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Resumable};
+    use crate::{
+        director::{Frame, Handle},
+        transport::pipe::{channel, Channel},
+    };
+    use alloc::{boxed::Box, vec::Vec};
+    use core::pin::Pin;
+    use futures::{
+        executor::block_on,
+        future::{poll_fn, ready, Ready},
+        FutureExt, Sink, SinkExt, StreamExt,
+    };
+
+    type Transport = Channel<Frame<u32>>;
+
+    type Reconnect = Box<dyn FnMut() -> Ready<Result<Transport, ()>>>;
+
+    fn resumable(
+        transport: Transport,
+        mut next: Option<Transport>,
+        batch: u64,
+    ) -> Resumable<Reconnect, u32> {
+        let connect: Reconnect = Box::new(move || ready(next.take().ok_or(())));
+        Resumable::new(connect, transport, 7, 0, 8, batch)
+    }
+
+    fn poll_ready(resumable: &mut Resumable<Reconnect, u32>) -> Option<Result<(), Error<()>>> {
+        poll_fn(|ctx| Pin::new(&mut *resumable).poll_ready(ctx)).now_or_never()
+    }
+
+    fn frames(peer: &mut Transport) -> Vec<Frame<u32>> {
+        let mut frames = Vec::new();
+        while let Some(Some(Ok(frame))) = peer.next().now_or_never() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn replays_unacknowledged_frames() {
+        let (left, mut peer) = channel();
+        let (reconnect, mut replacement) = channel();
+        let mut resumable = resumable(left, Some(reconnect), 32);
+        block_on(peer.send(Frame::Resume(7, 0))).unwrap();
+        block_on(resumable.send(Frame::Item(Handle::ROOT, 1))).unwrap();
+        block_on(resumable.send(Frame::Item(Handle::ROOT, 2))).unwrap();
+        assert!(matches!(
+            frames(&mut peer)[..],
+            [
+                Frame::Resume(7, 0),
+                Frame::Item(Handle::ROOT, 1),
+                Frame::Item(Handle::ROOT, 2)
+            ]
+        ));
+        block_on(peer.send(Frame::Ack(1))).unwrap();
+        assert!(resumable.next().now_or_never().is_none());
+        assert_eq!(resumable.buffer.len(), 1);
+        drop(peer);
+        block_on(replacement.send(Frame::Resume(7, 1))).unwrap();
+        assert!(resumable.next().now_or_never().is_none());
+        block_on(resumable.flush()).unwrap();
+        assert!(matches!(
+            frames(&mut replacement)[..],
+            [Frame::Resume(7, 0), Frame::Item(Handle::ROOT, 2)]
+        ));
+    }
+
+    #[test]
+    fn rejects_unbuffered_acks() {
+        let (left, mut peer) = channel();
+        let mut resumable = resumable(left, None, 32);
+        block_on(peer.send(Frame::Resume(7, 0))).unwrap();
+        block_on(peer.send(Frame::Ack(1))).unwrap();
+        assert!(matches!(
+            resumable.next().now_or_never(),
+            Some(Some(Err(Error::Session)))
+        ));
+    }
+
+    #[test]
+    fn acknowledges_in_batches() {
+        let (left, mut peer) = channel();
+        let mut resumable = resumable(left, None, 2);
+        block_on(peer.send(Frame::Resume(7, 0))).unwrap();
+        block_on(peer.send(Frame::Item(Handle::ROOT, 1))).unwrap();
+        block_on(peer.send(Frame::Item(Handle::ROOT, 2))).unwrap();
+        assert!(matches!(
+            resumable.next().now_or_never(),
+            Some(Some(Ok(Frame::Item(Handle::ROOT, 1))))
+        ));
+        assert!(matches!(poll_ready(&mut resumable), Some(Ok(()))));
+        assert!(matches!(frames(&mut peer)[..], [Frame::Resume(7, 0)]));
+        assert!(matches!(
+            resumable.next().now_or_never(),
+            Some(Some(Ok(Frame::Item(Handle::ROOT, 2))))
+        ));
+        assert!(matches!(poll_ready(&mut resumable), Some(Ok(()))));
+        assert!(matches!(frames(&mut peer)[..], [Frame::Ack(2)]));
+    }
+}
@@ -0,0 +1,193 @@
+use super::{Error, Frame, Slab, Status};
+use core::{
+    future::Future,
+    pin::Pin,
+    ptr,
+    task::{Context, Poll},
+};
+use futures::{ready, Sink, TryStream};
+
+pub struct Driver<'a, T, R, const N: usize, const W: usize> {
+    transport: T,
+    slab: &'a Slab<R, N, W>,
+    pending: Option<Frame<R>>,
+    busy: bool,
+}
+
+impl<'a, T, R, const N: usize, const W: usize> Driver<'a, T, R, N, W> {
+    pub(super) fn new(transport: T, slab: &'a Slab<R, N, W>) -> Self {
+        Driver {
+            transport,
+            slab,
+            pending: None,
+            busy: false,
+        }
+    }
+
+    pub(super) fn busy(transport: T, slab: &'a Slab<R, N, W>) -> Self {
+        Driver {
+            transport,
+            slab,
+            pending: None,
+            busy: true,
+        }
+    }
+}
+
+impl<'a, T: Unpin, R, const N: usize, const W: usize> Unpin for Driver<'a, T, R, N, W> {}
+
+impl<'a, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, R, const N: usize, const W: usize>
+    Driver<'a, T, R, N, W>
+{
+    fn fail(&mut self, status: Status) {
+        let mut state = self.slab.state.borrow_mut();
+        state.status = status;
+        state.wake_channels();
+    }
+
+    fn route(&mut self) -> bool {
+        let mut state = self.slab.state.borrow_mut();
+        let (handle, item) = match self.pending.take() {
+            Some(Frame::Item(handle, item)) => (handle, item),
+            Some(Frame::Close(handle)) | Some(Frame::Cancel(handle)) => {
+                if let Some(index) = state.find(handle) {
+                    let entry = &mut state.channels[index];
+                    entry.closed = true;
+                    if let Some(waker) = entry.waker.take() {
+                        waker.wake();
+                    }
+                }
+                return true;
+            }
+            Some(_) | None => return true,
+        };
+        let index = match state.claim(handle) {
+            Some(index) => index,
+            None => {
+                state.fill();
+                return false;
+            }
+        };
+        if state.channels[index].inbound.is_some() {
+            return match state.defer(handle, item) {
+                Ok(()) => true,
+                Err(item) => {
+                    self.pending = Some(Frame::Item(handle, item));
+                    false
+                }
+            };
+        }
+        let entry = &mut state.channels[index];
+        entry.inbound = Some(item);
+        if let Some(waker) = entry.waker.take() {
+            waker.wake();
+        }
+        true
+    }
+
+    fn poll_read(&mut self, ctx: &mut Context) -> Result<(), <T as TryStream>::Error> {
+        while self.route() && self.slab.state.borrow().status == Status::Open {
+            match Pin::new(&mut self.transport).try_poll_next(ctx) {
+                Poll::Ready(Some(Ok(frame))) => self.pending = Some(frame),
+                Poll::Ready(Some(Err(e))) => return Err(e),
+                Poll::Ready(None) => {
+                    let mut state = self.slab.state.borrow_mut();
+                    state.status = Status::Terminated;
+                    state.wake_channels();
+                }
+                Poll::Pending => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_tasks(&mut self, ctx: &mut Context) {
+        for index in 0..N {
+            let task = match self.slab.state.borrow().tasks[index] {
+                Some(task) => task,
+                None => continue,
+            };
+            // SAFETY: tasks are pinned in the slab's storage until dropped
+            // here or in `Drop`, and no borrow of the state is held while
+            // they run.
+            if unsafe { Pin::new_unchecked(&mut *task) }.poll(ctx).is_ready() {
+                unsafe { ptr::drop_in_place(task) };
+                self.slab.state.borrow_mut().tasks[index] = None;
+            }
+        }
+    }
+
+    fn poll_write(&mut self, ctx: &mut Context) -> Poll<Result<(), <T as Sink<Frame<R>>>::Error>> {
+        if self.slab.state.borrow().outbound.is_some() {
+            ready!(Pin::new(&mut self.transport).poll_ready(ctx))?;
+            let frame = self.slab.state.borrow_mut().outbound.take();
+            if let Some(frame) = frame {
+                Pin::new(&mut self.transport).start_send(frame)?;
+            }
+            self.slab.state.borrow_mut().wake_channels();
+        }
+        ready!(Pin::new(&mut self.transport).poll_flush(ctx))?;
+        let mut state = self.slab.state.borrow_mut();
+        if !state.flushed {
+            state.flushed = true;
+            state.wake_channels();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<'a, T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>, R, const N: usize, const W: usize>
+    Future for Driver<'a, T, R, N, W>
+{
+    type Output = Result<(), Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        if self.busy {
+            return Poll::Ready(Err(Error::Busy));
+        }
+        self.slab.state.borrow_mut().driver = Some(ctx.waker().clone());
+        if let Err(e) = self.poll_read(ctx) {
+            self.fail(Status::Failed);
+            return Poll::Ready(Err(Error::Stream(e)));
+        }
+        if self.slab.state.borrow().status == Status::Full {
+            return Poll::Ready(Err(Error::Full));
+        }
+        self.poll_tasks(ctx);
+        let written = match self.poll_write(ctx) {
+            Poll::Ready(Ok(())) => true,
+            Poll::Ready(Err(e)) => {
+                self.fail(Status::Failed);
+                return Poll::Ready(Err(Error::Sink(e)));
+            }
+            Poll::Pending => false,
+        };
+        let idle = {
+            let state = self.slab.state.borrow();
+            state.live == 0 && state.outbound.is_none() && state.tasks.iter().all(Option::is_none)
+        };
+        if written && idle {
+            return Pin::new(&mut self.transport)
+                .poll_close(ctx)
+                .map_err(Error::Sink);
+        }
+        Poll::Pending
+    }
+}
+
+impl<'a, T, R, const N: usize, const W: usize> Drop for Driver<'a, T, R, N, W> {
+    fn drop(&mut self) {
+        if self.busy {
+            return;
+        }
+        for index in 0..N {
+            let task = self.slab.state.borrow_mut().tasks[index].take();
+            if let Some(task) = task {
+                // SAFETY: the task is live and was removed from the table
+                // above, so it is dropped exactly once, within `'a`.
+                unsafe { ptr::drop_in_place(task) };
+            }
+        }
+        self.slab.state.borrow_mut().driving = false;
+    }
+}
@@ -0,0 +1,821 @@
+use super::{Director, DirectorError, Drive, Driven, Embed, Frame, Handle, Prioritize, Priority};
+use crate::{Channel, Channels, ContextError, Dispatch, Format, Join, Protocol, Spawn};
+use core::{
+    cell::{RefCell, UnsafeCell},
+    future::Future,
+    marker::PhantomData,
+    mem::{align_of, size_of, transmute, MaybeUninit},
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    ptr,
+    task::{self, Poll, Waker},
+};
+use futures::{
+    future::{ready, Either, Map, MapErr, Ready},
+    FutureExt, Sink, Stream, TryFutureExt, TryStream,
+};
+
+mod driver;
+pub use driver::Driver;
+
+#[derive(Debug)]
+pub enum Error<Stream, Sink> {
+    Stream(Stream),
+    Sink(Sink),
+    Full,
+    Busy,
+}
+
+#[derive(Debug)]
+pub enum ChannelError {
+    Disconnected,
+    Unexpected,
+    Full,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Open,
+    Terminated,
+    Failed,
+    Full,
+}
+
+// Tasks are stored with their lifetime erased. A task spawned through a
+// `&'a Slab` may borrow for `'a`, so it is only ever polled or dropped by a
+// `Driver<'a>`: tasks are only stored while that driver is live, and it drops
+// every remaining task when it is dropped. If the driver is leaked instead,
+// the slab stays busy and its tasks are leaked with it, since `'a` may have
+// ended and the slab may have moved since they were stored.
+type Task = *mut (dyn Future<Output = ()> + 'static);
+
+struct Entry<R> {
+    handle: Option<Handle>,
+    inbound: Option<R>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+struct State<R, const N: usize> {
+    channels: [Entry<R>; N],
+    tasks: [Option<Task>; N],
+    backlog: [Option<(u64, Handle, R)>; N],
+    sequence: u64,
+    outbound: Option<Frame<R>>,
+    flushed: bool,
+    next: u32,
+    live: usize,
+    status: Status,
+    driver: Option<Waker>,
+    driving: bool,
+}
+
+impl<R, const N: usize> State<R, N> {
+    fn find(&self, handle: Handle) -> Option<usize> {
+        self.channels
+            .iter()
+            .position(|entry| entry.handle == Some(handle))
+    }
+
+    fn claim(&mut self, handle: Handle) -> Option<usize> {
+        self.find(handle).or_else(|| {
+            let index = self.channels.iter().position(|entry| entry.handle.is_none())?;
+            self.channels[index].handle = Some(handle);
+            Some(index)
+        })
+    }
+
+    fn defer(&mut self, handle: Handle, item: R) -> Result<(), R> {
+        match self.backlog.iter().position(Option::is_none) {
+            Some(index) => {
+                self.backlog[index] = Some((self.sequence, handle, item));
+                self.sequence += 1;
+                Ok(())
+            }
+            None => Err(item),
+        }
+    }
+
+    fn refill(&mut self, index: usize) {
+        let handle = self.channels[index].handle;
+        let next = self
+            .backlog
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, deferred)| match deferred {
+                Some((sequence, owner, _)) if Some(*owner) == handle => Some((*sequence, slot)),
+                _ => None,
+            })
+            .min()
+            .map(|(_, slot)| slot);
+        if let Some(slot) = next {
+            self.channels[index].inbound = self.backlog[slot].take().map(|(_, _, item)| item);
+        }
+    }
+
+    fn discard(&mut self, handle: Handle) {
+        for deferred in self.backlog.iter_mut() {
+            if matches!(deferred, Some((_, owner, _)) if *owner == handle) {
+                *deferred = None;
+            }
+        }
+    }
+
+    fn allocate(&mut self) -> Handle {
+        let handle = Handle(self.next);
+        self.next += 2;
+        handle
+    }
+
+    fn fill(&mut self) {
+        self.status = Status::Full;
+        self.wake_driver();
+        self.wake_channels();
+    }
+
+    fn wake_driver(&mut self) {
+        if let Some(waker) = self.driver.take() {
+            waker.wake();
+        }
+    }
+
+    fn wake_channels(&mut self) {
+        for entry in self.channels.iter_mut() {
+            if let Some(waker) = entry.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+#[repr(C, align(16))]
+struct Storage<const W: usize>(UnsafeCell<MaybeUninit<[u8; W]>>);
+
+pub struct Slab<R, const N: usize, const W: usize = 256> {
+    state: RefCell<State<R, N>>,
+    storage: [Storage<W>; N],
+}
+
+impl<R, const N: usize, const W: usize> Slab<R, N, W> {
+    pub fn new() -> Self {
+        Slab {
+            state: RefCell::new(State {
+                channels: [(); N].map(|_| Entry {
+                    handle: None,
+                    inbound: None,
+                    waker: None,
+                    closed: false,
+                }),
+                tasks: [None; N],
+                backlog: [(); N].map(|_| None),
+                sequence: 0,
+                outbound: None,
+                flushed: true,
+                next: 0,
+                live: 0,
+                status: Status::Open,
+                driver: None,
+                driving: false,
+            }),
+            storage: [(); N].map(|_| Storage(UnsafeCell::new(MaybeUninit::uninit()))),
+        }
+    }
+
+    fn reset(&self, next: u32) -> bool {
+        let mut state = self.state.borrow_mut();
+        if state.driving || state.live != 0 || state.tasks.iter().any(Option::is_some) {
+            return false;
+        }
+        for entry in state.channels.iter_mut() {
+            *entry = Entry {
+                handle: None,
+                inbound: None,
+                waker: None,
+                closed: false,
+            };
+        }
+        for deferred in state.backlog.iter_mut() {
+            *deferred = None;
+        }
+        state.sequence = 0;
+        state.outbound = None;
+        state.flushed = true;
+        state.next = next;
+        state.status = Status::Open;
+        state.driver = None;
+        state.driving = true;
+        true
+    }
+
+    fn store<'a, T: Future<Output = ()> + 'a>(&'a self, task: T) -> Result<(), T> {
+        if size_of::<T>() > W || align_of::<T>() > align_of::<Storage<W>>() {
+            return Err(task);
+        }
+        let mut state = self.state.borrow_mut();
+        if !state.driving {
+            return Err(task);
+        }
+        let index = match state.tasks.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => return Err(task),
+        };
+        let slot = self.storage[index].0.get() as *mut T;
+        // SAFETY: the slot is unoccupied and fits `T` in both size and
+        // alignment. The task is never moved out of the slot, and a driver
+        // borrowing this slab for `'a` is live, so it is dropped before `'a`
+        // ends (see `Task`).
+        unsafe { ptr::write(slot, task) };
+        let task: *mut (dyn Future<Output = ()> + 'a) = slot;
+        state.tasks[index] = Some(unsafe { transmute(task) });
+        state.wake_driver();
+        Ok(())
+    }
+}
+
+impl<R, const N: usize, const W: usize> Default for Slab<R, N, W> {
+    fn default() -> Self {
+        Slab::new()
+    }
+}
+
+pub struct Context<'a, R, const N: usize, const W: usize> {
+    slab: &'a Slab<R, N, W>,
+}
+
+impl<'a, R, const N: usize, const W: usize> Context<'a, R, N, W> {
+    fn open<A, B>(&self, handle: Handle) -> Option<Link<'a, R, A, B, N, W>> {
+        let mut state = self.slab.state.borrow_mut();
+        let index = match state.claim(handle) {
+            Some(index) => index,
+            None => {
+                state.fill();
+                return None;
+            }
+        };
+        state.live += 1;
+        Some(Link {
+            context: Context { slab: self.slab },
+            index,
+            handle,
+            data: PhantomData,
+        })
+    }
+}
+
+struct Link<'a, R, A, B, const N: usize, const W: usize> {
+    context: Context<'a, R, N, W>,
+    index: usize,
+    handle: Handle,
+    data: PhantomData<fn(A, B)>,
+}
+
+impl<'a, R, A, B, const N: usize, const W: usize> Link<'a, R, A, B, N, W> {
+    fn poll_next<I: Embed<R>>(
+        &mut self,
+        ctx: &mut task::Context,
+    ) -> Poll<Option<Result<I, ChannelError>>> {
+        let mut state = self.context.slab.state.borrow_mut();
+        if let Some(item) = state.channels[self.index].inbound.take() {
+            state.refill(self.index);
+            state.wake_driver();
+            return Poll::Ready(Some(I::extract(item).map_err(|_| ChannelError::Unexpected)));
+        }
+        if state.channels[self.index].closed {
+            return Poll::Ready(None);
+        }
+        match state.status {
+            Status::Open => {
+                state.channels[self.index].waker = Some(ctx.waker().clone());
+                Poll::Pending
+            }
+            Status::Terminated => Poll::Ready(None),
+            Status::Failed => Poll::Ready(Some(Err(ChannelError::Disconnected))),
+            Status::Full => Poll::Ready(Some(Err(ChannelError::Full))),
+        }
+    }
+
+    fn poll_ready(&mut self, ctx: &mut task::Context) -> Poll<Result<(), ChannelError>> {
+        let mut state = self.context.slab.state.borrow_mut();
+        match state.status {
+            Status::Failed => return Poll::Ready(Err(ChannelError::Disconnected)),
+            Status::Full => return Poll::Ready(Err(ChannelError::Full)),
+            _ => {}
+        }
+        if state.outbound.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+        state.channels[self.index].waker = Some(ctx.waker().clone());
+        state.wake_driver();
+        Poll::Pending
+    }
+
+    fn start_send<I: Embed<R>>(&mut self, item: I) -> Result<(), ChannelError> {
+        let mut state = self.context.slab.state.borrow_mut();
+        match state.status {
+            Status::Failed => return Err(ChannelError::Disconnected),
+            Status::Full => return Err(ChannelError::Full),
+            _ => {}
+        }
+        state.outbound = Some(Frame::Item(self.handle, item.embed()));
+        state.flushed = false;
+        state.wake_driver();
+        Ok(())
+    }
+
+    fn poll_flush(&mut self, ctx: &mut task::Context) -> Poll<Result<(), ChannelError>> {
+        let mut state = self.context.slab.state.borrow_mut();
+        match state.status {
+            Status::Failed => return Poll::Ready(Err(ChannelError::Disconnected)),
+            Status::Full => return Poll::Ready(Err(ChannelError::Full)),
+            _ => {}
+        }
+        if state.outbound.is_none() && state.flushed {
+            return Poll::Ready(Ok(()));
+        }
+        state.channels[self.index].waker = Some(ctx.waker().clone());
+        state.wake_driver();
+        Poll::Pending
+    }
+}
+
+impl<'a, R, A, B, const N: usize, const W: usize> Drop for Link<'a, R, A, B, N, W> {
+    fn drop(&mut self) {
+        let mut state = self.context.slab.state.borrow_mut();
+        let entry = &mut state.channels[self.index];
+        entry.handle = None;
+        entry.inbound = None;
+        entry.waker = None;
+        entry.closed = false;
+        state.discard(self.handle);
+        state.live -= 1;
+        state.wake_driver();
+    }
+}
+
+pub struct Unravel<'a, R, A, B, const N: usize, const W: usize>(Link<'a, R, A, B, N, W>);
+
+pub struct Coalesce<'a, R, A, B, const N: usize, const W: usize>(Link<'a, R, A, B, N, W>);
+
+impl<'a, R, A: Embed<R>, B, const N: usize, const W: usize> Sink<A>
+    for Unravel<'a, R, A, B, N, W>
+{
+    type Error = ChannelError;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(ctx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: A) -> Result<(), Self::Error> {
+        self.0.start_send(item)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_flush(ctx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_flush(ctx)
+    }
+}
+
+impl<'a, R, A, B: Embed<R>, const N: usize, const W: usize> Stream
+    for Unravel<'a, R, A, B, N, W>
+{
+    type Item = Result<B, ChannelError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut task::Context) -> Poll<Option<Self::Item>> {
+        self.0.poll_next(ctx)
+    }
+}
+
+impl<'a, R, A, B: Embed<R>, const N: usize, const W: usize> Sink<B>
+    for Coalesce<'a, R, A, B, N, W>
+{
+    type Error = ChannelError;
+
+    fn poll_ready(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(ctx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: B) -> Result<(), Self::Error> {
+        self.0.start_send(item)
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_flush(ctx)
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context,
+    ) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_flush(ctx)
+    }
+}
+
+impl<'a, R, A: Embed<R>, B, const N: usize, const W: usize> Stream
+    for Coalesce<'a, R, A, B, N, W>
+{
+    type Item = Result<A, ChannelError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut task::Context) -> Poll<Option<Self::Item>> {
+        self.0.poll_next(ctx)
+    }
+}
+
+impl<'a, R, A, B, const N: usize, const W: usize> Deref for Unravel<'a, R, A, B, N, W> {
+    type Target = Context<'a, R, N, W>;
+
+    fn deref(&self) -> &Context<'a, R, N, W> {
+        &self.0.context
+    }
+}
+
+impl<'a, R, A, B, const N: usize, const W: usize> DerefMut for Unravel<'a, R, A, B, N, W> {
+    fn deref_mut(&mut self) -> &mut Context<'a, R, N, W> {
+        &mut self.0.context
+    }
+}
+
+impl<'a, R, A, B, const N: usize, const W: usize> Deref for Coalesce<'a, R, A, B, N, W> {
+    type Target = Context<'a, R, N, W>;
+
+    fn deref(&self) -> &Context<'a, R, N, W> {
+        &self.0.context
+    }
+}
+
+impl<'a, R, A, B, const N: usize, const W: usize> DerefMut for Coalesce<'a, R, A, B, N, W> {
+    fn deref_mut(&mut self) -> &mut Context<'a, R, N, W> {
+        &mut self.0.context
+    }
+}
+
+impl<'a, R, A: Embed<R>, B: Embed<R>, const N: usize, const W: usize>
+    Channel<B, A, Context<'a, R, N, W>> for Unravel<'a, R, A, B, N, W>
+{
+}
+
+impl<'a, R, A: Embed<R>, B: Embed<R>, const N: usize, const W: usize>
+    Channel<A, B, Context<'a, R, N, W>> for Coalesce<'a, R, A, B, N, W>
+{
+}
+
+impl<'a, R, A: Embed<R>, B: Embed<R>, const N: usize, const W: usize> Channels<A, B>
+    for Context<'a, R, N, W>
+{
+    type Unravel = Unravel<'a, R, A, B, N, W>;
+    type Coalesce = Coalesce<'a, R, A, B, N, W>;
+}
+
+impl<'a, R, const N: usize, const W: usize> Dispatch for Context<'a, R, N, W> {
+    type Handle = Handle;
+}
+
+impl<'a, R, const N: usize, const W: usize> Prioritize for Context<'a, R, N, W> {
+    fn prioritize(&mut self, _: Priority) {}
+}
+
+type Full<T, E> = Ready<Result<T, ContextError<ChannelError, E>>>;
+
+fn full<T, E>() -> Full<T, E> {
+    ready(Err(ContextError::Context(ChannelError::Full)))
+}
+
+impl<
+        'a,
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        R,
+        P: Protocol<F, Context<'a, R, N, W>>,
+        const N: usize,
+        const W: usize,
+    > Join<P, F> for Context<'a, R, N, W>
+where
+    P::Unravel: Embed<R>,
+    P::Coalesce: Embed<R>,
+{
+    type Error = ChannelError;
+    type Target = Context<'a, R, N, W>;
+    type Output = Either<
+        MapErr<
+            P::CoalesceFuture,
+            fn(P::CoalesceError) -> ContextError<ChannelError, P::CoalesceError>,
+        >,
+        Full<P, P::CoalesceError>,
+    >;
+
+    fn join(&mut self, handle: Handle) -> Self::Output {
+        match self.open(handle) {
+            Some(link) => Either::Left(P::coalesce(Coalesce(link)).map_err(ContextError::Protocol)),
+            None => Either::Right(full()),
+        }
+    }
+}
+
+impl<
+        'a,
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        R,
+        P: Protocol<F, Context<'a, R, N, W>>,
+        const N: usize,
+        const W: usize,
+    > Spawn<P, F> for Context<'a, R, N, W>
+where
+    P::Unravel: Embed<R>,
+    P::Coalesce: Embed<R>,
+    P::UnravelFuture: 'a,
+    P::UnravelError: 'a,
+{
+    type Error = ChannelError;
+    type Target = Context<'a, R, N, W>;
+    type Output = Full<Handle, P::UnravelError>;
+
+    fn spawn(&mut self, protocol: P) -> Self::Output {
+        let handle = self.slab.state.borrow_mut().allocate();
+        let link = match self.open(handle) {
+            Some(link) => link,
+            None => return full(),
+        };
+        let child: Map<P::UnravelFuture, fn(_)> = protocol.unravel(Unravel(link)).map(drop);
+        match self.slab.store(child) {
+            Ok(()) => ready(Ok(handle)),
+            Err(_) => {
+                self.slab.state.borrow_mut().fill();
+                full()
+            }
+        }
+    }
+}
+
+impl<
+        'a,
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        P: Protocol<F, Context<'a, R, N, W>>,
+        R,
+        T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>,
+        const N: usize,
+        const W: usize,
+    > Drive<P, F, T> for &'a Slab<R, N, W>
+where
+    P::Unravel: Embed<R>,
+    P::Coalesce: Embed<R>,
+    P::UnravelFuture: Unpin,
+    P::CoalesceFuture: Unpin,
+{
+    type DriverError = Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>;
+    type Driver = Driver<'a, T, R, N, W>;
+    type UnravelHandle = Either<
+        MapErr<
+            P::UnravelFuture,
+            fn(P::UnravelError) -> DirectorError<Self::UnravelError, P::UnravelError>,
+        >,
+        Ready<Result<(), DirectorError<Self::UnravelError, P::UnravelError>>>,
+    >;
+    type CoalesceHandle = Either<
+        MapErr<
+            P::CoalesceFuture,
+            fn(P::CoalesceError) -> DirectorError<Self::CoalesceError, P::CoalesceError>,
+        >,
+        Ready<Result<P, DirectorError<Self::CoalesceError, P::CoalesceError>>>,
+    >;
+
+    fn unravel_driven(self, protocol: P, transport: T) -> (Self::UnravelHandle, Self::Driver) {
+        use DirectorError::{Director, Protocol};
+        if !self.reset(2) {
+            return (
+                Either::Right(ready(Err(Director(Error::Busy)))),
+                Driver::busy(transport, self),
+            );
+        }
+        let context = Context { slab: self };
+        let handle = match context.open(Handle::ROOT) {
+            Some(link) => Either::Left(protocol.unravel(Unravel(link)).map_err(Protocol as fn(_) -> _)),
+            None => Either::Right(ready(Err(Director(Error::Full)))),
+        };
+        (handle, Driver::new(transport, self))
+    }
+
+    fn coalesce_driven(self, transport: T) -> (Self::CoalesceHandle, Self::Driver) {
+        use DirectorError::{Director, Protocol};
+        if !self.reset(1) {
+            return (
+                Either::Right(ready(Err(Director(Error::Busy)))),
+                Driver::busy(transport, self),
+            );
+        }
+        let context = Context { slab: self };
+        let handle = match context.open(Handle::ROOT) {
+            Some(link) => Either::Left(P::coalesce(Coalesce(link)).map_err(Protocol as fn(_) -> _)),
+            None => Either::Right(ready(Err(Director(Error::Full)))),
+        };
+        (handle, Driver::new(transport, self))
+    }
+}
+
+impl<
+        'a,
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        P: Protocol<F, Context<'a, R, N, W>>,
+        R,
+        T: Unpin + TryStream<Ok = Frame<R>> + Sink<Frame<R>>,
+        const N: usize,
+        const W: usize,
+    > Director<P, F, T> for &'a Slab<R, N, W>
+where
+    P::Unravel: Embed<R>,
+    P::Coalesce: Embed<R>,
+    P::UnravelFuture: Unpin,
+    P::CoalesceFuture: Unpin,
+{
+    type Context = Context<'a, R, N, W>;
+    type UnravelError = Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>;
+    type Unravel = Driven<<Self as Drive<P, F, T>>::UnravelHandle, Driver<'a, T, R, N, W>>;
+    type CoalesceError = Error<<T as TryStream>::Error, <T as Sink<Frame<R>>>::Error>;
+    type Coalesce = Driven<<Self as Drive<P, F, T>>::CoalesceHandle, Driver<'a, T, R, N, W>>;
+
+    fn unravel(self, protocol: P, transport: T) -> Self::Unravel {
+        let (handle, driver) = Drive::<P, F, T>::unravel_driven(self, protocol, transport);
+        Driven::new(handle, driver, true)
+    }
+
+    fn coalesce(self, transport: T) -> Self::Coalesce {
+        let (handle, driver) = Drive::<P, F, T>::coalesce_driven(self, transport);
+        Driven::new(handle, driver, false)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::{ChannelError, Error, Slab};
+    use crate::{
+        director::{Director, DirectorError, Frame},
+        format::Null,
+        option,
+        transport::pipe::{channel, Channel},
+        Bottom, Channels, ContextError, Protocol,
+    };
+    use alloc::rc::Rc;
+    use core::{
+        cell::Cell,
+        future::Future,
+        mem::forget,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use futures::{
+        executor::block_on,
+        future::{join, pending, Pending},
+        FutureExt,
+    };
+    use void::Void;
+
+    type Transport = Channel<Frame<u32>>;
+
+    struct Hold(Rc<Cell<bool>>);
+
+    struct Held(Rc<Cell<bool>>);
+
+    impl Future for Held {
+        type Output = Result<(), Void>;
+
+        fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+            Poll::Pending
+        }
+    }
+
+    impl Drop for Held {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    impl<F: ?Sized, C> Protocol<F, C> for Hold {
+        type Unravel = Bottom;
+        type UnravelError = Void;
+        type UnravelFuture = Held;
+        type Coalesce = Bottom;
+        type CoalesceError = Void;
+        type CoalesceFuture = Pending<Result<Hold, Void>>;
+
+        fn unravel(self, _: C::Unravel) -> Self::UnravelFuture
+        where
+            C: Channels<Self::Unravel, Self::Coalesce>,
+        {
+            Held(self.0)
+        }
+
+        fn coalesce(_: C::Coalesce) -> Self::CoalesceFuture
+        where
+            C: Channels<Self::Unravel, Self::Coalesce>,
+        {
+            pending()
+        }
+    }
+
+    #[test]
+    fn round_trips_spawned_children() {
+        let (left, right) = channel();
+        let (unravelling, coalescing) = (Slab::<u32, 4>::new(), Slab::<u32, 4>::new());
+        let unravel = <&Slab<u32, 4> as Director<_, Null, Transport>>::unravel(
+            &unravelling,
+            Some(Some(())),
+            left,
+        );
+        let coalesce = <&Slab<u32, 4> as Director<Option<Option<()>>, Null, Transport>>::coalesce(
+            &coalescing,
+            right,
+        );
+        let (unravelled, coalesced) = block_on(join(unravel, coalesce));
+        unravelled.unwrap();
+        assert_eq!(coalesced.unwrap(), Some(Some(())));
+    }
+
+    #[test]
+    fn reports_exhausted_channels() {
+        let (left, _right) = channel();
+        let slab = Slab::<u32, 1>::new();
+        let unravel =
+            <&Slab<u32, 1> as Director<_, Null, Transport>>::unravel(&slab, Some(Some(())), left);
+        assert!(matches!(
+            block_on(unravel),
+            Err(DirectorError::Protocol(option::Error::Unravel(
+                ContextError::Context(ChannelError::Full)
+            )))
+        ));
+    }
+
+    #[test]
+    fn rejects_concurrent_sessions() {
+        let (left, _right) = channel();
+        let (other, _peer) = channel();
+        let slab = Slab::<u32, 4>::new();
+        let mut first =
+            <&Slab<u32, 4> as Director<Option<()>, Null, Transport>>::coalesce(&slab, left);
+        assert!((&mut first).now_or_never().is_none());
+        let second =
+            <&Slab<u32, 4> as Director<Option<()>, Null, Transport>>::coalesce(&slab, other);
+        assert!(matches!(
+            block_on(second),
+            Err(DirectorError::Director(Error::Busy))
+        ));
+        drop(first);
+        let (left, _right) = channel();
+        let mut third =
+            <&Slab<u32, 4> as Director<Option<()>, Null, Transport>>::coalesce(&slab, left);
+        assert!((&mut third).now_or_never().is_none());
+    }
+
+    #[test]
+    fn drops_tasks_with_driver() {
+        let (left, _right) = channel();
+        let dropped = Rc::new(Cell::new(false));
+        let slab = Slab::<u32, 4>::new();
+        let mut unravel = <&Slab<u32, 4> as Director<_, Null, Transport>>::unravel(
+            &slab,
+            Some(Hold(dropped.clone())),
+            left,
+        );
+        assert!((&mut unravel).now_or_never().is_none());
+        assert!(!dropped.get());
+        drop(unravel);
+        assert!(dropped.get());
+    }
+
+    #[test]
+    fn leaks_tasks_of_leaked_drivers() {
+        let (left, _right) = channel();
+        let dropped = Rc::new(Cell::new(false));
+        let slab = Slab::<u32, 4>::new();
+        let mut unravel = <&Slab<u32, 4> as Director<_, Null, Transport>>::unravel(
+            &slab,
+            Some(Hold(dropped.clone())),
+            left,
+        );
+        assert!((&mut unravel).now_or_never().is_none());
+        forget(unravel);
+        let (other, _peer) = channel();
+        let second =
+            <&Slab<u32, 4> as Director<Option<()>, Null, Transport>>::coalesce(&slab, other);
+        assert!(matches!(
+            block_on(second),
+            Err(DirectorError::Director(Error::Busy))
+        ));
+        drop(slab);
+        assert!(!dropped.get());
+    }
+}
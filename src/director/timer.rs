@@ -0,0 +1,104 @@
+use super::Director;
+use crate::{Join, Protocol};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures::TryFuture;
+
+pub trait Timer {
+    type Delay: Future<Output = ()> + Unpin;
+
+    fn delay(&mut self, duration: Duration) -> Self::Delay;
+}
+
+#[derive(Debug)]
+pub enum TimeoutError<T> {
+    Elapsed,
+    Inner(T),
+}
+
+pub struct Timeout<T, D> {
+    future: T,
+    delay: D,
+}
+
+impl<T, D> Timeout<T, D> {
+    pub fn new(future: T, delay: D) -> Self {
+        Timeout { future, delay }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.future
+    }
+}
+
+impl<T: TryFuture + Unpin, D: Future<Output = ()> + Unpin> Future for Timeout<T, D> {
+    type Output = Result<T::Ok, TimeoutError<T::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = Pin::new(&mut self.future).try_poll(ctx) {
+            return Poll::Ready(output.map_err(TimeoutError::Inner));
+        }
+        Pin::new(&mut self.delay)
+            .poll(ctx)
+            .map(|_| Err(TimeoutError::Elapsed))
+    }
+}
+
+pub fn coalesce_timeout<
+    P: Protocol<F, D::Context>,
+    F: ?Sized,
+    T,
+    D: Director<P, F, T>,
+    C: Timer,
+>(
+    director: D,
+    transport: T,
+    timer: &mut C,
+    duration: Duration,
+) -> Timeout<D::Coalesce, C::Delay> {
+    Timeout::new(director.coalesce(transport), timer.delay(duration))
+}
+
+pub fn join_timeout<P: Protocol<F, J::Target>, F: ?Sized, J: Join<P, F>, C: Timer>(
+    context: &mut J,
+    handle: J::Handle,
+    timer: &mut C,
+    duration: Duration,
+) -> Timeout<J::Output, C::Delay> {
+    Timeout::new(context.join(handle), timer.delay(duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Timeout, TimeoutError};
+    use futures::{
+        future::{pending, ready},
+        FutureExt,
+    };
+
+    #[test]
+    fn resolves_before_delay() {
+        let timeout = Timeout::new(ready(Ok::<_, ()>(1)), ready(()));
+        assert!(matches!(timeout.now_or_never(), Some(Ok(1))));
+        let timeout = Timeout::new(ready(Err::<(), _>(2)), ready(()));
+        assert!(matches!(
+            timeout.now_or_never(),
+            Some(Err(TimeoutError::Inner(2)))
+        ));
+    }
+
+    #[test]
+    fn elapses_after_delay() {
+        let mut timeout = Timeout::new(pending::<Result<(), ()>>(), pending::<()>());
+        assert!((&mut timeout).now_or_never().is_none());
+        let timeout = Timeout::new(timeout.into_inner(), ready(()));
+        assert!(matches!(
+            timeout.now_or_never(),
+            Some(Err(TimeoutError::Elapsed))
+        ));
+    }
+}
@@ -1,184 +1,496 @@
-use super::{Director, DirectorError, Empty};
+use super::{Director, DirectorError};
 use crate::{Bottom, Channel, Channels, ContextError, Dispatch, Format, Join, Protocol, Spawn};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, collections::VecDeque, rc::Rc};
+#[cfg(feature = "alloc")]
+use core::{any::Any, cell::RefCell, future::Future};
 use core::{
-    marker::PhantomData,
     ops::{Deref, DerefMut},
     pin::Pin,
     task::{self, Poll},
 };
+#[cfg(feature = "alloc")]
+use futures::future::{ready, Ready};
 use futures::{
-    future::MapErr, stream::IntoStream, Sink, Stream, TryFutureExt, TryStream, TryStreamExt,
+    future::{Either, MapErr},
+    ready,
+    stream::IntoStream,
+    Sink, Stream, TryFutureExt, TryStream, TryStreamExt,
 };
 use void::Void;
 
-pub struct Context<T, U>(PhantomData<(T, U)>);
+pub trait Embed<R>: Sized {
+    fn embed(self) -> R;
 
-pub struct Unravel<T, U>(IntoStream<U>, Context<T, U>);
+    fn extract(representation: R) -> Result<Self, R>;
+}
+
+impl<R> Embed<R> for Bottom {
+    fn embed(self) -> R {
+        match self {}
+    }
+
+    fn extract(representation: R) -> Result<Self, R> {
+        Err(representation)
+    }
+}
 
-pub struct Coalesce<T, U>(IntoStream<T>, Context<T, U>);
+impl Embed<()> for () {
+    fn embed(self) {}
 
-impl<T: Unpin + TryStream, U: Unpin + Sink<<T as TryStream>::Ok>> Sink<T::Ok> for Unravel<T, U> {
-    type Error = <U as Sink<T::Ok>>::Error;
+    fn extract(_: ()) -> Result<Self, ()> {
+        Ok(())
+    }
+}
+
+impl Embed<u32> for u32 {
+    fn embed(self) -> u32 {
+        self
+    }
+
+    fn extract(representation: u32) -> Result<Self, u32> {
+        Ok(representation)
+    }
+}
+
+impl Embed<(u32, u64)> for (u32, u64) {
+    fn embed(self) -> (u32, u64) {
+        self
+    }
+
+    fn extract(representation: (u32, u64)) -> Result<Self, (u32, u64)> {
+        Ok(representation)
+    }
+}
+
+impl<T, U> Embed<Either<T, U>> for Either<T, U> {
+    fn embed(self) -> Self {
+        self
+    }
+
+    fn extract(representation: Self) -> Result<Self, Self> {
+        Ok(representation)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error<T> {
+    Transport(T),
+    Unexpected,
+    #[cfg(feature = "alloc")]
+    Nested(Box<dyn Any>),
+}
+
+#[cfg(feature = "alloc")]
+struct Slot<T>(Rc<RefCell<Option<T>>>);
+
+#[cfg(not(feature = "alloc"))]
+struct Slot<T>(Option<T>);
+
+impl<T> Slot<T> {
+    #[cfg(feature = "alloc")]
+    fn new(item: Option<T>) -> Self {
+        Slot(Rc::new(RefCell::new(item)))
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn new(item: Option<T>) -> Self {
+        Slot(item)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn share(&self) -> Self {
+        Slot(self.0.clone())
+    }
+
+    #[cfg(feature = "alloc")]
+    fn with<R>(&mut self, call: impl FnOnce(Option<&mut T>) -> R) -> R {
+        call(self.0.borrow_mut().as_mut())
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn with<R>(&mut self, call: impl FnOnce(Option<&mut T>) -> R) -> R {
+        call(self.0.as_mut())
+    }
+}
+
+#[cfg(feature = "alloc")]
+type Deferred = Pin<Box<dyn Future<Output = Result<(), Box<dyn Any>>>>>;
+
+pub struct Context<T, U> {
+    coalesce: Slot<IntoStream<T>>,
+    unravel: Slot<IntoStream<U>>,
+    #[cfg(feature = "alloc")]
+    deferred: VecDeque<Deferred>,
+    #[cfg(feature = "alloc")]
+    released: usize,
+    root: bool,
+}
+
+impl<T, U> Context<T, U> {
+    fn new(coalesce: Option<IntoStream<T>>, unravel: Option<IntoStream<U>>) -> Self {
+        Context {
+            coalesce: Slot::new(coalesce),
+            unravel: Slot::new(unravel),
+            #[cfg(feature = "alloc")]
+            deferred: VecDeque::new(),
+            #[cfg(feature = "alloc")]
+            released: 0,
+            root: true,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn fork(&self) -> Self {
+        Context {
+            coalesce: self.coalesce.share(),
+            unravel: self.unravel.share(),
+            deferred: VecDeque::new(),
+            released: 0,
+            root: false,
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn fork(&self) -> Self {
+        Context {
+            coalesce: Slot::new(None),
+            unravel: Slot::new(None),
+            root: false,
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn poll_deferred<E>(&mut self, ctx: &mut task::Context) -> Poll<Result<(), Error<E>>> {
+        while self.released > 0 {
+            let child = self
+                .deferred
+                .front_mut()
+                .expect("violated invariant in Trivial: released more children than deferred");
+            ready!(child.as_mut().poll(ctx)).map_err(Error::Nested)?;
+            self.deferred.pop_front();
+            self.released -= 1;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn poll_deferred<E>(&mut self, _: &mut task::Context) -> Poll<Result<(), Error<E>>> {
+        Poll::Ready(Ok(()))
+    }
+
+    #[cfg(feature = "alloc")]
+    fn release(&mut self) {
+        self.released = self.deferred.len();
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn release(&mut self) {}
+}
+
+pub struct Unravel<T: TryStream, U: TryStream, A, B> {
+    context: Context<T, U>,
+    embed: fn(A) -> T::Ok,
+    extract: fn(U::Ok) -> Result<B, U::Ok>,
+}
+
+pub struct Coalesce<T: TryStream, U: TryStream, A, B> {
+    context: Context<T, U>,
+    embed: fn(B) -> U::Ok,
+    extract: fn(T::Ok) -> Result<A, T::Ok>,
+}
+
+impl<T: Unpin + TryStream, U: Unpin + TryStream + Sink<T::Ok>, A, B> Sink<A>
+    for Unravel<T, U, A, B>
+{
+    type Error = Error<<U as Sink<T::Ok>>::Error>;
 
     fn poll_ready(
         mut self: Pin<&mut Self>,
         ctx: &mut task::Context,
     ) -> Poll<Result<(), Self::Error>> {
-        Pin::new(&mut self.0).poll_ready(ctx)
+        ready!(self.context.poll_deferred(ctx))?;
+        self.context.unravel.with(|transport| match transport {
+            Some(transport) => Pin::new(transport).poll_ready(ctx).map_err(Error::Transport),
+            None => Poll::Ready(Ok(())),
+        })
     }
 
-    fn start_send(mut self: core::pin::Pin<&mut Self>, item: T::Ok) -> Result<(), Self::Error> {
-        Pin::new(&mut self.0).start_send(item)
+    fn start_send(mut self: core::pin::Pin<&mut Self>, item: A) -> Result<(), Self::Error> {
+        let item = (self.embed)(item);
+        self.context.release();
+        self.context.unravel.with(|transport| match transport {
+            Some(transport) => Pin::new(transport)
+                .start_send(item)
+                .map_err(Error::Transport),
+            None => panic!("sent item over detached channel in Trivial"),
+        })
     }
 
     fn poll_flush(
         mut self: Pin<&mut Self>,
         ctx: &mut task::Context,
     ) -> Poll<Result<(), Self::Error>> {
-        Pin::new(&mut self.0).poll_flush(ctx)
+        ready!(self.context.poll_deferred(ctx))?;
+        self.context.unravel.with(|transport| match transport {
+            Some(transport) => Pin::new(transport).poll_flush(ctx).map_err(Error::Transport),
+            None => Poll::Ready(Ok(())),
+        })
     }
 
     fn poll_close(
         mut self: Pin<&mut Self>,
         ctx: &mut task::Context,
     ) -> Poll<Result<(), Self::Error>> {
-        Pin::new(&mut self.0).poll_close(ctx)
+        self.context.release();
+        ready!(self.context.poll_deferred(ctx))?;
+        let root = self.context.root;
+        self.context.unravel.with(|transport| match transport {
+            Some(transport) if root => {
+                Pin::new(transport).poll_close(ctx).map_err(Error::Transport)
+            }
+            Some(transport) => Pin::new(transport).poll_flush(ctx).map_err(Error::Transport),
+            None => Poll::Ready(Ok(())),
+        })
     }
 }
 
-impl<T: Unpin, U: Unpin + TryStream> Stream for Unravel<T, U> {
-    type Item = <IntoStream<U> as Stream>::Item;
+impl<T: Unpin + TryStream, U: Unpin + TryStream, A, B> Stream for Unravel<T, U, A, B> {
+    type Item = Result<B, Error<U::Error>>;
 
     fn poll_next(mut self: Pin<&mut Self>, ctx: &mut task::Context) -> Poll<Option<Self::Item>> {
-        Pin::new(&mut self.0).poll_next(ctx)
+        let extract = self.extract;
+        self.context.unravel.with(|transport| match transport {
+            Some(transport) => Poll::Ready(ready!(Pin::new(transport).poll_next(ctx)).map(
+                |item| {
+                    item.map_err(Error::Transport)
+                        .and_then(|item| extract(item).map_err(|_| Error::Unexpected))
+                },
+            )),
+            None => Poll::Ready(None),
+        })
     }
 }
 
-impl<T: Unpin + Sink<U::Ok> + TryStream, U: Unpin + TryStream + Sink<<T as TryStream>::Ok>>
-    Sink<U::Ok> for Coalesce<T, U>
+impl<T: Unpin + TryStream + Sink<U::Ok>, U: Unpin + TryStream, A, B> Sink<B>
+    for Coalesce<T, U, A, B>
 {
-    type Error = <T as Sink<U::Ok>>::Error;
+    type Error = Error<<T as Sink<U::Ok>>::Error>;
 
     fn poll_ready(
         mut self: Pin<&mut Self>,
         ctx: &mut task::Context,
     ) -> Poll<Result<(), Self::Error>> {
-        Pin::new(&mut self.0).poll_ready(ctx)
+        self.context.coalesce.with(|transport| match transport {
+            Some(transport) => Pin::new(transport).poll_ready(ctx).map_err(Error::Transport),
+            None => Poll::Ready(Ok(())),
+        })
     }
 
-    fn start_send(mut self: core::pin::Pin<&mut Self>, item: U::Ok) -> Result<(), Self::Error> {
-        Pin::new(&mut self.0).start_send(item)
+    fn start_send(mut self: core::pin::Pin<&mut Self>, item: B) -> Result<(), Self::Error> {
+        let item = (self.embed)(item);
+        self.context.coalesce.with(|transport| match transport {
+            Some(transport) => Pin::new(transport)
+                .start_send(item)
+                .map_err(Error::Transport),
+            None => panic!("sent item over detached channel in Trivial"),
+        })
     }
 
     fn poll_flush(
         mut self: Pin<&mut Self>,
         ctx: &mut task::Context,
     ) -> Poll<Result<(), Self::Error>> {
-        Pin::new(&mut self.0).poll_flush(ctx)
+        self.context.coalesce.with(|transport| match transport {
+            Some(transport) => Pin::new(transport).poll_flush(ctx).map_err(Error::Transport),
+            None => Poll::Ready(Ok(())),
+        })
     }
 
     fn poll_close(
         mut self: Pin<&mut Self>,
         ctx: &mut task::Context,
     ) -> Poll<Result<(), Self::Error>> {
-        Pin::new(&mut self.0).poll_close(ctx)
+        let root = self.context.root;
+        self.context.coalesce.with(|transport| match transport {
+            Some(transport) if root => {
+                Pin::new(transport).poll_close(ctx).map_err(Error::Transport)
+            }
+            Some(transport) => Pin::new(transport).poll_flush(ctx).map_err(Error::Transport),
+            None => Poll::Ready(Ok(())),
+        })
     }
 }
 
-impl<T: Unpin + TryStream, U: Unpin + Stream> Stream for Coalesce<T, U> {
-    type Item = <IntoStream<T> as Stream>::Item;
+impl<T: Unpin + TryStream, U: Unpin + TryStream, A, B> Stream for Coalesce<T, U, A, B> {
+    type Item = Result<A, Error<T::Error>>;
 
     fn poll_next(mut self: Pin<&mut Self>, ctx: &mut task::Context) -> Poll<Option<Self::Item>> {
-        Pin::new(&mut self.0).poll_next(ctx)
+        let extract = self.extract;
+        self.context.coalesce.with(|transport| match transport {
+            Some(transport) => Poll::Ready(ready!(Pin::new(transport).poll_next(ctx)).map(
+                |item| {
+                    item.map_err(Error::Transport)
+                        .and_then(|item| extract(item).map_err(|_| Error::Unexpected))
+                },
+            )),
+            None => Poll::Ready(None),
+        })
     }
 }
 
-impl<T, U> Deref for Unravel<T, U> {
+impl<T: TryStream, U: TryStream, A, B> Deref for Unravel<T, U, A, B> {
     type Target = Context<T, U>;
 
     fn deref(&self) -> &Context<T, U> {
-        &self.1
+        &self.context
     }
 }
 
-impl<T, U> DerefMut for Unravel<T, U> {
+impl<T: TryStream, U: TryStream, A, B> DerefMut for Unravel<T, U, A, B> {
     fn deref_mut(&mut self) -> &mut Context<T, U> {
-        &mut self.1
+        &mut self.context
     }
 }
 
-impl<T, U> Deref for Coalesce<T, U> {
+impl<T: TryStream, U: TryStream, A, B> Deref for Coalesce<T, U, A, B> {
     type Target = Context<T, U>;
 
     fn deref(&self) -> &Context<T, U> {
-        &self.1
+        &self.context
     }
 }
 
-impl<T, U> DerefMut for Coalesce<T, U> {
+impl<T: TryStream, U: TryStream, A, B> DerefMut for Coalesce<T, U, A, B> {
     fn deref_mut(&mut self) -> &mut Context<T, U> {
-        &mut self.1
+        &mut self.context
     }
 }
 
-impl<T: Unpin + TryStream + Sink<U::Ok>, U: Unpin + TryStream + Sink<<T as TryStream>::Ok>>
-    Channel<U::Ok, T::Ok, Context<T, U>> for Unravel<T, U>
+impl<T: Unpin + TryStream + Sink<U::Ok>, U: Unpin + TryStream + Sink<T::Ok>, A, B>
+    Channel<B, A, Context<T, U>> for Unravel<T, U, A, B>
 {
 }
 
-impl<T: Unpin + TryStream + Sink<U::Ok>, U: Unpin + TryStream + Sink<<T as TryStream>::Ok>>
-    Channel<T::Ok, U::Ok, Context<T, U>> for Coalesce<T, U>
+impl<T: Unpin + TryStream + Sink<U::Ok>, U: Unpin + TryStream + Sink<T::Ok>, A, B>
+    Channel<A, B, Context<T, U>> for Coalesce<T, U, A, B>
 {
 }
 
-impl<T: Unpin + TryStream + Sink<U::Ok>, U: Unpin + TryStream + Sink<<T as TryStream>::Ok>>
-    Channels<T::Ok, U::Ok> for Context<T, U>
+impl<T: Unpin + TryStream + Sink<U::Ok>, U: Unpin + TryStream + Sink<T::Ok>, A, B> Channels<A, B>
+    for Context<T, U>
 {
-    type Unravel = Unravel<T, U>;
-    type Coalesce = Coalesce<T, U>;
+    type Unravel = Unravel<T, U, A, B>;
+    type Coalesce = Coalesce<T, U, A, B>;
 }
 
 impl<T, U> Dispatch for Context<T, U> {
     type Handle = ();
 }
 
+#[cfg(not(feature = "alloc"))]
 impl<
         F: ?Sized + Format<Bottom>,
-        T,
-        U,
-        P: Protocol<F, Context<Empty, Empty>, Unravel = Bottom, Coalesce = Bottom>,
+        T: Unpin + TryStream + Sink<U::Ok>,
+        U: Unpin + TryStream + Sink<T::Ok>,
+        P: Protocol<F, Context<T, U>, Unravel = Bottom, Coalesce = Bottom>,
     > Join<P, F> for Context<T, U>
 {
     type Error = Void;
-    type Target = Context<Empty, Empty>;
+    type Target = Context<T, U>;
     type Output =
         MapErr<P::CoalesceFuture, fn(P::CoalesceError) -> ContextError<Void, P::CoalesceError>>;
 
     fn join(&mut self, _: ()) -> Self::Output {
-        P::coalesce(Coalesce(Empty::new().into_stream(), Context(PhantomData)))
-            .map_err(ContextError::Protocol)
+        P::coalesce(Coalesce {
+            context: self.fork(),
+            embed: Bottom::embed,
+            extract: Bottom::extract,
+        })
+        .map_err(ContextError::Protocol)
     }
 }
 
+#[cfg(not(feature = "alloc"))]
 impl<
         F: ?Sized + Format<Bottom>,
-        T: Unpin,
-        U: Unpin,
-        P: Protocol<F, Context<Empty, Empty>, Unravel = Bottom, Coalesce = Bottom>,
+        T: Unpin + TryStream + Sink<U::Ok>,
+        U: Unpin + TryStream + Sink<T::Ok>,
+        P: Protocol<F, Context<T, U>, Unravel = Bottom, Coalesce = Bottom>,
     > Spawn<P, F> for Context<T, U>
 {
     type Error = Void;
-    type Target = Context<Empty, Empty>;
+    type Target = Context<T, U>;
     type Output =
         MapErr<P::UnravelFuture, fn(P::UnravelError) -> ContextError<Void, P::UnravelError>>;
 
     fn spawn(&mut self, protocol: P) -> Self::Output {
         protocol
-            .unravel(Unravel(Empty::new().into_stream(), Context(PhantomData)))
+            .unravel(Unravel {
+                context: self.fork(),
+                embed: Bottom::embed,
+                extract: Bottom::extract,
+            })
             .map_err(ContextError::Protocol)
     }
 }
 
+#[cfg(feature = "alloc")]
+impl<
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        T: Unpin + TryStream + Sink<U::Ok>,
+        U: Unpin + TryStream + Sink<T::Ok>,
+        P: Protocol<F, Context<T, U>>,
+    > Join<P, F> for Context<T, U>
+where
+    P::Unravel: Embed<T::Ok>,
+    P::Coalesce: Embed<U::Ok>,
+{
+    type Error = Void;
+    type Target = Context<T, U>;
+    type Output =
+        MapErr<P::CoalesceFuture, fn(P::CoalesceError) -> ContextError<Void, P::CoalesceError>>;
+
+    fn join(&mut self, _: ()) -> Self::Output {
+        P::coalesce(Coalesce {
+            context: self.fork(),
+            embed: Embed::embed,
+            extract: Embed::extract,
+        })
+        .map_err(ContextError::Protocol)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<
+        F: ?Sized + Format<P::Unravel> + Format<P::Coalesce>,
+        T: Unpin + TryStream + Sink<U::Ok> + 'static,
+        U: Unpin + TryStream + Sink<T::Ok> + 'static,
+        P: Protocol<F, Context<T, U>>,
+    > Spawn<P, F> for Context<T, U>
+where
+    P::Unravel: Embed<T::Ok>,
+    P::Coalesce: Embed<U::Ok>,
+    P::UnravelFuture: 'static,
+    P::UnravelError: 'static,
+{
+    type Error = Void;
+    type Target = Context<T, U>;
+    type Output = Ready<Result<(), ContextError<Void, P::UnravelError>>>;
+
+    fn spawn(&mut self, protocol: P) -> Self::Output {
+        let child = protocol.unravel(Unravel {
+            context: self.fork(),
+            embed: Embed::embed,
+            extract: Embed::extract,
+        });
+        self.deferred
+            .push_back(Box::pin(child.map_err(|e| Box::new(e) as Box<dyn Any>)));
+        ready(Ok(()))
+    }
+}
+
+#[derive(Clone, Copy)]
 pub struct Trivial;
 
 impl<
@@ -199,19 +511,25 @@ impl<
     fn unravel(self, protocol: P, transport: T) -> Self::Unravel {
         use DirectorError::Protocol;
         protocol
-            .unravel(Unravel::<U, T>(
-                transport.into_stream(),
-                Context(PhantomData),
-            ))
+            .unravel(Unravel::<U, T, _, _> {
+                context: Context::new(None, Some(transport.into_stream())),
+                embed: identity,
+                extract: Ok,
+            })
             .map_err(Protocol)
     }
 
     fn coalesce(self, transport: U) -> Self::Coalesce {
         use DirectorError::Protocol;
-        P::coalesce(Coalesce::<U, T>(
-            transport.into_stream(),
-            Context(PhantomData),
-        ))
+        P::coalesce(Coalesce::<U, T, _, _> {
+            context: Context::new(Some(transport.into_stream()), None),
+            embed: identity,
+            extract: Ok,
+        })
         .map_err(Protocol)
     }
 }
+
+fn identity<T>(item: T) -> T {
+    item
+}
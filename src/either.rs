@@ -0,0 +1,232 @@
+use crate::Bottom;
+use crate::{Channels, ContextError, Dispatch, Join, Pass, Protocol, Spawn};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{
+    future::{ready, Either, Ready},
+    ready,
+    stream::{once, Forward, IntoStream, Once, StreamFuture},
+    Sink, StreamExt, TryFuture, TryStream, TryStreamExt,
+};
+use pin_utils::pin_mut;
+
+type Tag<C> = Either<<C as Dispatch>::Handle, <C as Dispatch>::Handle>;
+
+#[derive(Debug)]
+pub enum Error<Left, Right, Channel> {
+    Left(Left),
+    Right(Right),
+    Channel(Channel),
+    Terminated,
+}
+
+pub enum Coalesce<
+    C: Channels<Tag<C>, Bottom> + Pass<L, F> + Pass<R, F>,
+    L: Unpin + Protocol<F, <C as Spawn<L, F>>::Target> + Protocol<F, <C as Join<L, F>>::Target>,
+    R: Unpin + Protocol<F, <C as Spawn<R, F>>::Target> + Protocol<F, <C as Join<R, F>>::Target>,
+    F: ?Sized,
+> {
+    Next(StreamFuture<IntoStream<C::Coalesce>>),
+    Left(<C as Join<L, F>>::Output),
+    Right(<C as Join<R, F>>::Output),
+}
+
+pub enum Unravel<
+    C: Channels<Tag<C>, Bottom> + Pass<L, F> + Pass<R, F>,
+    L: Unpin + Protocol<F, <C as Spawn<L, F>>::Target> + Protocol<F, <C as Join<L, F>>::Target>,
+    R: Unpin + Protocol<F, <C as Spawn<R, F>>::Target> + Protocol<F, <C as Join<R, F>>::Target>,
+    F: ?Sized,
+> {
+    Left(Option<C::Unravel>, <C as Spawn<L, F>>::Output),
+    Right(Option<C::Unravel>, <C as Spawn<R, F>>::Output),
+    Send(Forward<Once<Ready<Result<Tag<C>, <C::Unravel as Sink<Tag<C>>>::Error>>>, C::Unravel>),
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<L, F> + Pass<R, F>,
+        L: Unpin + Protocol<F, <C as Spawn<L, F>>::Target> + Protocol<F, <C as Join<L, F>>::Target>,
+        R: Unpin + Protocol<F, <C as Spawn<R, F>>::Target> + Protocol<F, <C as Join<R, F>>::Target>,
+    > Coalesce<C, L, R, F>
+where
+    C::Coalesce: Unpin,
+{
+    fn new(channel: C::Coalesce) -> Self {
+        Coalesce::Next(channel.into_stream().into_future())
+    }
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<L, F> + Pass<R, F>,
+        L: Unpin + Protocol<F, <C as Spawn<L, F>>::Target> + Protocol<F, <C as Join<L, F>>::Target>,
+        R: Unpin + Protocol<F, <C as Spawn<R, F>>::Target> + Protocol<F, <C as Join<R, F>>::Target>,
+    > Unravel<C, L, R, F>
+{
+    fn new(mut channel: C::Unravel, item: Either<L, R>) -> Self {
+        match item {
+            Either::Left(item) => {
+                let spawn = <C as Spawn<L, F>>::spawn(&mut channel, item);
+                Unravel::Left(Some(channel), spawn)
+            }
+            Either::Right(item) => {
+                let spawn = <C as Spawn<R, F>>::spawn(&mut channel, item);
+                Unravel::Right(Some(channel), spawn)
+            }
+        }
+    }
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<L, F> + Pass<R, F>,
+        L: Unpin + Protocol<F, <C as Spawn<L, F>>::Target> + Protocol<F, <C as Join<L, F>>::Target>,
+        R: Unpin + Protocol<F, <C as Spawn<R, F>>::Target> + Protocol<F, <C as Join<R, F>>::Target>,
+    > Future for Coalesce<C, L, R, F>
+where
+    <C as Dispatch>::Handle: Unpin,
+    <C as Join<L, F>>::Output: Unpin,
+    <C as Join<R, F>>::Output: Unpin,
+    C::Coalesce: Unpin,
+{
+    type Output = Result<
+        Either<L, R>,
+        Error<
+            ContextError<
+                <C as Join<L, F>>::Error,
+                <<L as Protocol<F, <C as Join<L, F>>::Target>>::CoalesceFuture as TryFuture>::Error,
+            >,
+            ContextError<
+                <C as Join<R, F>>::Error,
+                <<R as Protocol<F, <C as Join<R, F>>::Target>>::CoalesceFuture as TryFuture>::Error,
+            >,
+            <C::Coalesce as TryStream>::Error,
+        >,
+    >;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        loop {
+            match &mut *self {
+                Coalesce::Next(next) => {
+                    pin_mut!(next);
+                    let tag = ready!(next.poll(ctx));
+                    let (tag, channel) = match tag {
+                        (Some(tag), channel) => (tag, channel),
+                        (None, _) => return Poll::Ready(Err(Error::Terminated)),
+                    };
+                    let mut channel = channel.into_inner();
+                    let replacement = match tag.map_err(Error::Channel)? {
+                        Either::Left(handle) => {
+                            Coalesce::Left(<C as Join<L, F>>::join(&mut channel, handle))
+                        }
+                        Either::Right(handle) => {
+                            Coalesce::Right(<C as Join<R, F>>::join(&mut channel, handle))
+                        }
+                    };
+                    self.set(replacement);
+                }
+                Coalesce::Left(join) => {
+                    pin_mut!(join);
+                    return Poll::Ready(
+                        ready!(join.poll(ctx))
+                            .map(Either::Left)
+                            .map_err(Error::Left),
+                    );
+                }
+                Coalesce::Right(join) => {
+                    pin_mut!(join);
+                    return Poll::Ready(
+                        ready!(join.poll(ctx))
+                            .map(Either::Right)
+                            .map_err(Error::Right),
+                    );
+                }
+            };
+        }
+    }
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<L, F> + Pass<R, F>,
+        L: Unpin + Protocol<F, <C as Spawn<L, F>>::Target> + Protocol<F, <C as Join<L, F>>::Target>,
+        R: Unpin + Protocol<F, <C as Spawn<R, F>>::Target> + Protocol<F, <C as Join<R, F>>::Target>,
+    > Future for Unravel<C, L, R, F>
+where
+    <C as Dispatch>::Handle: Unpin,
+    <C as Spawn<L, F>>::Output: Unpin,
+    <C as Spawn<R, F>>::Output: Unpin,
+    C::Unravel: Unpin,
+{
+    type Output = Result<
+        (),
+        Error<
+            ContextError<
+                <C as Spawn<L, F>>::Error,
+                <<L as Protocol<F, <C as Spawn<L, F>>::Target>>::UnravelFuture as TryFuture>::Error,
+            >,
+            ContextError<
+                <C as Spawn<R, F>>::Error,
+                <<R as Protocol<F, <C as Spawn<R, F>>::Target>>::UnravelFuture as TryFuture>::Error,
+            >,
+            <C::Unravel as Sink<Tag<C>>>::Error,
+        >,
+    >;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        loop {
+            let (channel, tag) = match &mut *self {
+                Unravel::Left(channel, item) => {
+                    let handle = ready!(Pin::new(item).poll(ctx)).map_err(Error::Left)?;
+                    (channel.take(), Either::Left(handle))
+                }
+                Unravel::Right(channel, item) => {
+                    let handle = ready!(Pin::new(item).poll(ctx)).map_err(Error::Right)?;
+                    (channel.take(), Either::Right(handle))
+                }
+                Unravel::Send(send) => {
+                    pin_mut!(send);
+                    return Poll::Ready(ready!(send.poll(ctx)).map_err(Error::Channel));
+                }
+            };
+            let replacement = Unravel::Send(once(ready(Ok(tag))).forward(channel.expect(
+                "violated invariant in Protocol for Either: no channel in Spawn stage",
+            )));
+            self.set(replacement);
+        }
+    }
+}
+
+impl<
+        F: ?Sized,
+        C: Channels<Tag<C>, Bottom> + Pass<L, F> + Pass<R, F>,
+        L: Unpin + Protocol<F, <C as Spawn<L, F>>::Target> + Protocol<F, <C as Join<L, F>>::Target>,
+        R: Unpin + Protocol<F, <C as Spawn<R, F>>::Target> + Protocol<F, <C as Join<R, F>>::Target>,
+    > Protocol<F, C> for Either<L, R>
+where
+    C::Handle: Unpin,
+    <C as Spawn<L, F>>::Output: Unpin,
+    <C as Spawn<R, F>>::Output: Unpin,
+    <C as Join<L, F>>::Output: Unpin,
+    <C as Join<R, F>>::Output: Unpin,
+    <C as Channels<Tag<C>, Bottom>>::Coalesce: Unpin,
+    <C as Channels<Tag<C>, Bottom>>::Unravel: Unpin,
+{
+    type Unravel = Tag<C>;
+    type UnravelError = <Unravel<C, L, R, F> as TryFuture>::Error;
+    type UnravelFuture = Unravel<C, L, R, F>;
+    type Coalesce = Bottom;
+    type CoalesceError = <Coalesce<C, L, R, F> as TryFuture>::Error;
+    type CoalesceFuture = Coalesce<C, L, R, F>;
+
+    fn unravel(self, channel: <C as Channels<Tag<C>, Bottom>>::Unravel) -> Self::UnravelFuture {
+        Unravel::new(channel, self)
+    }
+
+    fn coalesce(channel: <C as Channels<Tag<C>, Bottom>>::Coalesce) -> Self::CoalesceFuture {
+        Coalesce::new(channel)
+    }
+}
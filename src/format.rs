@@ -1,6 +1,17 @@
 use core_futures_io::{AsyncRead, AsyncWrite};
 use futures::{Sink, TryStream};
 
+pub mod checksummed;
+pub use checksummed::Checksummed;
+#[cfg(feature = "compression")]
+pub mod compressed;
+#[cfg(feature = "compression")]
+pub use compressed::Compressed;
+#[cfg(feature = "alloc")]
+pub mod negotiate;
+#[cfg(feature = "alloc")]
+pub use negotiate::{Descriptor, Dynamic};
+
 pub trait Format<T> {}
 
 pub trait ItemFormat<T, S: Sink<Self::Representation> + TryStream<Ok = Self::Representation>>:
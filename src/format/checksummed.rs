@@ -0,0 +1,615 @@
+use super::{Format, ItemFormat};
+use crate::{director::Timer, transport::framing::Packet};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::{
+    future::Future,
+    marker::PhantomData,
+    ops::Deref,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use futures::{ready, Sink, Stream, TryStream};
+
+const DATA: u8 = 0;
+const NAK: u8 = 1;
+
+pub trait Checksum {
+    const LENGTH: usize;
+
+    fn compute(&self, parts: &[&[u8]]) -> u32;
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Crc32;
+
+impl Checksum for Crc32 {
+    const LENGTH: usize = 4;
+
+    fn compute(&self, parts: &[&[u8]]) -> u32 {
+        let mut crc = !0u32;
+        for byte in parts.iter().flat_map(|part| part.iter()) {
+            crc ^= *byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB88320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Crc16;
+
+impl Checksum for Crc16 {
+    const LENGTH: usize = 2;
+
+    fn compute(&self, parts: &[&[u8]]) -> u32 {
+        let mut crc = 0xFFFFu16;
+        for byte in parts.iter().flat_map(|part| part.iter()) {
+            crc ^= (*byte as u16) << 8;
+            for _ in 0..8 {
+                crc = if crc & 0x8000 != 0 {
+                    (crc << 1) ^ 0x1021
+                } else {
+                    crc << 1
+                };
+            }
+        }
+        crc as u32
+    }
+}
+
+pub type Standard = Crc32;
+
+pub trait Buffer: Deref<Target = [u8]> + Sized {
+    fn concat(parts: &[&[u8]]) -> Option<Self>;
+}
+
+impl<const N: usize> Buffer for Packet<N> {
+    fn concat(parts: &[&[u8]]) -> Option<Self> {
+        let mut data = [0; N];
+        let mut length = 0;
+        for part in parts {
+            data.get_mut(length..length + part.len())?
+                .copy_from_slice(part);
+            length += part.len();
+        }
+        Packet::new(&data[..length])
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Buffer for Vec<u8> {
+    fn concat(parts: &[&[u8]]) -> Option<Self> {
+        Some(parts.concat())
+    }
+}
+
+pub trait History<P> {
+    fn slots(&mut self) -> Option<&mut [Option<(u16, P)>]>;
+}
+
+impl<P> History<P> for () {
+    fn slots(&mut self) -> Option<&mut [Option<(u16, P)>]> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Retransmit<H>(H);
+
+impl<P, H: AsMut<[Option<(u16, P)>]>> History<P> for Retransmit<H> {
+    fn slots(&mut self) -> Option<&mut [Option<(u16, P)>]> {
+        Some(self.0.as_mut()).filter(|slots| !slots.is_empty())
+    }
+}
+
+pub trait Resend {
+    fn arm(&mut self);
+
+    fn disarm(&mut self);
+
+    fn poll_elapsed(&mut self, ctx: &mut Context) -> Poll<()>;
+}
+
+impl Resend for () {
+    fn arm(&mut self) {}
+
+    fn disarm(&mut self) {}
+
+    fn poll_elapsed(&mut self, _: &mut Context) -> Poll<()> {
+        Poll::Pending
+    }
+}
+
+pub struct Rto<T: Timer> {
+    timer: T,
+    duration: Duration,
+    delay: Option<T::Delay>,
+}
+
+impl<T: Timer> Resend for Rto<T> {
+    fn arm(&mut self) {
+        self.delay = Some(self.timer.delay(self.duration));
+    }
+
+    fn disarm(&mut self) {
+        self.delay = None;
+    }
+
+    fn poll_elapsed(&mut self, ctx: &mut Context) -> Poll<()> {
+        match &mut self.delay {
+            Some(delay) => {
+                ready!(Pin::new(delay).poll(ctx));
+                self.delay = None;
+                Poll::Ready(())
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error<Stream, Sink> {
+    Stream(Stream),
+    Sink(Sink),
+    Mismatch,
+    Truncated,
+    Oversized,
+    Unexpected,
+    Lost,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Checksummed<F, C = Standard, B = (), R = ()> {
+    format: F,
+    checksum: C,
+    history: B,
+    resend: R,
+}
+
+impl<F> Checksummed<F> {
+    pub fn new(format: F) -> Self {
+        Checksummed {
+            format,
+            checksum: Standard::default(),
+            history: (),
+            resend: (),
+        }
+    }
+}
+
+impl<F, C, B, R> Checksummed<F, C, B, R> {
+    pub fn with_checksum<D: Checksum>(self, checksum: D) -> Checksummed<F, D, B, R> {
+        Checksummed {
+            format: self.format,
+            checksum,
+            history: self.history,
+            resend: self.resend,
+        }
+    }
+
+    pub fn with_retransmit<H>(self, history: H) -> Checksummed<F, C, Retransmit<H>, R> {
+        Checksummed {
+            format: self.format,
+            checksum: self.checksum,
+            history: Retransmit(history),
+            resend: self.resend,
+        }
+    }
+
+    pub fn with_timeout<T: Timer>(
+        self,
+        timer: T,
+        duration: Duration,
+    ) -> Checksummed<F, C, B, Rto<T>> {
+        Checksummed {
+            format: self.format,
+            checksum: self.checksum,
+            history: self.history,
+            resend: Rto {
+                timer,
+                duration,
+                delay: None,
+            },
+        }
+    }
+
+    pub fn into_inner(self) -> F {
+        self.format
+    }
+}
+
+impl<T, F: Format<T>, C, B, R> Format<T> for Checksummed<F, C, B, R> {}
+
+type CheckedError<S, P> = Error<<S as TryStream>::Error, <S as Sink<P>>::Error>;
+
+pub struct Checked<S, C, P, B, R = ()> {
+    transport: S,
+    checksum: C,
+    history: B,
+    resend: R,
+    next: u16,
+    slot: usize,
+    expected: u16,
+    nak: Option<u16>,
+    outstanding: Option<u16>,
+    replay: Option<u16>,
+    flush: bool,
+    marker: PhantomData<fn(P) -> P>,
+}
+
+impl<S: Unpin, C, P, B, R: Unpin> Unpin for Checked<S, C, P, B, R> {}
+
+impl<S, C, P, B, R> Checked<S, C, P, B, R> {
+    fn new(transport: S, checksum: C, history: B, resend: R) -> Self {
+        Checked {
+            transport,
+            checksum,
+            history,
+            resend,
+            next: 0,
+            slot: 0,
+            expected: 0,
+            nak: None,
+            outstanding: None,
+            replay: None,
+            flush: false,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.transport
+    }
+
+    pub fn into_inner(self) -> S {
+        self.transport
+    }
+}
+
+impl<
+        P: Buffer + Clone,
+        C: Checksum,
+        B: History<P>,
+        R: Resend,
+        S: Unpin + Sink<P> + TryStream<Ok = P>,
+    > Checked<S, C, P, B, R>
+{
+    fn seal(&self, header: &[u8], payload: &[u8]) -> Result<P, CheckedError<S, P>> {
+        let checksum = self.checksum.compute(&[header, payload]).to_le_bytes();
+        P::concat(&[header, payload, &checksum[..C::LENGTH]]).ok_or(Error::Oversized)
+    }
+
+    fn open<'a>(&self, frame: &'a [u8]) -> Result<&'a [u8], CheckedError<S, P>> {
+        let split = frame.len().checked_sub(C::LENGTH).ok_or(Error::Truncated)?;
+        let (content, checksum) = frame.split_at(split);
+        let computed = self.checksum.compute(&[content]).to_le_bytes();
+        if checksum == &computed[..C::LENGTH] {
+            Ok(content)
+        } else {
+            Err(Error::Mismatch)
+        }
+    }
+
+    fn request(&mut self) {
+        self.nak = Some(self.expected);
+        self.outstanding = Some(self.expected);
+    }
+
+    fn accept(&mut self, sequence: u16) {
+        self.expected = sequence.wrapping_add(1);
+        if self.outstanding == Some(sequence) {
+            self.outstanding = None;
+            self.resend.disarm();
+        }
+    }
+
+    fn rewind(&mut self, sequence: u16) -> Result<(), CheckedError<S, P>> {
+        let next = self.next;
+        let length = self.history.slots().map_or(0, |slots| slots.len());
+        if next.wrapping_sub(sequence) as usize > length {
+            return Err(Error::Lost);
+        }
+        self.replay = Some(match self.replay {
+            Some(replay) if next.wrapping_sub(replay) >= next.wrapping_sub(sequence) => replay,
+            _ => sequence,
+        });
+        Ok(())
+    }
+
+    fn poll_control(&mut self, ctx: &mut Context) -> Poll<Result<(), CheckedError<S, P>>> {
+        if self.outstanding.is_some() && self.resend.poll_elapsed(ctx).is_ready() {
+            self.nak = self.outstanding;
+        }
+        loop {
+            if self.replay == Some(self.next) {
+                self.replay = None;
+            }
+            if self.nak.is_none() && self.replay.is_none() {
+                break;
+            }
+            ready!(Pin::new(&mut self.transport).poll_ready(ctx)).map_err(Error::Sink)?;
+            let frame = if let Some(sequence) = self.nak.take() {
+                let [low, high] = sequence.to_le_bytes();
+                self.resend.arm();
+                self.seal(&[NAK, low, high], &[])?
+            } else {
+                let sequence = self.replay.unwrap();
+                let (next, slot) = (self.next, self.slot);
+                let slots = self
+                    .history
+                    .slots()
+                    .expect("violated invariant in Checksummed: replay without history");
+                let length = slots.len();
+                let index = (slot + length - next.wrapping_sub(sequence) as usize) % length;
+                let frame = slots[index]
+                    .as_ref()
+                    .filter(|(stored, _)| *stored == sequence)
+                    .map(|(_, frame)| frame.clone())
+                    .ok_or(Error::Lost)?;
+                self.replay = Some(sequence.wrapping_add(1));
+                frame
+            };
+            Pin::new(&mut self.transport)
+                .start_send(frame)
+                .map_err(Error::Sink)?;
+            self.flush = true;
+        }
+        if self.flush {
+            ready!(Pin::new(&mut self.transport).poll_flush(ctx)).map_err(Error::Sink)?;
+            self.flush = false;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<
+        P: Buffer + Clone,
+        C: Checksum,
+        B: History<P>,
+        R: Resend,
+        S: Unpin + Sink<P> + TryStream<Ok = P>,
+    > Stream for Checked<S, C, P, B, R>
+{
+    type Item = Result<P, CheckedError<S, P>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Poll::Ready(Err(e)) = this.poll_control(ctx) {
+                return Poll::Ready(Some(Err(e)));
+            }
+            let frame = match ready!(Pin::new(&mut this.transport).try_poll_next(ctx)) {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => return Poll::Ready(Some(Err(Error::Stream(e)))),
+                None => return Poll::Ready(None),
+            };
+            let retransmit = this.history.slots().is_some();
+            let content = match this.open(&frame) {
+                Ok(content) => content,
+                Err(_) if retransmit => {
+                    this.request();
+                    continue;
+                }
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            };
+            if !retransmit {
+                return Poll::Ready(Some(P::concat(&[content]).ok_or(Error::Oversized)));
+            }
+            match *content {
+                [NAK, low, high] => {
+                    if let Err(e) = this.rewind(u16::from_le_bytes([low, high])) {
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+                [DATA, low, high, ref payload @ ..] => {
+                    let sequence = u16::from_le_bytes([low, high]);
+                    if sequence == this.expected {
+                        this.accept(sequence);
+                        return Poll::Ready(Some(P::concat(&[payload]).ok_or(Error::Oversized)));
+                    }
+                    if sequence.wrapping_sub(this.expected) < 0x8000 {
+                        this.request();
+                    }
+                }
+                _ => return Poll::Ready(Some(Err(Error::Unexpected))),
+            }
+        }
+    }
+}
+
+impl<
+        P: Buffer + Clone,
+        C: Checksum,
+        B: History<P>,
+        R: Resend,
+        S: Unpin + Sink<P> + TryStream<Ok = P>,
+    > Sink<P> for Checked<S, C, P, B, R>
+{
+    type Error = CheckedError<S, P>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_control(ctx))?;
+        Pin::new(&mut self.transport)
+            .poll_ready(ctx)
+            .map_err(Error::Sink)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: P) -> Result<(), Self::Error> {
+        let this = &mut *self;
+        let sequence = this.next;
+        let frame = if this.history.slots().is_some() {
+            let [low, high] = sequence.to_le_bytes();
+            let frame = this.seal(&[DATA, low, high], &item)?;
+            let slots = this.history.slots().unwrap();
+            let length = slots.len();
+            slots[this.slot] = Some((sequence, frame.clone()));
+            this.slot = (this.slot + 1) % length;
+            this.next = sequence.wrapping_add(1);
+            frame
+        } else {
+            this.seal(&[], &item)?
+        };
+        Pin::new(&mut this.transport)
+            .start_send(frame)
+            .map_err(Error::Sink)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_control(ctx))?;
+        Pin::new(&mut self.transport)
+            .poll_flush(ctx)
+            .map_err(Error::Sink)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_control(ctx))?;
+        Pin::new(&mut self.transport)
+            .poll_close(ctx)
+            .map_err(Error::Sink)
+    }
+}
+
+impl<
+        T,
+        P: Buffer + Clone,
+        C: Checksum,
+        B: History<P>,
+        R: Resend,
+        S: Unpin + Sink<P> + TryStream<Ok = P>,
+        F: ItemFormat<T, Checked<S, C, P, B, R>, Representation = P>,
+    > ItemFormat<T, S> for Checksummed<F, C, B, R>
+{
+    type Representation = P;
+    type Output = F::Output;
+
+    fn wire(self, transport: S) -> F::Output {
+        self.format.wire(Checked::new(
+            transport,
+            self.checksum,
+            self.history,
+            self.resend,
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::{Checked, Checksum, Crc16, Crc32, Error, Retransmit, Rto};
+    use crate::{
+        director::Timer,
+        transport::pipe::{channel, Channel},
+    };
+    use alloc::vec::Vec;
+    use core::time::Duration;
+    use futures::{
+        executor::block_on,
+        future::{ready, select, Either, Ready},
+        FutureExt, SinkExt, StreamExt,
+    };
+
+    type History<const N: usize> = Retransmit<[Option<(u16, Vec<u8>)>; N]>;
+
+    struct Immediate;
+
+    impl Timer for Immediate {
+        type Delay = Ready<()>;
+
+        fn delay(&mut self, _: Duration) -> Ready<()> {
+            ready(())
+        }
+    }
+
+    fn retransmitting<const N: usize>(
+        transport: Channel<Vec<u8>>,
+    ) -> Checked<Channel<Vec<u8>>, Crc32, Vec<u8>, History<N>, Rto<Immediate>> {
+        let resend = Rto {
+            timer: Immediate,
+            duration: Duration::from_millis(0),
+            delay: None,
+        };
+        Checked::new(transport, Crc32, Retransmit([(); N].map(|_| None)), resend)
+    }
+
+    #[test]
+    fn known_vectors() {
+        assert_eq!(Crc32.compute(&[b"123456789"]), 0xCBF43926);
+        assert_eq!(Crc32.compute(&[b"1234", b"56789"]), 0xCBF43926);
+        assert_eq!(Crc16.compute(&[b"123456789"]), 0x29B1);
+    }
+
+    #[test]
+    fn detects_mismatch() {
+        let (left, right) = channel::<Vec<u8>>();
+        let mut sender = Checked::new(left, Crc32, (), ());
+        let mut receiver = Checked::new(right, Crc32, (), ());
+        block_on(async {
+            sender.send(b"intact".to_vec()).await.unwrap();
+            sender.send(b"corrupt".to_vec()).await.unwrap();
+            sender.get_ref().alter(|frame| frame[0] ^= 0x01);
+            assert_eq!(receiver.next().await.unwrap().unwrap(), b"intact");
+            assert!(matches!(receiver.next().await, Some(Err(Error::Mismatch))));
+        });
+    }
+
+    #[test]
+    fn retransmits_corrupt_frames() {
+        let (left, right) = channel::<Vec<u8>>();
+        let (mut sender, mut receiver) = (retransmitting::<8>(left), retransmitting::<8>(right));
+        block_on(async {
+            sender.send(b"one".to_vec()).await.unwrap();
+            sender.send(b"two".to_vec()).await.unwrap();
+            sender.get_ref().alter(|frame| frame[4] ^= 0x01);
+            sender.send(b"three".to_vec()).await.unwrap();
+            for expected in [&b"one"[..], b"two", b"three"].iter() {
+                match select(receiver.next(), sender.next()).await {
+                    Either::Left((item, _)) => assert_eq!(item.unwrap().unwrap(), *expected),
+                    Either::Right(_) => panic!("unexpected frame at sender"),
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn resends_lost_requests() {
+        let (left, right) = channel::<Vec<u8>>();
+        let (mut sender, mut receiver) = (retransmitting::<8>(left), retransmitting::<8>(right));
+        block_on(sender.send(b"tail".to_vec())).unwrap();
+        sender.get_ref().alter(|frame| frame[4] ^= 0x01);
+        assert!(receiver.next().now_or_never().is_none());
+        receiver.get_ref().alter(|frame| frame[1] ^= 0x01);
+        assert!(sender.next().now_or_never().is_none());
+        assert!(receiver.next().now_or_never().is_none());
+        assert!(sender.next().now_or_never().is_none());
+        let item = receiver.next().now_or_never().unwrap().unwrap().unwrap();
+        assert_eq!(item, b"tail");
+    }
+
+    #[test]
+    fn retransmits_across_sequence_wrap() {
+        let (left, right) = channel::<Vec<u8>>();
+        let (mut sender, mut receiver) = (retransmitting::<3>(left), retransmitting::<3>(right));
+        sender.next = u16::MAX - 1;
+        receiver.expected = u16::MAX - 1;
+        block_on(async {
+            sender.send(b"one".to_vec()).await.unwrap();
+            sender.send(b"two".to_vec()).await.unwrap();
+            sender.get_ref().alter(|frame| frame[4] ^= 0x01);
+            sender.send(b"three".to_vec()).await.unwrap();
+            for expected in [&b"one"[..], b"two", b"three"].iter() {
+                match select(receiver.next(), sender.next()).await {
+                    Either::Left((item, _)) => assert_eq!(item.unwrap().unwrap(), *expected),
+                    Either::Right(_) => panic!("unexpected frame at sender"),
+                }
+            }
+        });
+        assert_eq!(sender.next, 1);
+    }
+}
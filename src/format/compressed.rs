@@ -0,0 +1,687 @@
+use super::{ByteFormat, Format, ItemFormat};
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Debug, Formatter},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::{ready, Sink, Stream, TryStream};
+
+const RAW: u8 = 0;
+const COMPRESSED: u8 = 1;
+const HELLO: u8 = 2;
+const DEFAULT_THRESHOLD: usize = 256;
+const DEFAULT_LIMIT: usize = 1 << 24;
+const FRAME: usize = 1 << 16;
+const CHUNK: usize = 4096;
+
+pub trait Compression {
+    const ID: u8;
+
+    fn compress(&self, input: &[u8]) -> Vec<u8>;
+
+    fn decompress(&self, input: &[u8], limit: usize) -> Option<Vec<u8>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deflate {
+    level: u8,
+}
+
+impl Deflate {
+    pub fn new(level: u8) -> Self {
+        Deflate { level: level.min(10) }
+    }
+}
+
+impl Default for Deflate {
+    fn default() -> Self {
+        Deflate::new(6)
+    }
+}
+
+impl Compression for Deflate {
+    const ID: u8 = 1;
+
+    fn compress(&self, input: &[u8]) -> Vec<u8> {
+        miniz_oxide::deflate::compress_to_vec(input, self.level)
+    }
+
+    fn decompress(&self, input: &[u8], limit: usize) -> Option<Vec<u8>> {
+        miniz_oxide::inflate::decompress_to_vec_with_limit(input, limit).ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    Flag(u8),
+    Hello,
+    Unnegotiated,
+    Corrupt,
+    Length,
+}
+
+#[derive(Debug)]
+pub enum Error<E> {
+    Transport(E),
+    Protocol(Violation),
+}
+
+struct State<C> {
+    codec: C,
+    threshold: usize,
+    limit: usize,
+    enabled: bool,
+    greeted: bool,
+    announced: bool,
+    heard: bool,
+    remote: bool,
+}
+
+impl<C: Compression> State<C> {
+    fn hello(&self) -> [u8; 3] {
+        [HELLO, C::ID, self.enabled as u8]
+    }
+
+    fn encode(&self, payload: &[u8], output: &mut Vec<u8>) {
+        if self.enabled && self.remote && payload.len() >= self.threshold {
+            let compressed = self.codec.compress(payload);
+            if compressed.len() < payload.len() {
+                output.push(COMPRESSED);
+                output.extend_from_slice(&compressed);
+                return;
+            }
+        }
+        output.push(RAW);
+        output.extend_from_slice(payload);
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, Violation> {
+        match frame.split_first() {
+            Some((&flag, _)) if flag != HELLO && !self.heard => Err(Violation::Hello),
+            Some((&RAW, payload)) => Ok(Some(payload.into())),
+            Some((&COMPRESSED, payload)) => {
+                if !self.enabled {
+                    return Err(Violation::Unnegotiated);
+                }
+                self.codec
+                    .decompress(payload, self.limit)
+                    .map(Some)
+                    .ok_or(Violation::Corrupt)
+            }
+            Some((&HELLO, &[id, enabled])) if !self.heard => {
+                if enabled > 1 {
+                    return Err(Violation::Hello);
+                }
+                self.remote = id == C::ID && enabled == 1;
+                self.heard = true;
+                Ok(None)
+            }
+            Some((&HELLO, _)) => Err(Violation::Hello),
+            Some((&flag, _)) => Err(Violation::Flag(flag)),
+            None => Err(Violation::Length),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Compressed<F, C = Deflate> {
+    format: F,
+    codec: C,
+    threshold: usize,
+    limit: usize,
+    enabled: bool,
+}
+
+impl<F> Compressed<F> {
+    pub fn new(format: F) -> Self {
+        Compressed {
+            format,
+            codec: Deflate::default(),
+            threshold: DEFAULT_THRESHOLD,
+            limit: DEFAULT_LIMIT,
+            enabled: true,
+        }
+    }
+}
+
+impl<F, C> Compressed<F, C> {
+    pub fn with_codec<D: Compression>(self, codec: D) -> Compressed<F, D> {
+        Compressed {
+            format: self.format,
+            codec,
+            threshold: self.threshold,
+            limit: self.limit,
+            enabled: self.enabled,
+        }
+    }
+
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(FRAME);
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn into_inner(self) -> F {
+        self.format
+    }
+
+    fn split(self) -> (F, State<C>) {
+        let state = State {
+            codec: self.codec,
+            threshold: self.threshold,
+            limit: self.limit,
+            enabled: self.enabled,
+            greeted: false,
+            announced: false,
+            heard: false,
+            remote: false,
+        };
+        (self.format, state)
+    }
+}
+
+impl<T, F: Format<T>, C> Format<T> for Compressed<F, C> {}
+
+pub struct Frames<S, C> {
+    transport: S,
+    state: State<C>,
+}
+
+impl<S, C> Frames<S, C> {
+    pub fn is_negotiated(&self) -> bool {
+        self.state.enabled && self.state.remote
+    }
+
+    pub fn into_inner(self) -> S {
+        self.transport
+    }
+}
+
+impl<S: Unpin, C> Unpin for Frames<S, C> {}
+
+impl<S: Unpin + Sink<Vec<u8>> + TryStream<Ok = Vec<u8>>, C: Compression> Frames<S, C> {
+    fn poll_announce(
+        &mut self,
+        ctx: &mut Context,
+    ) -> Poll<Result<(), <S as Sink<Vec<u8>>>::Error>> {
+        if !self.state.greeted {
+            ready!(Pin::new(&mut self.transport).poll_ready(ctx))?;
+            Pin::new(&mut self.transport).start_send(self.state.hello().into())?;
+            self.state.greeted = true;
+        }
+        if !self.state.announced {
+            ready!(Pin::new(&mut self.transport).poll_flush(ctx))?;
+            self.state.announced = true;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: Unpin + Sink<Vec<u8>> + TryStream<Ok = Vec<u8>>, C: Compression> Stream for Frames<S, C> {
+    type Item = Result<Vec<u8>, Error<<S as TryStream>::Error>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        // Failures to announce resurface on the next write.
+        let _ = this.poll_announce(ctx);
+        loop {
+            let frame = match ready!(Pin::new(&mut this.transport).try_poll_next(ctx)) {
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => return Poll::Ready(Some(Err(Error::Transport(e)))),
+                None => return Poll::Ready(None),
+            };
+            match this.state.decode(&frame) {
+                Ok(Some(item)) => return Poll::Ready(Some(Ok(item))),
+                Ok(None) => {}
+                Err(violation) => return Poll::Ready(Some(Err(Error::Protocol(violation)))),
+            }
+        }
+    }
+}
+
+impl<S: Unpin + Sink<Vec<u8>> + TryStream<Ok = Vec<u8>>, C: Compression> Sink<Vec<u8>>
+    for Frames<S, C>
+{
+    type Error = Error<<S as Sink<Vec<u8>>>::Error>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let this = &mut *self;
+        ready!(this.poll_announce(ctx)).map_err(Error::Transport)?;
+        while !this.state.heard {
+            match ready!(Pin::new(&mut this.transport).try_poll_next(ctx)) {
+                Some(Ok(frame)) => {
+                    this.state.decode(&frame).map_err(Error::Protocol)?;
+                }
+                Some(Err(_)) | None => return Poll::Ready(Err(Error::Protocol(Violation::Hello))),
+            }
+        }
+        Pin::new(&mut this.transport)
+            .poll_ready(ctx)
+            .map_err(Error::Transport)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Vec<u8>) -> Result<(), Self::Error> {
+        let this = &mut *self;
+        let mut frame = Vec::with_capacity(item.len() + 1);
+        this.state.encode(&item, &mut frame);
+        Pin::new(&mut this.transport)
+            .start_send(frame)
+            .map_err(Error::Transport)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.transport)
+            .poll_flush(ctx)
+            .map_err(Error::Transport)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.transport)
+            .poll_close(ctx)
+            .map_err(Error::Transport)
+    }
+}
+
+impl<
+        T,
+        C: Compression,
+        S: Unpin + Sink<Vec<u8>> + TryStream<Ok = Vec<u8>>,
+        F: ItemFormat<T, Frames<S, C>, Representation = Vec<u8>>,
+    > ItemFormat<T, S> for Compressed<F, C>
+{
+    type Representation = Vec<u8>;
+    type Output = F::Output;
+
+    fn wire(self, transport: S) -> F::Output {
+        let (format, state) = self.split();
+        format.wire(Frames { transport, state })
+    }
+}
+
+pub enum StreamError<S: AsyncRead + AsyncWrite> {
+    Read(S::Error),
+    Write(S::WriteError),
+    Flush(S::FlushError),
+    Close(S::CloseError),
+    Protocol(Violation),
+    Terminated,
+}
+
+impl<S: AsyncRead + AsyncWrite> Debug for StreamError<S>
+where
+    S::Error: Debug,
+    S::WriteError: Debug,
+    S::FlushError: Debug,
+    S::CloseError: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            StreamError::Read(e) => f.debug_tuple("Read").field(e).finish(),
+            StreamError::Write(e) => f.debug_tuple("Write").field(e).finish(),
+            StreamError::Flush(e) => f.debug_tuple("Flush").field(e).finish(),
+            StreamError::Close(e) => f.debug_tuple("Close").field(e).finish(),
+            StreamError::Protocol(e) => f.debug_tuple("Protocol").field(e).finish(),
+            StreamError::Terminated => f.write_str("Terminated"),
+        }
+    }
+}
+
+pub struct Compressing<S, C> {
+    stream: S,
+    state: State<C>,
+    read: Vec<u8>,
+    plain: Vec<u8>,
+    consumed: usize,
+    pending: Vec<u8>,
+    write: Vec<u8>,
+}
+
+impl<S, C> Compressing<S, C> {
+    fn new(stream: S, state: State<C>) -> Self {
+        Compressing {
+            stream,
+            state,
+            read: Vec::new(),
+            plain: Vec::new(),
+            consumed: 0,
+            pending: Vec::new(),
+            write: Vec::new(),
+        }
+    }
+
+    pub fn is_negotiated(&self) -> bool {
+        self.state.enabled && self.state.remote
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Unpin, C> Unpin for Compressing<S, C> {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin, C: Compression> Compressing<S, C> {
+    fn frame(&mut self, body: impl FnOnce(&State<C>, &mut Vec<u8>)) {
+        let start = self.write.len();
+        self.write.extend_from_slice(&[0; 4]);
+        body(&self.state, &mut self.write);
+        let length = (self.write.len() - start - 4) as u32;
+        self.write[start..start + 4].copy_from_slice(&length.to_be_bytes());
+    }
+
+    fn poll_announce(&mut self, ctx: &mut Context) -> Poll<Result<(), StreamError<S>>> {
+        if !self.state.greeted {
+            self.frame(|state, output| output.extend_from_slice(&state.hello()));
+            self.state.greeted = true;
+        }
+        if !self.state.announced {
+            ready!(self.poll_write_buffer(ctx))?;
+            ready!(Pin::new(&mut self.stream).poll_flush(ctx)).map_err(StreamError::Flush)?;
+            self.state.announced = true;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_hear(&mut self, ctx: &mut Context) -> Poll<Result<(), StreamError<S>>> {
+        ready!(self.poll_announce(ctx))?;
+        let mut chunk = [0; CHUNK];
+        while !self.state.heard {
+            if self.next_frame()? {
+                continue;
+            }
+            let count = ready!(Pin::new(&mut self.stream).poll_read(ctx, &mut chunk))
+                .map_err(StreamError::Read)?;
+            if count == 0 {
+                return Poll::Ready(Err(StreamError::Terminated));
+            }
+            self.read.extend_from_slice(&chunk[..count]);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn seal(&mut self) {
+        if !self.pending.is_empty() {
+            let pending = core::mem::take(&mut self.pending);
+            self.frame(|state, output| state.encode(&pending, output));
+            self.pending = pending;
+            self.pending.clear();
+        }
+    }
+
+    fn poll_write_buffer(&mut self, ctx: &mut Context) -> Poll<Result<(), StreamError<S>>> {
+        while !self.write.is_empty() {
+            let count = ready!(Pin::new(&mut self.stream).poll_write(ctx, &self.write))
+                .map_err(StreamError::Write)?;
+            if count == 0 {
+                return Poll::Ready(Err(StreamError::Terminated));
+            }
+            self.write.drain(..count);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn next_frame(&mut self) -> Result<bool, StreamError<S>> {
+        if self.read.len() < 4 {
+            return Ok(false);
+        }
+        let length = u32::from_be_bytes([self.read[0], self.read[1], self.read[2], self.read[3]]);
+        let length = length as usize;
+        if length > self.state.limit.saturating_add(1) {
+            return Err(StreamError::Protocol(Violation::Length));
+        }
+        if self.read.len() < 4 + length {
+            return Ok(false);
+        }
+        let decoded = self.state.decode(&self.read[4..4 + length]);
+        self.read.drain(..4 + length);
+        if let Some(plain) = decoded.map_err(StreamError::Protocol)? {
+            self.plain = plain;
+            self.consumed = 0;
+        }
+        Ok(true)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin, C: Compression> AsyncRead for Compressing<S, C> {
+    type Error = StreamError<S>;
+
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, StreamError<S>>> {
+        let this = &mut *self;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        ready!(this.poll_announce(ctx))?;
+        let mut chunk = [0; CHUNK];
+        loop {
+            if this.consumed < this.plain.len() {
+                let count = buf.len().min(this.plain.len() - this.consumed);
+                buf[..count].copy_from_slice(&this.plain[this.consumed..this.consumed + count]);
+                this.consumed += count;
+                return Poll::Ready(Ok(count));
+            }
+            if this.next_frame()? {
+                continue;
+            }
+            let count = ready!(Pin::new(&mut this.stream).poll_read(ctx, &mut chunk))
+                .map_err(StreamError::Read)?;
+            if count == 0 {
+                return Poll::Ready(if this.read.is_empty() {
+                    Ok(0)
+                } else {
+                    Err(StreamError::Terminated)
+                });
+            }
+            this.read.extend_from_slice(&chunk[..count]);
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin, C: Compression> AsyncWrite for Compressing<S, C> {
+    type WriteError = StreamError<S>;
+    type FlushError = StreamError<S>;
+    type CloseError = StreamError<S>;
+
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, StreamError<S>>> {
+        let this = &mut *self;
+        if this.pending.len() >= FRAME {
+            ready!(this.poll_hear(ctx))?;
+            this.seal();
+        }
+        if this.write.len() >= CHUNK {
+            ready!(this.poll_write_buffer(ctx))?;
+        }
+        let count = buf.len().min(FRAME - this.pending.len());
+        this.pending.extend_from_slice(&buf[..count]);
+        Poll::Ready(Ok(count))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), StreamError<S>>> {
+        ready!(self.poll_hear(ctx))?;
+        self.seal();
+        ready!(self.poll_write_buffer(ctx))?;
+        Pin::new(&mut self.stream)
+            .poll_flush(ctx)
+            .map_err(StreamError::Flush)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), StreamError<S>>> {
+        ready!(self.poll_hear(ctx))?;
+        self.seal();
+        ready!(self.poll_write_buffer(ctx))?;
+        ready!(Pin::new(&mut self.stream).poll_flush(ctx)).map_err(StreamError::Flush)?;
+        Pin::new(&mut self.stream)
+            .poll_close(ctx)
+            .map_err(StreamError::Close)
+    }
+}
+
+impl<
+        T,
+        C: Compression,
+        S: AsyncRead + AsyncWrite + Unpin,
+        F: ByteFormat<T, Compressing<S, C>>,
+    > ByteFormat<T, S> for Compressed<F, C>
+{
+    type Output = F::Output;
+
+    fn wire(self, transport: S) -> F::Output {
+        let (format, state) = self.split();
+        format.wire(Compressing::new(transport, state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Compressed, Compressing, Compression, Deflate, Frames, StreamError, Violation,
+        DEFAULT_LIMIT, FRAME, HELLO,
+    };
+    use crate::transport::pipe::{channel, pipe, read, write, Pipe};
+    use alloc::{vec, vec::Vec};
+    use core::pin::Pin;
+    use core_futures_io::AsyncWrite;
+    use futures::{
+        executor::block_on,
+        future::{join, poll_fn},
+        SinkExt, StreamExt,
+    };
+
+    fn compressing(stream: Pipe) -> Compressing<Pipe, Deflate> {
+        let (_, state) = Compressed::new(()).with_threshold(64).split();
+        Compressing::new(stream, state)
+    }
+
+    fn connected() -> (Compressing<Pipe, Deflate>, Compressing<Pipe, Deflate>) {
+        let (left, right) = pipe();
+        let (mut left, mut right) = (compressing(left), compressing(right));
+        let (a, b) = block_on(join(
+            poll_fn(|ctx| Pin::new(&mut left).poll_flush(ctx)),
+            poll_fn(|ctx| Pin::new(&mut right).poll_flush(ctx)),
+        ));
+        a.unwrap();
+        b.unwrap();
+        (left, right)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (mut client, mut server) = connected();
+        assert!(client.is_negotiated() && server.is_negotiated());
+        write(&mut client, b"short");
+        assert_eq!(client.get_ref().pending(), 4 + 1 + 5);
+        assert_eq!(read(&mut server, 5).unwrap(), b"short");
+        let long = vec![b'a'; 4096];
+        write(&mut client, &long);
+        assert!(client.get_ref().pending() < 1024);
+        assert_eq!(read(&mut server, long.len()).unwrap(), long);
+    }
+
+    #[test]
+    fn disabled_peer_receives_raw_frames() {
+        let (left, right) = pipe();
+        let (_, state) = Compressed::new(()).with_enabled(false).split();
+        let (mut client, mut server) = (compressing(left), Compressing::new(right, state));
+        let (a, b) = block_on(join(
+            poll_fn(|ctx| Pin::new(&mut client).poll_flush(ctx)),
+            poll_fn(|ctx| Pin::new(&mut server).poll_flush(ctx)),
+        ));
+        a.unwrap();
+        b.unwrap();
+        assert!(!client.is_negotiated());
+        let long = vec![b'a'; 4096];
+        write(&mut client, &long);
+        assert_eq!(client.get_ref().pending(), 4 + 1 + long.len());
+        assert_eq!(read(&mut server, long.len()).unwrap(), long);
+    }
+
+    #[test]
+    fn rejects_corrupt_frames() {
+        let (mut client, mut server) = connected();
+        write(&mut client, b"hi");
+        client.get_ref().flip(4);
+        assert!(matches!(
+            read(&mut server, 2),
+            Err(StreamError::Protocol(Violation::Corrupt))
+        ));
+    }
+
+    #[test]
+    fn rejects_data_before_hello() {
+        let (mut left, right) = pipe();
+        let mut server = compressing(right);
+        write(&mut left, &[0, 0, 0, 3, 0, b'h', b'i']);
+        assert!(matches!(
+            read(&mut server, 2),
+            Err(StreamError::Protocol(Violation::Hello))
+        ));
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let (mut left, right) = pipe();
+        let mut server = compressing(right);
+        let mut frames = vec![0, 0, 0, 3, HELLO, Deflate::ID, 1];
+        frames.extend_from_slice(&(DEFAULT_LIMIT as u32 + 2).to_be_bytes());
+        write(&mut left, &frames);
+        assert!(matches!(
+            read(&mut server, 1),
+            Err(StreamError::Protocol(Violation::Length))
+        ));
+    }
+
+    #[test]
+    fn clamps_limit() {
+        let (_, state) = Compressed::new(()).with_limit(16).split();
+        assert_eq!(state.limit, FRAME);
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let (left, right) = channel::<Vec<u8>>();
+        let (_, state) = Compressed::new(()).with_threshold(64).split();
+        let mut client = Frames {
+            transport: left,
+            state,
+        };
+        let (_, state) = Compressed::new(()).with_threshold(64).split();
+        let mut server = Frames {
+            transport: right,
+            state,
+        };
+        let long = vec![b'a'; 4096];
+        let (sent, received) = block_on(join(
+            async {
+                client.send(b"short".to_vec()).await?;
+                client.send(long.clone()).await
+            },
+            async { (server.next().await, server.next().await) },
+        ));
+        sent.unwrap();
+        assert_eq!(received.0.unwrap().unwrap(), b"short");
+        assert_eq!(received.1.unwrap().unwrap(), long);
+        assert!(client.is_negotiated());
+    }
+}
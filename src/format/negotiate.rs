@@ -0,0 +1,526 @@
+use super::{ByteFormat, Format};
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    mem::replace,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::{ready, Sink, Stream, TryStream};
+
+const MAGIC: u8 = 0x4E;
+const ENTRY: usize = 6;
+
+pub type Erased = Box<dyn Debug>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Descriptor {
+    id: u16,
+    minimum: u16,
+    version: u16,
+}
+
+impl Descriptor {
+    pub fn new(id: u16, version: u16) -> Self {
+        Descriptor {
+            id,
+            minimum: version,
+            version,
+        }
+    }
+
+    pub fn with_minimum(mut self, minimum: u16) -> Self {
+        self.minimum = minimum.min(self.version);
+        self
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn minimum(&self) -> u16 {
+        self.minimum
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    fn encode(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&self.id.to_be_bytes());
+        output.extend_from_slice(&self.minimum.to_be_bytes());
+        output.extend_from_slice(&self.version.to_be_bytes());
+    }
+
+    fn decode(entry: &[u8]) -> Option<Self> {
+        let field = |index: usize| u16::from_be_bytes([entry[index], entry[index + 1]]);
+        let (id, minimum, version) = (field(0), field(2), field(4));
+        if minimum > version {
+            return None;
+        }
+        Some(Descriptor {
+            id,
+            minimum,
+            version,
+        })
+    }
+
+    fn agree(&self, other: &Descriptor) -> Option<Descriptor> {
+        let version = self.version.min(other.version);
+        if self.id != other.id || version < self.minimum.max(other.minimum) {
+            return None;
+        }
+        Some(Descriptor::new(self.id, version))
+    }
+}
+
+pub enum Error<S: AsyncRead + AsyncWrite> {
+    Read(S::Error),
+    Write(S::WriteError),
+    Flush(S::FlushError),
+    Format(Erased),
+    Malformed,
+    Incompatible,
+    Terminated,
+}
+
+impl<S: AsyncRead + AsyncWrite> Debug for Error<S>
+where
+    S::Error: Debug,
+    S::WriteError: Debug,
+    S::FlushError: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Read(e) => f.debug_tuple("Read").field(e).finish(),
+            Error::Write(e) => f.debug_tuple("Write").field(e).finish(),
+            Error::Flush(e) => f.debug_tuple("Flush").field(e).finish(),
+            Error::Format(e) => f.debug_tuple("Format").field(e).finish(),
+            Error::Malformed => f.write_str("Malformed"),
+            Error::Incompatible => f.write_str("Incompatible"),
+            Error::Terminated => f.write_str("Terminated"),
+        }
+    }
+}
+
+trait Wired<T>: Stream<Item = Result<T, Erased>> + Sink<T, Error = Erased> {}
+
+impl<T, W: Stream<Item = Result<T, Erased>> + Sink<T, Error = Erased>> Wired<T> for W {}
+
+struct Erase<O>(Pin<Box<O>>);
+
+impl<T, O: TryStream<Ok = T>> Stream for Erase<O>
+where
+    O::Error: Debug + 'static,
+{
+    type Item = Result<T, Erased>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let item = ready!(self.0.as_mut().try_poll_next(ctx));
+        Poll::Ready(item.map(|item| item.map_err(|e| Box::new(e) as Erased)))
+    }
+}
+
+impl<T, O: Sink<T>> Sink<T> for Erase<O>
+where
+    O::Error: Debug + 'static,
+{
+    type Error = Erased;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Erased>> {
+        self.0
+            .as_mut()
+            .poll_ready(ctx)
+            .map_err(|e| Box::new(e) as Erased)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), Erased> {
+        self.0
+            .as_mut()
+            .start_send(item)
+            .map_err(|e| Box::new(e) as Erased)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Erased>> {
+        self.0
+            .as_mut()
+            .poll_flush(ctx)
+            .map_err(|e| Box::new(e) as Erased)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Erased>> {
+        self.0
+            .as_mut()
+            .poll_close(ctx)
+            .map_err(|e| Box::new(e) as Erased)
+    }
+}
+
+type Wire<T, S> = Box<dyn FnOnce(S, u16) -> Pin<Box<dyn Wired<T>>>>;
+
+pub struct Dynamic<T, S> {
+    candidates: Vec<(Descriptor, Wire<T, S>)>,
+}
+
+impl<T: 'static, S: AsyncRead + AsyncWrite + 'static> Dynamic<T, S> {
+    pub fn new() -> Self {
+        Dynamic {
+            candidates: Vec::new(),
+        }
+    }
+
+    pub fn with_format<F: ByteFormat<T, S> + 'static>(
+        self,
+        descriptor: Descriptor,
+        format: F,
+    ) -> Self
+    where
+        F::Output: 'static,
+        <F::Output as TryStream>::Error: Debug + 'static,
+        <F::Output as Sink<T>>::Error: Debug + 'static,
+    {
+        self.with_versioned(descriptor, move |_| format)
+    }
+
+    pub fn with_versioned<F: ByteFormat<T, S> + 'static, C: FnOnce(u16) -> F + 'static>(
+        mut self,
+        descriptor: Descriptor,
+        constructor: C,
+    ) -> Self
+    where
+        F::Output: 'static,
+        <F::Output as TryStream>::Error: Debug + 'static,
+        <F::Output as Sink<T>>::Error: Debug + 'static,
+    {
+        self.candidates.retain(|(existing, _)| existing.id != descriptor.id);
+        if self.candidates.len() == u8::MAX as usize {
+            return self;
+        }
+        self.candidates.push((
+            descriptor,
+            Box::new(move |stream: S, version: u16| {
+                let output = constructor(version).wire(stream);
+                Box::pin(Erase(Box::pin(output))) as Pin<Box<dyn Wired<T>>>
+            }),
+        ));
+        self
+    }
+
+    pub fn descriptors(&self) -> impl Iterator<Item = Descriptor> + '_ {
+        self.candidates.iter().map(|(descriptor, _)| *descriptor)
+    }
+}
+
+impl<T: 'static, S: AsyncRead + AsyncWrite + 'static> Default for Dynamic<T, S> {
+    fn default() -> Self {
+        Dynamic::new()
+    }
+}
+
+impl<T, S> Format<T> for Dynamic<T, S> {}
+
+struct Greeting<T, S> {
+    stream: S,
+    candidates: Vec<(Descriptor, Wire<T, S>)>,
+    outbound: Vec<u8>,
+    written: usize,
+    flushed: bool,
+    inbound: Vec<u8>,
+    filled: usize,
+}
+
+impl<T, S: AsyncRead + AsyncWrite + Unpin> Greeting<T, S> {
+    fn poll_exchange(&mut self, ctx: &mut Context) -> Poll<Result<(), Error<S>>> {
+        while self.written < self.outbound.len() {
+            let buf = &self.outbound[self.written..];
+            let count = ready!(Pin::new(&mut self.stream).poll_write(ctx, buf))
+                .map_err(Error::Write)?;
+            if count == 0 {
+                return Poll::Ready(Err(Error::Terminated));
+            }
+            self.written += count;
+        }
+        if !self.flushed {
+            ready!(Pin::new(&mut self.stream).poll_flush(ctx)).map_err(Error::Flush)?;
+            self.flushed = true;
+        }
+        loop {
+            let target = if self.filled < 2 {
+                2
+            } else if self.inbound[0] != MAGIC {
+                return Poll::Ready(Err(Error::Malformed));
+            } else {
+                2 + self.inbound[1] as usize * ENTRY
+            };
+            if self.filled == target {
+                return Poll::Ready(Ok(()));
+            }
+            self.inbound.resize(target, 0);
+            let buf = &mut self.inbound[self.filled..];
+            let count =
+                ready!(Pin::new(&mut self.stream).poll_read(ctx, buf)).map_err(Error::Read)?;
+            if count == 0 {
+                return Poll::Ready(Err(Error::Terminated));
+            }
+            self.filled += count;
+        }
+    }
+
+    fn select(self) -> Result<(Descriptor, Pin<Box<dyn Wired<T>>>), Error<S>> {
+        let mut remote = Vec::new();
+        for entry in self.inbound[2..].chunks(ENTRY) {
+            remote.push(Descriptor::decode(entry).ok_or(Error::Malformed)?);
+        }
+        let mut selected: Option<(usize, Descriptor, usize)> = None;
+        for (index, (local, _)) in self.candidates.iter().enumerate() {
+            for (position, peer) in remote.iter().enumerate() {
+                let agreed = match local.agree(peer) {
+                    Some(agreed) => agreed,
+                    None => continue,
+                };
+                let rank = index + position;
+                let better = match selected {
+                    Some((best, current, _)) => {
+                        rank < best || (rank == best && agreed.id < current.id)
+                    }
+                    None => true,
+                };
+                if better {
+                    selected = Some((rank, agreed, index));
+                }
+            }
+        }
+        let (_, agreed, index) = selected.ok_or(Error::Incompatible)?;
+        let (_, wire) = self.candidates.into_iter().nth(index).unwrap();
+        Ok((agreed, wire(self.stream, agreed.version)))
+    }
+}
+
+enum Stage<T, S> {
+    Greeting(Greeting<T, S>),
+    Wired(Descriptor, Pin<Box<dyn Wired<T>>>),
+    Failed,
+}
+
+pub struct Negotiated<T, S> {
+    stage: Stage<T, S>,
+}
+
+impl<T, S: Unpin> Unpin for Negotiated<T, S> {}
+
+impl<T, S> Negotiated<T, S> {
+    pub fn agreed(&self) -> Option<Descriptor> {
+        match &self.stage {
+            Stage::Wired(descriptor, _) => Some(*descriptor),
+            _ => None,
+        }
+    }
+}
+
+impl<T, S: AsyncRead + AsyncWrite + Unpin> Negotiated<T, S> {
+    fn poll_wired(&mut self, ctx: &mut Context) -> Poll<Result<Pin<&mut dyn Wired<T>>, Error<S>>> {
+        if let Stage::Greeting(greeting) = &mut self.stage {
+            let exchanged = ready!(greeting.poll_exchange(ctx));
+            let greeting = match replace(&mut self.stage, Stage::Failed) {
+                Stage::Greeting(greeting) => greeting,
+                _ => unreachable!(),
+            };
+            let (descriptor, wired) = exchanged.and_then(|_| greeting.select())?;
+            self.stage = Stage::Wired(descriptor, wired);
+        }
+        match &mut self.stage {
+            Stage::Wired(_, wired) => Poll::Ready(Ok(wired.as_mut())),
+            _ => Poll::Ready(Err(Error::Terminated)),
+        }
+    }
+}
+
+impl<T, S: AsyncRead + AsyncWrite + Unpin> Stream for Negotiated<T, S> {
+    type Item = Result<T, Error<S>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let wired = match ready!(self.poll_wired(ctx)) {
+            Ok(wired) => wired,
+            Err(e) => return Poll::Ready(Some(Err(e))),
+        };
+        let item = ready!(wired.poll_next(ctx));
+        Poll::Ready(item.map(|item| item.map_err(Error::Format)))
+    }
+}
+
+impl<T, S: AsyncRead + AsyncWrite + Unpin> Sink<T> for Negotiated<T, S> {
+    type Error = Error<S>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_wired(ctx))?
+            .poll_ready(ctx)
+            .map_err(Error::Format)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        match &mut self.stage {
+            Stage::Wired(_, wired) => wired.as_mut().start_send(item).map_err(Error::Format),
+            _ => Err(Error::Terminated),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_wired(ctx))?
+            .poll_flush(ctx)
+            .map_err(Error::Format)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        match &mut self.stage {
+            Stage::Wired(_, wired) => wired.as_mut().poll_close(ctx).map_err(Error::Format),
+            _ => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+impl<T, S: AsyncRead + AsyncWrite + Unpin> ByteFormat<T, S> for Dynamic<T, S> {
+    type Output = Negotiated<T, S>;
+
+    fn wire(self, stream: S) -> Negotiated<T, S> {
+        let count = self.candidates.len();
+        let mut outbound = Vec::with_capacity(2 + count * ENTRY);
+        outbound.extend_from_slice(&[MAGIC, count as u8]);
+        for (descriptor, _) in &self.candidates {
+            descriptor.encode(&mut outbound);
+        }
+        Negotiated {
+            stage: Stage::Greeting(Greeting {
+                stream,
+                candidates: self.candidates,
+                outbound,
+                written: 0,
+                flushed: false,
+                inbound: Vec::new(),
+                filled: 0,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Descriptor, Dynamic, Error, Negotiated};
+    use crate::{
+        format::{ByteFormat, Format},
+        transport::pipe::{pipe, Pipe},
+    };
+    use core::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use core_futures_io::{AsyncRead, AsyncWrite};
+    use futures::{executor::block_on, future::join, ready, Sink, SinkExt, Stream, StreamExt};
+    use void::Void;
+
+    struct Shift(u8);
+
+    impl Format<u8> for Shift {}
+
+    impl ByteFormat<u8, Pipe> for Shift {
+        type Output = Shifted;
+
+        fn wire(self, stream: Pipe) -> Shifted {
+            Shifted {
+                stream,
+                shift: self.0,
+                pending: None,
+            }
+        }
+    }
+
+    struct Shifted {
+        stream: Pipe,
+        shift: u8,
+        pending: Option<u8>,
+    }
+
+    impl Stream for Shifted {
+        type Item = Result<u8, Void>;
+
+        fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+            let mut byte = [0];
+            let count = ready!(Pin::new(&mut self.stream).poll_read(ctx, &mut byte))?;
+            Poll::Ready(Some(Ok(byte[0].wrapping_sub(self.shift))).filter(|_| count == 1))
+        }
+    }
+
+    impl Sink<u8> for Shifted {
+        type Error = Void;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Void>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: u8) -> Result<(), Void> {
+            self.pending = Some(item.wrapping_add(self.shift));
+            Ok(())
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Void>> {
+            if let Some(byte) = self.pending.take() {
+                ready!(Pin::new(&mut self.stream).poll_write(ctx, &[byte]))?;
+            }
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Void>> {
+            self.poll_flush(ctx)
+        }
+    }
+
+    fn versioned(version: u16) -> Shift {
+        Shift(version as u8 * 10)
+    }
+
+    #[test]
+    fn selects_common_format() {
+        let (left, right) = pipe();
+        let mut client: Negotiated<u8, Pipe> = Dynamic::new()
+            .with_format(Descriptor::new(1, 1), Shift(1))
+            .with_versioned(Descriptor::new(2, 3).with_minimum(1), versioned)
+            .wire(left);
+        let mut server: Negotiated<u8, Pipe> = Dynamic::new()
+            .with_versioned(Descriptor::new(2, 2), versioned)
+            .with_format(Descriptor::new(3, 1), Shift(3))
+            .wire(right);
+        let (sent, received) = block_on(join(client.send(42), server.next()));
+        sent.unwrap();
+        assert_eq!(received.unwrap().unwrap(), 42);
+        assert_eq!(client.agreed(), Some(Descriptor::new(2, 2)));
+        assert_eq!(server.agreed(), Some(Descriptor::new(2, 2)));
+    }
+
+    #[test]
+    fn rejects_incompatible_formats() {
+        let (left, right) = pipe();
+        let mut client: Negotiated<u8, Pipe> = Dynamic::new()
+            .with_versioned(Descriptor::new(2, 1), versioned)
+            .wire(left);
+        let mut server: Negotiated<u8, Pipe> = Dynamic::new()
+            .with_versioned(Descriptor::new(2, 3).with_minimum(2), versioned)
+            .wire(right);
+        let (sent, received) = block_on(join(client.send(42), server.next()));
+        assert!(matches!(sent, Err(Error::Incompatible)));
+        assert!(matches!(received, Some(Err(Error::Incompatible))));
+    }
+
+    #[test]
+    fn ignores_formats_past_limit() {
+        let dynamic = (0..=u8::MAX as u16).fold(Dynamic::<u8, Pipe>::new(), |dynamic, id| {
+            dynamic.with_format(Descriptor::new(id, 1), Shift(0))
+        });
+        assert_eq!(dynamic.descriptors().count(), u8::MAX as usize);
+        assert_eq!(
+            dynamic.descriptors().last(),
+            Some(Descriptor::new(u8::MAX as u16 - 1, 1))
+        );
+        let dynamic = dynamic.with_format(Descriptor::new(0, 2), Shift(0));
+        assert_eq!(dynamic.descriptors().last(), Some(Descriptor::new(0, 2)));
+    }
+}
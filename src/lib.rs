@@ -6,10 +6,20 @@ extern crate alloc;
 use core::{future::Future, ops::DerefMut};
 use futures::{Sink, TryFuture, TryStream};
 
+mod control_flow;
+pub mod deadline;
+pub use deadline::Deadline;
+#[cfg(all(unix, feature = "std"))]
+pub mod descriptor;
 pub mod director;
 pub use director::Director;
+mod either;
 pub mod format;
 mod option;
+mod poll;
+#[cfg(feature = "alloc")]
+pub mod session;
+pub mod transport;
 mod unit;
 pub use format::Format;
 
@@ -2,7 +2,6 @@ use crate::Bottom;
 use crate::{Channels, ContextError, Dispatch, Join, Pass, Protocol, Spawn};
 use core::{
     future::Future,
-    mem::replace,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -101,7 +100,7 @@ where
                     };
                     let replacement =
                         Coalesce::Join(channel.into_inner().join(handle.map_err(Error::Channel)?));
-                    replace(&mut *self, replacement);
+                    self.set(replacement);
                 }
                 Coalesce::Join(join) => {
                     pin_mut!(join);
@@ -146,7 +145,7 @@ where
                         Unravel::Send(once(ready(Ok(handle))).forward(channel.take().expect(
                             "violated invariant in Protocol for Option: no channel in Spawn stage",
                         )));
-                    replace(&mut *self, replacement);
+                    self.set(replacement);
                 }
                 Unravel::Send(send) => {
                     pin_mut!(send);
@@ -193,3 +192,82 @@ where
         Coalesce::new(channel)
     }
 }
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::Error;
+    use crate::{
+        director::{trivial, DirectorError, Trivial},
+        format::Null,
+        transport::pipe::{duplex, Channel},
+        Bottom, Channels, Director, Protocol,
+    };
+    use futures::{
+        executor::block_on,
+        future::{join, pending, ready, Pending, Ready},
+    };
+    use void::Void;
+
+    type Unravel = Channel<(), Bottom>;
+    type Coalesce = Channel<Bottom, ()>;
+
+    fn round_trip(item: Option<Option<()>>) -> Option<Option<()>> {
+        let (left, right) = duplex();
+        let unravel =
+            <Trivial as Director<_, Null, Coalesce, Unravel>>::unravel(Trivial, item, left);
+        let coalesce = <Trivial as Director<Option<Option<()>>, Null, Coalesce, Unravel>>::coalesce(
+            Trivial, right,
+        );
+        let (unravelled, coalesced) = block_on(join(unravel, coalesce));
+        unravelled.unwrap();
+        coalesced.unwrap()
+    }
+
+    #[test]
+    fn round_trips_nested_options() {
+        assert_eq!(round_trip(Some(Some(()))), Some(Some(())));
+        assert_eq!(round_trip(Some(None)), Some(None));
+        assert_eq!(round_trip(None), None);
+    }
+
+    #[derive(Debug)]
+    struct Refused;
+
+    struct Refuse;
+
+    impl<F: ?Sized, C> Protocol<F, C> for Refuse {
+        type Unravel = Bottom;
+        type UnravelError = Refused;
+        type UnravelFuture = Ready<Result<(), Refused>>;
+        type Coalesce = Bottom;
+        type CoalesceError = Void;
+        type CoalesceFuture = Pending<Result<Refuse, Void>>;
+
+        fn unravel(self, _: C::Unravel) -> Self::UnravelFuture
+        where
+            C: Channels<Self::Unravel, Self::Coalesce>,
+        {
+            ready(Err(Refused))
+        }
+
+        fn coalesce(_: C::Coalesce) -> Self::CoalesceFuture
+        where
+            C: Channels<Self::Unravel, Self::Coalesce>,
+        {
+            pending()
+        }
+    }
+
+    #[test]
+    fn propagates_deferred_child_errors() {
+        let (left, _right) = duplex();
+        let unravel =
+            <Trivial as Director<_, Null, Coalesce, Unravel>>::unravel(Trivial, Some(Refuse), left);
+        match block_on(unravel) {
+            Err(DirectorError::Protocol(Error::Channel(trivial::Error::Nested(error)))) => {
+                assert!(error.is::<Refused>())
+            }
+            _ => panic!("child error was discarded"),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{Channels, Format, Protocol};
+use core::task::Poll;
+use futures::{future::MapOk, TryFutureExt};
+
+fn from_option<T>(item: Option<T>) -> Poll<T> {
+    match item {
+        Some(item) => Poll::Ready(item),
+        None => Poll::Pending,
+    }
+}
+
+impl<F: ?Sized, C, T> Protocol<F, C> for Poll<T>
+where
+    Option<T>: Protocol<F, C>,
+{
+    type Unravel = <Option<T> as Protocol<F, C>>::Unravel;
+    type UnravelError = <Option<T> as Protocol<F, C>>::UnravelError;
+    type UnravelFuture = <Option<T> as Protocol<F, C>>::UnravelFuture;
+    type Coalesce = <Option<T> as Protocol<F, C>>::Coalesce;
+    type CoalesceError = <Option<T> as Protocol<F, C>>::CoalesceError;
+    type CoalesceFuture =
+        MapOk<<Option<T> as Protocol<F, C>>::CoalesceFuture, fn(Option<T>) -> Poll<T>>;
+
+    fn unravel(self, channel: C::Unravel) -> Self::UnravelFuture
+    where
+        C: Channels<Self::Unravel, Self::Coalesce>,
+        F: Format<Self::Unravel> + Format<Self::Coalesce>,
+    {
+        match self {
+            Poll::Ready(item) => Some(item),
+            Poll::Pending => None,
+        }
+        .unravel(channel)
+    }
+
+    fn coalesce(channel: C::Coalesce) -> Self::CoalesceFuture
+    where
+        C: Channels<Self::Unravel, Self::Coalesce>,
+        F: Format<Self::Unravel> + Format<Self::Coalesce>,
+    {
+        <Option<T> as Protocol<F, C>>::coalesce(channel).map_ok(from_option as fn(_) -> _)
+    }
+}
@@ -0,0 +1,394 @@
+use alloc::{rc::Rc, sync::Arc, task::Wake};
+use core::{
+    cell::{Cell, RefCell},
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use futures::{ready, task::AtomicWaker, Sink, Stream, TryFuture, TryStream};
+
+#[derive(Debug)]
+pub enum Message<Unravel, Coalesce> {
+    Unravel(Unravel),
+    Coalesce(Coalesce),
+}
+
+#[derive(Debug)]
+pub enum Error<Unravel, Coalesce> {
+    Unravel(Unravel),
+    Coalesce(Coalesce),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Unravel,
+    Coalesce,
+}
+
+impl Side {
+    fn peer(self) -> Side {
+        match self {
+            Side::Unravel => Side::Coalesce,
+            Side::Coalesce => Side::Unravel,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Wakers {
+    unravel: AtomicWaker,
+    coalesce: AtomicWaker,
+}
+
+impl Wakers {
+    fn register(&self, side: Side, waker: &Waker) {
+        match side {
+            Side::Unravel => self.unravel.register(waker),
+            Side::Coalesce => self.coalesce.register(waker),
+        }
+    }
+
+    fn wake_peer(&self, side: Side) {
+        match side {
+            Side::Unravel => self.coalesce.wake(),
+            Side::Coalesce => self.unravel.wake(),
+        }
+    }
+}
+
+impl Wake for Wakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.unravel.wake();
+        self.coalesce.wake();
+    }
+}
+
+struct Status {
+    wakers: Arc<Wakers>,
+    closed: [Cell<bool>; 2],
+    dropped: [Cell<bool>; 2],
+}
+
+impl Status {
+    fn close(&self, side: Side) -> bool {
+        self.closed[side as usize].set(true);
+        self.wakers.wake_by_ref();
+        self.closed.iter().all(Cell::get)
+    }
+
+    fn release(&self, side: Side) {
+        self.dropped[side as usize].set(true);
+        self.close(side);
+    }
+}
+
+struct Shared<T: TryStream> {
+    transport: T,
+    buffer: Option<T::Ok>,
+    status: Rc<Status>,
+    waker: Waker,
+    terminated: bool,
+}
+
+impl<T: Unpin + TryStream> Shared<T> {
+    fn register(&self, side: Side, ctx: &Context) -> Waker {
+        self.status.wakers.register(side, ctx.waker());
+        self.waker.clone()
+    }
+}
+
+impl<T: Unpin + TryStream<Ok = Message<C, D>>, C, D> Shared<T> {
+    fn poll_next<I>(
+        &mut self,
+        side: Side,
+        ctx: &mut Context,
+        select: fn(Message<C, D>) -> Result<I, Message<C, D>>,
+    ) -> Poll<Option<Result<I, T::Error>>> {
+        let waker = self.register(side, ctx);
+        loop {
+            if let Some(message) = self.buffer.take() {
+                match select(message) {
+                    Ok(item) => {
+                        self.status.wakers.wake_peer(side);
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                    Err(_) if self.status.dropped[side.peer() as usize].get() => continue,
+                    Err(message) => {
+                        self.buffer = Some(message);
+                        self.status.wakers.wake_peer(side);
+                        return Poll::Pending;
+                    }
+                }
+            }
+            if self.terminated {
+                return Poll::Ready(None);
+            }
+            let mut ctx = Context::from_waker(&waker);
+            match ready!(Pin::new(&mut self.transport).try_poll_next(&mut ctx)) {
+                Some(Ok(message)) => self.buffer = Some(message),
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    self.terminated = true;
+                    self.status.wakers.wake_by_ref();
+                }
+            }
+        }
+    }
+}
+
+pub struct Unravel<T: TryStream, A, B> {
+    shared: Rc<RefCell<Shared<T>>>,
+    status: Rc<Status>,
+    data: PhantomData<fn(A, B)>,
+}
+
+pub struct Coalesce<T: TryStream, A, B> {
+    shared: Rc<RefCell<Shared<T>>>,
+    status: Rc<Status>,
+    data: PhantomData<fn(A, B)>,
+}
+
+pub struct Session<T: TryStream> {
+    shared: Rc<RefCell<Shared<T>>>,
+    status: Rc<Status>,
+}
+
+impl<T: TryStream> Session<T> {
+    pub fn new(transport: T) -> Self {
+        let wakers = Arc::new(Wakers::default());
+        let status = Rc::new(Status {
+            wakers: wakers.clone(),
+            closed: Default::default(),
+            dropped: Default::default(),
+        });
+        Session {
+            shared: Rc::new(RefCell::new(Shared {
+                transport,
+                buffer: None,
+                status: status.clone(),
+                waker: Waker::from(wakers),
+                terminated: false,
+            })),
+            status,
+        }
+    }
+
+    pub fn split<A, B>(self) -> (Unravel<T, A, B>, Coalesce<T, A, B>)
+    where
+        T: Sink<Message<A, B>>,
+    {
+        (
+            Unravel {
+                shared: self.shared.clone(),
+                status: self.status.clone(),
+                data: PhantomData,
+            },
+            Coalesce {
+                shared: self.shared,
+                status: self.status,
+                data: PhantomData,
+            },
+        )
+    }
+
+    pub fn exchange<A, B, U: TryFuture<Ok = ()> + Unpin, C: TryFuture + Unpin>(
+        self,
+        unravel: impl FnOnce(Unravel<T, A, B>) -> U,
+        coalesce: impl FnOnce(Coalesce<T, A, B>) -> C,
+    ) -> Exchange<U, C>
+    where
+        T: Sink<Message<A, B>>,
+    {
+        let (unravel_half, coalesce_half) = self.split();
+        Exchange {
+            unravel: Some(unravel(unravel_half)),
+            coalesce: Some(coalesce(coalesce_half)),
+            output: None,
+        }
+    }
+}
+
+fn poll_sink<T: Unpin + TryStream + Sink<M>, M>(
+    shared: &RefCell<Shared<T>>,
+    side: Side,
+    ctx: &mut Context,
+    call: fn(Pin<&mut T>, &mut Context) -> Poll<Result<(), <T as Sink<M>>::Error>>,
+) -> Poll<Result<(), <T as Sink<M>>::Error>> {
+    let mut shared = shared.borrow_mut();
+    let waker = shared.register(side, ctx);
+    call(
+        Pin::new(&mut shared.transport),
+        &mut Context::from_waker(&waker),
+    )
+}
+
+fn poll_close<T: Unpin + TryStream + Sink<M>, M>(
+    shared: &RefCell<Shared<T>>,
+    status: &Status,
+    side: Side,
+    ctx: &mut Context,
+) -> Poll<Result<(), <T as Sink<M>>::Error>> {
+    if status.close(side) {
+        poll_sink(shared, side, ctx, Sink::poll_close)
+    } else {
+        poll_sink(shared, side, ctx, Sink::poll_flush)
+    }
+}
+
+impl<T: Unpin + TryStream + Sink<Message<A, B>>, A, B> Sink<A> for Unravel<T, A, B> {
+    type Error = <T as Sink<Message<A, B>>>::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        poll_sink(&self.shared, Side::Unravel, ctx, Sink::poll_ready)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: A) -> Result<(), Self::Error> {
+        Pin::new(&mut self.shared.borrow_mut().transport).start_send(Message::Unravel(item))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        poll_sink(&self.shared, Side::Unravel, ctx, Sink::poll_flush)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        poll_close(&self.shared, &self.status, Side::Unravel, ctx)
+    }
+}
+
+impl<T: Unpin + TryStream + Sink<Message<A, B>>, A, B> Sink<B> for Coalesce<T, A, B> {
+    type Error = <T as Sink<Message<A, B>>>::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        poll_sink(&self.shared, Side::Coalesce, ctx, Sink::poll_ready)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: B) -> Result<(), Self::Error> {
+        Pin::new(&mut self.shared.borrow_mut().transport).start_send(Message::Coalesce(item))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        poll_sink(&self.shared, Side::Coalesce, ctx, Sink::poll_flush)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        poll_close(&self.shared, &self.status, Side::Coalesce, ctx)
+    }
+}
+
+impl<T: Unpin + TryStream<Ok = Message<C, D>>, A, B, C, D> Stream for Unravel<T, A, B> {
+    type Item = Result<D, T::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.shared
+            .borrow_mut()
+            .poll_next(Side::Unravel, ctx, |message| match message {
+                Message::Coalesce(item) => Ok(item),
+                message => Err(message),
+            })
+    }
+}
+
+impl<T: Unpin + TryStream<Ok = Message<C, D>>, A, B, C, D> Stream for Coalesce<T, A, B> {
+    type Item = Result<C, T::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.shared
+            .borrow_mut()
+            .poll_next(Side::Coalesce, ctx, |message| match message {
+                Message::Unravel(item) => Ok(item),
+                message => Err(message),
+            })
+    }
+}
+
+impl<T: TryStream, A, B> Drop for Unravel<T, A, B> {
+    fn drop(&mut self) {
+        self.status.release(Side::Unravel);
+    }
+}
+
+impl<T: TryStream, A, B> Drop for Coalesce<T, A, B> {
+    fn drop(&mut self) {
+        self.status.release(Side::Coalesce);
+    }
+}
+
+pub struct Exchange<U: TryFuture, C: TryFuture> {
+    unravel: Option<U>,
+    coalesce: Option<C>,
+    output: Option<C::Ok>,
+}
+
+impl<U: TryFuture + Unpin, C: TryFuture + Unpin> Unpin for Exchange<U, C> {}
+
+impl<U: TryFuture<Ok = ()> + Unpin, C: TryFuture + Unpin> Future for Exchange<U, C> {
+    type Output = Result<C::Ok, Error<U::Error, C::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        if let Some(unravel) = &mut self.unravel {
+            if let Poll::Ready(result) = Pin::new(unravel).try_poll(ctx) {
+                self.unravel = None;
+                result.map_err(Error::Unravel)?;
+            }
+        }
+        if let Some(coalesce) = &mut self.coalesce {
+            if let Poll::Ready(result) = Pin::new(coalesce).try_poll(ctx) {
+                self.coalesce = None;
+                self.output = Some(result.map_err(Error::Coalesce)?);
+            }
+        }
+        if self.unravel.is_none() && self.coalesce.is_none() {
+            Poll::Ready(Ok(self
+                .output
+                .take()
+                .expect("Exchange polled after completion")))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Message, Session};
+    use crate::transport::pipe::channel;
+    use futures::{executor::block_on, FutureExt, SinkExt, StreamExt};
+
+    #[test]
+    fn routes_messages_to_halves() {
+        let (left, right) = channel::<Message<u8, u16>>();
+        let (mut unravel, _coalesce) = Session::new(left).split::<u8, u16>();
+        let (_peer_unravel, mut peer_coalesce) = Session::new(right).split::<u8, u16>();
+        block_on(unravel.send(1)).unwrap();
+        assert_eq!(block_on(peer_coalesce.next()).unwrap().unwrap(), 1);
+        block_on(peer_coalesce.send(2)).unwrap();
+        assert_eq!(block_on(unravel.next()).unwrap().unwrap(), 2);
+    }
+
+    #[test]
+    fn closes_transport_after_both_halves() {
+        let (left, mut right) = channel::<Message<u8, u16>>();
+        let (mut unravel, mut coalesce) = Session::new(left).split::<u8, u16>();
+        block_on(unravel.close()).unwrap();
+        assert!(right.next().now_or_never().is_none());
+        block_on(coalesce.close()).unwrap();
+        assert!(right.next().now_or_never().unwrap().is_none());
+    }
+
+    #[test]
+    fn records_drop_while_borrowed() {
+        let (left, mut right) = channel::<Message<u8, u16>>();
+        let (mut unravel, coalesce) = Session::new(left).split::<u8, u16>();
+        block_on(right.send(Message::Unravel(1))).unwrap();
+        block_on(right.send(Message::Coalesce(2))).unwrap();
+        let borrowed = unravel.shared.borrow_mut();
+        drop(coalesce);
+        drop(borrowed);
+        assert_eq!(unravel.next().now_or_never().unwrap().unwrap().unwrap(), 2);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::director::Timer;
+use ::async_std::{
+    io,
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    task::sleep,
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use std::net::SocketAddr;
+#[cfg(unix)]
+use ::async_std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+
+pub struct Compat<T>(T);
+
+impl<T> Compat<T> {
+    pub fn new(stream: T) -> Self {
+        Compat(stream)
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.0
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: io::Read + Unpin> AsyncRead for Compat<T> {
+    type Error = io::Error;
+
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        io::Read::poll_read(Pin::new(&mut self.0), ctx, buf)
+    }
+}
+
+impl<T: io::Write + Unpin> AsyncWrite for Compat<T> {
+    type WriteError = io::Error;
+    type FlushError = io::Error;
+    type CloseError = io::Error;
+
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        io::Write::poll_write(Pin::new(&mut self.0), ctx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), io::Error>> {
+        io::Write::poll_flush(Pin::new(&mut self.0), ctx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), io::Error>> {
+        io::Write::poll_close(Pin::new(&mut self.0), ctx)
+    }
+}
+
+pub async fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Compat<TcpStream>> {
+    let stream = TcpStream::connect(address).await?;
+    stream.set_nodelay(true)?;
+    Ok(Compat(stream))
+}
+
+pub async fn accept(listener: &TcpListener) -> io::Result<(Compat<TcpStream>, SocketAddr)> {
+    let (stream, address) = listener.accept().await?;
+    stream.set_nodelay(true)?;
+    Ok((Compat(stream), address))
+}
+
+#[cfg(unix)]
+pub async fn connect_unix<P: AsRef<Path>>(path: P) -> io::Result<Compat<UnixStream>> {
+    UnixStream::connect(path.as_ref()).await.map(Compat)
+}
+
+#[cfg(unix)]
+pub async fn accept_unix(listener: &UnixListener) -> io::Result<Compat<UnixStream>> {
+    let (stream, _) = listener.accept().await?;
+    Ok(Compat(stream))
+}
+
+#[cfg(unix)]
+pub fn duplex() -> io::Result<(Compat<UnixStream>, Compat<UnixStream>)> {
+    let (a, b) = UnixStream::pair()?;
+    Ok((Compat(a), Compat(b)))
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Clock;
+
+impl Timer for Clock {
+    type Delay = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    fn delay(&mut self, duration: Duration) -> Self::Delay {
+        Box::pin(sleep(duration))
+    }
+}
@@ -0,0 +1,236 @@
+use super::Packet;
+use crate::director::{Frame, Handle};
+use core::{
+    convert::TryInto,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{ready, Sink, Stream, TryStream};
+
+const ITEM: u8 = 0;
+const WINDOW: u8 = 1;
+const CLOSE: u8 = 2;
+const CANCEL: u8 = 3;
+const CLAIM: u8 = 4;
+const GOAWAY: u8 = 5;
+const PING: u8 = 6;
+const PONG: u8 = 7;
+const RESUME: u8 = 8;
+const ACK: u8 = 9;
+const CHALLENGE: u8 = 10;
+const RESPONSE: u8 = 11;
+
+#[derive(Debug)]
+pub enum FrameError<E> {
+    Transport(E),
+    Malformed,
+    Oversized,
+}
+
+fn encode<const N: usize>(frame: &Frame<Packet<N>>) -> Option<Packet<N>> {
+    let mut buffer = [0; N];
+    let mut length = 0;
+    let mut push = |bytes: &[u8]| {
+        buffer
+            .get_mut(length..length + bytes.len())?
+            .copy_from_slice(bytes);
+        length += bytes.len();
+        Some(())
+    };
+    match frame {
+        Frame::Item(handle, item) => {
+            push(&[ITEM])?;
+            push(&handle.0.to_le_bytes())?;
+            push(item)?;
+        }
+        Frame::Window(handle, credit) => {
+            push(&[WINDOW])?;
+            push(&handle.0.to_le_bytes())?;
+            push(&credit.to_le_bytes())?;
+        }
+        Frame::Close(handle) => {
+            push(&[CLOSE])?;
+            push(&handle.0.to_le_bytes())?;
+        }
+        Frame::Cancel(handle) => {
+            push(&[CANCEL])?;
+            push(&handle.0.to_le_bytes())?;
+        }
+        Frame::Claim(handle, token) => {
+            push(&[CLAIM])?;
+            push(&handle.0.to_le_bytes())?;
+            push(&token.to_le_bytes())?;
+        }
+        Frame::GoAway => push(&[GOAWAY])?,
+        Frame::Ping => push(&[PING])?,
+        Frame::Pong => push(&[PONG])?,
+        Frame::Resume(session, received) => {
+            push(&[RESUME])?;
+            push(&session.to_le_bytes())?;
+            push(&received.to_le_bytes())?;
+        }
+        Frame::Ack(received) => {
+            push(&[ACK])?;
+            push(&received.to_le_bytes())?;
+        }
+        Frame::Challenge(id, nonce) => {
+            push(&[CHALLENGE])?;
+            push(&id.to_le_bytes())?;
+            push(nonce)?;
+        }
+        Frame::Response(mac) => {
+            push(&[RESPONSE])?;
+            push(mac)?;
+        }
+    }
+    Packet::new(&buffer[..length])
+}
+
+fn decode<const N: usize>(packet: &[u8]) -> Option<Frame<Packet<N>>> {
+    let (tag, body) = packet.split_first()?;
+    let handle = || Some(Handle(u32::from_le_bytes(body.get(..4)?.try_into().ok()?)));
+    let word = |offset: usize| Some(u64::from_le_bytes(body.get(offset..offset + 8)?.try_into().ok()?));
+    let exact = |length: usize| if body.len() == length { Some(()) } else { None };
+    Some(match *tag {
+        ITEM => Frame::Item(handle()?, Packet::new(&body[4..])?),
+        WINDOW => {
+            exact(8)?;
+            Frame::Window(handle()?, u32::from_le_bytes(body[4..8].try_into().ok()?))
+        }
+        CLOSE => {
+            exact(4)?;
+            Frame::Close(handle()?)
+        }
+        CANCEL => {
+            exact(4)?;
+            Frame::Cancel(handle()?)
+        }
+        CLAIM => {
+            exact(12)?;
+            Frame::Claim(handle()?, word(4)?)
+        }
+        GOAWAY => {
+            exact(0)?;
+            Frame::GoAway
+        }
+        PING => {
+            exact(0)?;
+            Frame::Ping
+        }
+        PONG => {
+            exact(0)?;
+            Frame::Pong
+        }
+        RESUME => {
+            exact(16)?;
+            Frame::Resume(word(0)?, word(8)?)
+        }
+        ACK => {
+            exact(8)?;
+            Frame::Ack(word(0)?)
+        }
+        CHALLENGE => {
+            exact(24)?;
+            Frame::Challenge(word(0)?, body[8..].try_into().ok()?)
+        }
+        RESPONSE => {
+            exact(32)?;
+            Frame::Response(body.try_into().ok()?)
+        }
+        _ => return None,
+    })
+}
+
+pub struct Bridge<T, const N: usize> {
+    transport: T,
+}
+
+impl<T, const N: usize> Bridge<T, N> {
+    pub fn new(transport: T) -> Self {
+        Bridge { transport }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.transport
+    }
+}
+
+impl<T: Unpin + TryStream<Ok = Packet<N>>, const N: usize> Stream for Bridge<T, N> {
+    type Item = Result<Frame<Packet<N>>, FrameError<T::Error>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let packet = match ready!(Pin::new(&mut self.transport).try_poll_next(ctx)) {
+            Some(Ok(packet)) => packet,
+            Some(Err(e)) => return Poll::Ready(Some(Err(FrameError::Transport(e)))),
+            None => return Poll::Ready(None),
+        };
+        Poll::Ready(Some(decode(&packet).ok_or(FrameError::Malformed)))
+    }
+}
+
+impl<T: Unpin + Sink<Packet<N>>, const N: usize> Sink<Frame<Packet<N>>> for Bridge<T, N> {
+    type Error = FrameError<T::Error>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.transport)
+            .poll_ready(ctx)
+            .map_err(FrameError::Transport)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Frame<Packet<N>>) -> Result<(), Self::Error> {
+        let packet = encode(&item).ok_or(FrameError::Oversized)?;
+        Pin::new(&mut self.transport)
+            .start_send(packet)
+            .map_err(FrameError::Transport)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.transport)
+            .poll_flush(ctx)
+            .map_err(FrameError::Transport)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.transport)
+            .poll_close(ctx)
+            .map_err(FrameError::Transport)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, Packet};
+    use crate::director::{Frame, Handle};
+
+    #[test]
+    fn round_trip() {
+        let frames = [
+            Frame::Item(Handle(7), Packet::new(b"payload").unwrap()),
+            Frame::Window(Handle(1), 64),
+            Frame::Close(Handle(2)),
+            Frame::Cancel(Handle(3)),
+            Frame::Claim(Handle(4), 99),
+            Frame::GoAway,
+            Frame::Ping,
+            Frame::Pong,
+            Frame::Resume(5, 6),
+            Frame::Ack(8),
+            Frame::Challenge(9, [3; 16]),
+            Frame::Response([4; 32]),
+        ];
+        for frame in frames.iter() {
+            let packet = encode::<64>(frame).unwrap();
+            let decoded = decode::<64>(&packet).unwrap();
+            assert_eq!(encode::<64>(&decoded).unwrap(), packet);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert!(decode::<64>(&[]).is_none());
+        assert!(decode::<64>(&[0xFF]).is_none());
+        assert!(decode::<64>(&[2, 1, 0]).is_none());
+        assert!(decode::<64>(&[6, 0]).is_none());
+        assert!(encode::<8>(&Frame::Item(Handle(0), Packet::new(&[0; 8]).unwrap())).is_none());
+    }
+}
@@ -0,0 +1,59 @@
+use super::Codec;
+
+#[derive(Clone, Copy)]
+pub struct Cobs;
+
+impl Codec for Cobs {
+    const DELIMITER: u8 = 0;
+
+    fn encode(&self, input: &[u8], output: &mut [u8]) -> Option<usize> {
+        let mut code_index = 0;
+        let mut code = 1u8;
+        let mut written = 1;
+        for byte in input {
+            if *byte == 0 {
+                *output.get_mut(code_index)? = code;
+                code_index = written;
+                written += 1;
+                code = 1;
+            } else {
+                *output.get_mut(written)? = *byte;
+                written += 1;
+                code += 1;
+                if code == 0xFF {
+                    *output.get_mut(code_index)? = code;
+                    code_index = written;
+                    written += 1;
+                    code = 1;
+                }
+            }
+        }
+        *output.get_mut(code_index)? = code;
+        *output.get_mut(written)? = Self::DELIMITER;
+        Some(written + 1)
+    }
+
+    fn decode(&self, frame: &mut [u8]) -> Option<usize> {
+        let mut read = 0;
+        let mut written = 0;
+        while read < frame.len() {
+            let code = frame[read] as usize;
+            if code == 0 {
+                return None;
+            }
+            read += 1;
+            let end = read + code - 1;
+            if end > frame.len() {
+                return None;
+            }
+            frame.copy_within(read..end, written);
+            written += end - read;
+            read = end;
+            if code != 0xFF && read < frame.len() {
+                frame[written] = 0;
+                written += 1;
+            }
+        }
+        Some(written)
+    }
+}
@@ -0,0 +1,304 @@
+use core::{
+    fmt::{self, Debug, Formatter},
+    ops::Deref,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::{ready, Sink, Stream};
+
+mod bridge;
+mod cobs;
+mod slip;
+pub use bridge::{Bridge, FrameError};
+pub use cobs::Cobs;
+pub use slip::Slip;
+
+pub trait Codec {
+    const DELIMITER: u8;
+
+    fn encode(&self, input: &[u8], output: &mut [u8]) -> Option<usize>;
+
+    fn decode(&self, frame: &mut [u8]) -> Option<usize>;
+}
+
+#[derive(Clone, Copy)]
+pub struct Packet<const N: usize> {
+    data: [u8; N],
+    length: usize,
+}
+
+impl<const N: usize> Packet<N> {
+    pub fn new(data: &[u8]) -> Option<Self> {
+        if data.len() > N {
+            return None;
+        }
+        let mut packet = Packet {
+            data: [0; N],
+            length: data.len(),
+        };
+        packet.data[..data.len()].copy_from_slice(data);
+        Some(packet)
+    }
+}
+
+impl<const N: usize> Deref for Packet<N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data[..self.length]
+    }
+}
+
+impl<const N: usize> Debug for Packet<N> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_tuple("Packet").field(&&**self).finish()
+    }
+}
+
+impl<const N: usize> PartialEq for Packet<N> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<const N: usize> Eq for Packet<N> {}
+
+pub enum Error<S: AsyncRead + AsyncWrite> {
+    Read(S::Error),
+    Write(S::WriteError),
+    Flush(S::FlushError),
+    Close(S::CloseError),
+    Overflow,
+    Corrupt,
+    Terminated,
+}
+
+impl<S: AsyncRead + AsyncWrite> Debug for Error<S>
+where
+    S::Error: Debug,
+    S::WriteError: Debug,
+    S::FlushError: Debug,
+    S::CloseError: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Read(e) => f.debug_tuple("Read").field(e).finish(),
+            Error::Write(e) => f.debug_tuple("Write").field(e).finish(),
+            Error::Flush(e) => f.debug_tuple("Flush").field(e).finish(),
+            Error::Close(e) => f.debug_tuple("Close").field(e).finish(),
+            Error::Overflow => f.write_str("Overflow"),
+            Error::Corrupt => f.write_str("Corrupt"),
+            Error::Terminated => f.write_str("Terminated"),
+        }
+    }
+}
+
+pub struct Framed<S, C, B, const N: usize> {
+    stream: S,
+    codec: C,
+    read: B,
+    filled: usize,
+    scanned: usize,
+    discard: bool,
+    write: B,
+    written: usize,
+    pending: usize,
+}
+
+impl<S, C: Codec, B: AsMut<[u8]>, const N: usize> Framed<S, C, B, N> {
+    pub fn new(stream: S, codec: C, read: B, write: B) -> Self {
+        Framed {
+            stream,
+            codec,
+            read,
+            filled: 0,
+            scanned: 0,
+            discard: false,
+            write,
+            written: 0,
+            pending: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    fn next_frame(&mut self) -> Option<Result<Packet<N>, Error<S>>>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        loop {
+            let read = self.read.as_mut();
+            let end = match read[self.scanned..self.filled]
+                .iter()
+                .position(|byte| *byte == C::DELIMITER)
+            {
+                Some(position) => self.scanned + position,
+                None => {
+                    self.scanned = self.filled;
+                    return None;
+                }
+            };
+            let frame = if self.discard || end == 0 {
+                None
+            } else {
+                Some(match self.codec.decode(&mut read[..end]) {
+                    Some(length) => Packet::new(&read[..length]).ok_or(Error::Overflow),
+                    None => Err(Error::Corrupt),
+                })
+            };
+            read.copy_within(end + 1..self.filled, 0);
+            self.filled -= end + 1;
+            self.scanned = 0;
+            self.discard = false;
+            if frame.is_some() {
+                return frame;
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin, C: Codec + Unpin, B: AsMut<[u8]> + Unpin, const N: usize>
+    Framed<S, C, B, N>
+{
+    fn poll_pending(&mut self, ctx: &mut Context) -> Poll<Result<(), Error<S>>> {
+        while self.written < self.pending {
+            let buf = &self.write.as_mut()[self.written..self.pending];
+            let count = ready!(Pin::new(&mut self.stream).poll_write(ctx, buf)).map_err(Error::Write)?;
+            if count == 0 {
+                return Poll::Ready(Err(Error::Terminated));
+            }
+            self.written += count;
+        }
+        self.written = 0;
+        self.pending = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin, C: Codec + Unpin, B: AsMut<[u8]> + Unpin, const N: usize>
+    Stream for Framed<S, C, B, N>
+{
+    type Item = Result<Packet<N>, Error<S>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if let Some(frame) = this.next_frame() {
+                return Poll::Ready(Some(frame));
+            }
+            if this.filled == this.read.as_mut().len() {
+                this.filled = 0;
+                this.scanned = 0;
+                if !this.discard {
+                    this.discard = true;
+                    return Poll::Ready(Some(Err(Error::Overflow)));
+                }
+            }
+            let buf = &mut this.read.as_mut()[this.filled..];
+            let count = match ready!(Pin::new(&mut this.stream).poll_read(ctx, buf)) {
+                Ok(count) => count,
+                Err(e) => return Poll::Ready(Some(Err(Error::Read(e)))),
+            };
+            if count == 0 {
+                return Poll::Ready(None);
+            }
+            this.filled += count;
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin, C: Codec + Unpin, B: AsMut<[u8]> + Unpin, const N: usize>
+    Sink<Packet<N>> for Framed<S, C, B, N>
+{
+    type Error = Error<S>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_pending(ctx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Packet<N>) -> Result<(), Self::Error> {
+        let this = &mut *self;
+        let length = this.codec.encode(&item, this.write.as_mut()).ok_or(Error::Overflow)?;
+        this.written = 0;
+        this.pending = length;
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_pending(ctx))?;
+        Pin::new(&mut self.stream)
+            .poll_flush(ctx)
+            .map_err(Error::Flush)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_pending(ctx))?;
+        ready!(Pin::new(&mut self.stream).poll_flush(ctx)).map_err(Error::Flush)?;
+        Pin::new(&mut self.stream)
+            .poll_close(ctx)
+            .map_err(Error::Close)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::{Cobs, Codec, Error, Framed, Packet, Slip};
+    use crate::transport::pipe::pipe;
+    use futures::{executor::block_on, SinkExt, StreamExt};
+
+    #[test]
+    fn cobs_round_trip() {
+        let (left, right) = pipe();
+        let mut sender = Framed::<_, _, _, 32>::new(left, Cobs, [0; 64], [0; 64]);
+        let mut receiver = Framed::<_, _, _, 32>::new(right, Cobs, [0; 64], [0; 64]);
+        block_on(async {
+            for payload in [&b"\x00a\x00\x00b"[..], b"", &[0xFF; 32]].iter() {
+                sender.send(Packet::new(payload).unwrap()).await.unwrap();
+                let packet = receiver.next().await.unwrap().unwrap();
+                assert_eq!(&*packet, *payload);
+            }
+        });
+    }
+
+    #[test]
+    fn slip_round_trip() {
+        let (left, right) = pipe();
+        let mut sender = Framed::<_, _, _, 32>::new(left, Slip, [0; 64], [0; 64]);
+        let mut receiver = Framed::<_, _, _, 32>::new(right, Slip, [0; 64], [0; 64]);
+        block_on(async {
+            for payload in [&[0xC0, 0xDB, 0x01][..], b"plain"].iter() {
+                sender.send(Packet::new(payload).unwrap()).await.unwrap();
+                let packet = receiver.next().await.unwrap().unwrap();
+                assert_eq!(&*packet, *payload);
+            }
+        });
+    }
+
+    #[test]
+    fn corrupt_frames() {
+        let mut frame = [0x03, 0x01, 0x00];
+        assert!(Cobs.decode(&mut frame[..2]).is_none());
+        let mut frame = [0x05, 0x01];
+        assert!(Cobs.decode(&mut frame).is_none());
+        let mut frame = [0xDB, 0x01];
+        assert!(Slip.decode(&mut frame).is_none());
+        let (left, right) = pipe();
+        let mut sender = Framed::<_, _, _, 32>::new(left, Cobs, [0; 64], [0; 64]);
+        let mut receiver = Framed::<_, _, _, 32>::new(right, Cobs, [0; 64], [0; 64]);
+        block_on(async {
+            sender.send(Packet::new(b"abc").unwrap()).await.unwrap();
+            sender.get_ref().flip(0);
+            match receiver.next().await.unwrap() {
+                Err(Error::Corrupt) => {}
+                other => panic!("unexpected {:?}", other.map(|packet| packet.len())),
+            }
+        });
+    }
+}
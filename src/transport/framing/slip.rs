@@ -0,0 +1,62 @@
+use super::Codec;
+
+const END: u8 = 0xC0;
+const ESC: u8 = 0xDB;
+const ESC_END: u8 = 0xDC;
+const ESC_ESC: u8 = 0xDD;
+
+/// SLIP cannot represent empty frames: an empty payload is indistinguishable from the
+/// delimiter that opens every frame, so the decoder skips it.
+#[derive(Clone, Copy)]
+pub struct Slip;
+
+impl Codec for Slip {
+    const DELIMITER: u8 = END;
+
+    fn encode(&self, input: &[u8], output: &mut [u8]) -> Option<usize> {
+        let mut written = 0;
+        let mut push = |byte: u8| {
+            *output.get_mut(written)? = byte;
+            written += 1;
+            Some(())
+        };
+        push(END)?;
+        for byte in input {
+            match *byte {
+                END => {
+                    push(ESC)?;
+                    push(ESC_END)?;
+                }
+                ESC => {
+                    push(ESC)?;
+                    push(ESC_ESC)?;
+                }
+                byte => push(byte)?,
+            }
+        }
+        push(END)?;
+        Some(written)
+    }
+
+    fn decode(&self, frame: &mut [u8]) -> Option<usize> {
+        let mut read = 0;
+        let mut written = 0;
+        while read < frame.len() {
+            let byte = match frame[read] {
+                ESC => {
+                    read += 1;
+                    match frame.get(read)? {
+                        &ESC_END => END,
+                        &ESC_ESC => ESC,
+                        _ => return None,
+                    }
+                }
+                byte => byte,
+            };
+            frame[written] = byte;
+            written += 1;
+            read += 1;
+        }
+        Some(written)
+    }
+}
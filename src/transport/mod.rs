@@ -0,0 +1,30 @@
+#[cfg(all(unix, feature = "std"))]
+use std::os::unix::io::OwnedFd;
+
+#[cfg(feature = "async-std")]
+pub mod async_std;
+pub mod framing;
+pub use framing::Framed;
+#[cfg(feature = "noise")]
+pub mod noise;
+#[cfg(feature = "noise")]
+pub use noise::{Encrypted, Noise};
+#[cfg(all(test, feature = "alloc"))]
+pub(crate) mod pipe;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(all(unix, feature = "tokio"))]
+mod unix;
+#[cfg(all(unix, feature = "tokio"))]
+pub use unix::Unix;
+#[cfg(feature = "alloc")]
+pub mod websocket;
+#[cfg(feature = "alloc")]
+pub use websocket::WebSocket;
+
+#[cfg(all(unix, feature = "std"))]
+pub trait Descriptors {
+    fn attach(&mut self, descriptor: OwnedFd) -> u32;
+
+    fn detach(&mut self, index: u32) -> Option<OwnedFd>;
+}
@@ -0,0 +1,213 @@
+use super::{
+    state::{Symmetric, KEY, TAG},
+    Encrypted, Error, Noise,
+};
+use alloc::vec::Vec;
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::ready;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+const MESSAGES: [usize; 3] = [KEY, KEY + KEY + TAG + TAG, KEY + TAG + TAG];
+
+fn public(secret: &StaticSecret) -> [u8; KEY] {
+    PublicKey::from(secret).to_bytes()
+}
+
+fn key(bytes: &[u8]) -> [u8; KEY] {
+    let mut key = [0; KEY];
+    key.copy_from_slice(&bytes[..KEY]);
+    key
+}
+
+pub struct Handshake<S> {
+    stream: Option<S>,
+    symmetric: Option<Symmetric>,
+    initiator: bool,
+    local: StaticSecret,
+    ephemeral: StaticSecret,
+    expected: Option<[u8; KEY]>,
+    remote_ephemeral: [u8; KEY],
+    remote: [u8; KEY],
+    step: usize,
+    buffer: Vec<u8>,
+    offset: usize,
+}
+
+impl<S> Unpin for Handshake<S> {}
+
+impl<S> Handshake<S> {
+    pub(super) fn new(noise: Noise, stream: S, initiator: bool) -> Self {
+        Handshake {
+            stream: Some(stream),
+            symmetric: Some(Symmetric::new(&noise.prologue)),
+            initiator,
+            local: StaticSecret::from(noise.secret),
+            ephemeral: StaticSecret::from((noise.entropy)()),
+            expected: noise.remote,
+            remote_ephemeral: [0; KEY],
+            remote: [0; KEY],
+            step: 0,
+            buffer: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    fn symmetric(&mut self) -> &mut Symmetric {
+        self.symmetric
+            .as_mut()
+            .expect("Handshake polled after completion")
+    }
+
+    fn compose(&mut self) -> Option<Vec<u8>> {
+        let length = MESSAGES[self.step];
+        let mut message = Vec::with_capacity(2 + length);
+        message.extend_from_slice(&(length as u16).to_be_bytes());
+        match self.step {
+            0 => {
+                let ephemeral = public(&self.ephemeral);
+                message.extend_from_slice(&ephemeral);
+                self.symmetric().mix_hash(&ephemeral);
+                self.symmetric().mix_hash(&[]);
+            }
+            1 => {
+                let ephemeral = public(&self.ephemeral);
+                message.extend_from_slice(&ephemeral);
+                let (secret, remote) = (self.ephemeral.clone(), self.remote_ephemeral);
+                let symmetric = self.symmetric();
+                symmetric.mix_hash(&ephemeral);
+                symmetric.mix_dh(&secret, &remote)?;
+                self.seal_static(&mut message)?;
+                let (secret, remote) = (self.local.clone(), self.remote_ephemeral);
+                self.symmetric().mix_dh(&secret, &remote)?;
+                self.seal_payload(&mut message)?;
+            }
+            _ => {
+                self.seal_static(&mut message)?;
+                let (secret, remote) = (self.local.clone(), self.remote_ephemeral);
+                self.symmetric().mix_dh(&secret, &remote)?;
+                self.seal_payload(&mut message)?;
+            }
+        }
+        Some(message)
+    }
+
+    fn seal_static(&mut self, message: &mut Vec<u8>) -> Option<()> {
+        let start = message.len();
+        message.extend_from_slice(&public(&self.local));
+        message.resize(start + KEY + TAG, 0);
+        self.symmetric().encrypt_and_hash(&mut message[start..])
+    }
+
+    fn seal_payload(&mut self, message: &mut Vec<u8>) -> Option<()> {
+        let start = message.len();
+        message.resize(start + TAG, 0);
+        self.symmetric().encrypt_and_hash(&mut message[start..])
+    }
+
+    fn open(&mut self, message: &mut [u8]) -> Option<()> {
+        let secret = self.ephemeral.clone();
+        let (remote, payload) = match self.step {
+            0 => {
+                self.remote_ephemeral = key(message);
+                let remote = self.remote_ephemeral;
+                self.symmetric().mix_hash(&remote);
+                self.symmetric().mix_hash(&[]);
+                return Some(());
+            }
+            1 => {
+                let (ephemeral, rest) = message.split_at_mut(KEY);
+                self.remote_ephemeral = key(ephemeral);
+                let remote = self.remote_ephemeral;
+                let symmetric = self.symmetric();
+                symmetric.mix_hash(&remote);
+                symmetric.mix_dh(&secret, &remote)?;
+                rest.split_at_mut(KEY + TAG)
+            }
+            _ => message.split_at_mut(KEY + TAG),
+        };
+        self.symmetric().decrypt_and_hash(remote)?;
+        self.remote = key(remote);
+        let remote = self.remote;
+        let symmetric = self.symmetric();
+        symmetric.mix_dh(&secret, &remote)?;
+        symmetric.decrypt_and_hash(payload)
+    }
+
+    fn receive(&mut self) -> Result<(), Error<S>>
+    where
+        S: AsyncRead + AsyncWrite,
+    {
+        let mut message = core::mem::take(&mut self.buffer);
+        self.open(&mut message[2..]).ok_or(Error::Handshake)?;
+        match self.expected {
+            Some(expected) if self.step > 0 && expected != self.remote => Err(Error::Rejected),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Future for Handshake<S> {
+    type Output = Result<Encrypted<S>, Error<S>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            let stream = this
+                .stream
+                .as_mut()
+                .expect("Handshake polled after completion");
+            if this.step == MESSAGES.len() {
+                ready!(Pin::new(stream).poll_flush(ctx)).map_err(Error::Flush)?;
+                let symmetric = this.symmetric.take().unwrap();
+                let (send, receive) = symmetric.split(this.initiator);
+                let stream = this.stream.take().unwrap();
+                return Poll::Ready(Ok(Encrypted::new(stream, send, receive, this.remote)));
+            }
+            if (this.step % 2 == 0) == this.initiator {
+                if this.buffer.is_empty() {
+                    this.buffer = this.compose().ok_or(Error::Handshake)?;
+                    this.offset = 0;
+                }
+                let stream = this.stream.as_mut().unwrap();
+                while this.offset < this.buffer.len() {
+                    let buf = &this.buffer[this.offset..];
+                    let count = ready!(Pin::new(&mut *stream).poll_write(ctx, buf))
+                        .map_err(Error::Write)?;
+                    if count == 0 {
+                        return Poll::Ready(Err(Error::Terminated));
+                    }
+                    this.offset += count;
+                }
+                this.buffer.clear();
+            } else {
+                let target = if this.offset < 2 {
+                    2
+                } else {
+                    let length = u16::from_be_bytes([this.buffer[0], this.buffer[1]]) as usize;
+                    if length != MESSAGES[this.step] {
+                        return Poll::Ready(Err(Error::Handshake));
+                    }
+                    2 + length
+                };
+                if this.offset < target {
+                    this.buffer.resize(target, 0);
+                    let buf = &mut this.buffer[this.offset..];
+                    let count = ready!(Pin::new(stream).poll_read(ctx, buf)).map_err(Error::Read)?;
+                    if count == 0 {
+                        return Poll::Ready(Err(Error::Terminated));
+                    }
+                    this.offset += count;
+                    continue;
+                }
+                this.receive()?;
+            }
+            this.offset = 0;
+            this.step += 1;
+        }
+    }
+}
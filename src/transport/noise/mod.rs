@@ -0,0 +1,340 @@
+use alloc::vec::Vec;
+use core::{
+    fmt::{self, Debug, Formatter},
+    mem::swap,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::ready;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+mod handshake;
+mod state;
+pub use handshake::Handshake;
+use state::{Cipher, TAG};
+
+const RECORD: usize = u16::MAX as usize;
+const CHUNK: usize = 4096;
+
+pub enum Error<S: AsyncRead + AsyncWrite> {
+    Read(S::Error),
+    Write(S::WriteError),
+    Flush(S::FlushError),
+    Close(S::CloseError),
+    Handshake,
+    Rejected,
+    Decrypt,
+    Exhausted,
+    Terminated,
+}
+
+impl<S: AsyncRead + AsyncWrite> Debug for Error<S>
+where
+    S::Error: Debug,
+    S::WriteError: Debug,
+    S::FlushError: Debug,
+    S::CloseError: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Read(e) => f.debug_tuple("Read").field(e).finish(),
+            Error::Write(e) => f.debug_tuple("Write").field(e).finish(),
+            Error::Flush(e) => f.debug_tuple("Flush").field(e).finish(),
+            Error::Close(e) => f.debug_tuple("Close").field(e).finish(),
+            Error::Handshake => f.write_str("Handshake"),
+            Error::Rejected => f.write_str("Rejected"),
+            Error::Decrypt => f.write_str("Decrypt"),
+            Error::Exhausted => f.write_str("Exhausted"),
+            Error::Terminated => f.write_str("Terminated"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Noise {
+    secret: [u8; 32],
+    entropy: fn() -> [u8; 32],
+    prologue: Vec<u8>,
+    remote: Option<[u8; 32]>,
+}
+
+impl Noise {
+    pub fn new(secret: [u8; 32], entropy: fn() -> [u8; 32]) -> Self {
+        Noise {
+            secret,
+            entropy,
+            prologue: Vec::new(),
+            remote: None,
+        }
+    }
+
+    pub fn with_prologue(mut self, prologue: &[u8]) -> Self {
+        self.prologue = prologue.into();
+        self
+    }
+
+    pub fn with_remote(mut self, remote: [u8; 32]) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        PublicKey::from(&StaticSecret::from(self.secret)).to_bytes()
+    }
+
+    pub fn initiate<S>(self, stream: S) -> Handshake<S> {
+        Handshake::new(self, stream, true)
+    }
+
+    pub fn respond<S>(self, stream: S) -> Handshake<S> {
+        Handshake::new(self, stream, false)
+    }
+}
+
+pub struct Encrypted<S> {
+    stream: S,
+    send: Cipher,
+    receive: Cipher,
+    remote: [u8; 32],
+    read: Vec<u8>,
+    plain: Vec<u8>,
+    scratch: Vec<u8>,
+    consumed: usize,
+    write: Vec<u8>,
+    failed: bool,
+}
+
+impl<S> Encrypted<S> {
+    fn new(stream: S, send: Cipher, receive: Cipher, remote: [u8; 32]) -> Self {
+        Encrypted {
+            stream,
+            send,
+            receive,
+            remote,
+            read: Vec::new(),
+            plain: Vec::new(),
+            scratch: Vec::new(),
+            consumed: 0,
+            write: Vec::new(),
+            failed: false,
+        }
+    }
+
+    pub fn remote_key(&self) -> [u8; 32] {
+        self.remote
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Encrypted<S> {
+    fn poll_write_buffer(&mut self, ctx: &mut Context) -> Poll<Result<(), Error<S>>> {
+        while !self.write.is_empty() {
+            let count = ready!(Pin::new(&mut self.stream).poll_write(ctx, &self.write))
+                .map_err(Error::Write)?;
+            if count == 0 {
+                return Poll::Ready(Err(Error::Terminated));
+            }
+            self.write.drain(..count);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn next_record(&mut self) -> Result<bool, Error<S>> {
+        if self.failed {
+            return Err(Error::Decrypt);
+        }
+        if self.read.len() < 2 {
+            return Ok(false);
+        }
+        let length = u16::from_be_bytes([self.read[0], self.read[1]]) as usize;
+        if length < TAG {
+            self.failed = true;
+            return Err(Error::Decrypt);
+        }
+        if self.read.len() < 2 + length {
+            return Ok(false);
+        }
+        self.scratch.clear();
+        self.scratch.extend(self.read.drain(..2 + length).skip(2));
+        if self.receive.decrypt(&[], &mut self.scratch).is_none() {
+            self.failed = true;
+            return Err(Error::Decrypt);
+        }
+        self.scratch.truncate(length - TAG);
+        swap(&mut self.plain, &mut self.scratch);
+        self.consumed = 0;
+        Ok(true)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for Encrypted<S> {
+    type Error = Error<S>;
+
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Error<S>>> {
+        let this = &mut *self;
+        let mut chunk = [0; CHUNK];
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            if this.consumed < this.plain.len() {
+                let count = buf.len().min(this.plain.len() - this.consumed);
+                buf[..count].copy_from_slice(&this.plain[this.consumed..this.consumed + count]);
+                this.consumed += count;
+                return Poll::Ready(Ok(count));
+            }
+            if this.next_record()? {
+                continue;
+            }
+            let count =
+                ready!(Pin::new(&mut this.stream).poll_read(ctx, &mut chunk)).map_err(Error::Read)?;
+            if count == 0 {
+                return Poll::Ready(if this.read.is_empty() {
+                    Ok(0)
+                } else {
+                    Err(Error::Terminated)
+                });
+            }
+            this.read.extend_from_slice(&chunk[..count]);
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for Encrypted<S> {
+    type WriteError = Error<S>;
+    type FlushError = Error<S>;
+    type CloseError = Error<S>;
+
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error<S>>> {
+        let this = &mut *self;
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        if this.write.len() >= CHUNK {
+            ready!(this.poll_write_buffer(ctx))?;
+        }
+        let count = buf.len().min(RECORD - TAG);
+        let start = this.write.len();
+        this.write
+            .extend_from_slice(&((count + TAG) as u16).to_be_bytes());
+        this.write.extend_from_slice(&buf[..count]);
+        this.write.resize(start + 2 + count + TAG, 0);
+        if this.send.encrypt(&[], &mut this.write[start + 2..]).is_none() {
+            this.write.truncate(start);
+            return Poll::Ready(Err(Error::Exhausted));
+        }
+        Poll::Ready(Ok(count))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Error<S>>> {
+        ready!(self.poll_write_buffer(ctx))?;
+        Pin::new(&mut self.stream)
+            .poll_flush(ctx)
+            .map_err(Error::Flush)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Error<S>>> {
+        ready!(self.poll_write_buffer(ctx))?;
+        ready!(Pin::new(&mut self.stream).poll_flush(ctx)).map_err(Error::Flush)?;
+        Pin::new(&mut self.stream)
+            .poll_close(ctx)
+            .map_err(Error::Close)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Encrypted, Error, Noise};
+    use crate::transport::pipe::{pipe, read, write, Pipe};
+    use core::{
+        pin::Pin,
+        sync::atomic::{AtomicU8, Ordering},
+    };
+    use core_futures_io::AsyncRead;
+    use futures::{
+        executor::block_on,
+        future::{join, poll_fn},
+        FutureExt,
+    };
+
+    fn entropy() -> [u8; 32] {
+        static COUNTER: AtomicU8 = AtomicU8::new(0);
+        [COUNTER.fetch_add(1, Ordering::Relaxed); 32]
+    }
+
+    fn connect(
+        initiator: Noise,
+        responder: Noise,
+    ) -> (
+        Result<Encrypted<Pipe>, Error<Pipe>>,
+        Result<Encrypted<Pipe>, Error<Pipe>>,
+    ) {
+        let (left, right) = pipe();
+        block_on(join(initiator.initiate(left), responder.respond(right)))
+    }
+
+    #[test]
+    fn round_trip() {
+        let (client, server) = (Noise::new([1; 32], entropy), Noise::new([2; 32], entropy));
+        let (client_key, server_key) = (client.public_key(), server.public_key());
+        let (client, server) = connect(
+            client.with_prologue(b"test"),
+            server.with_prologue(b"test"),
+        );
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+        assert_eq!(client.remote_key(), server_key);
+        assert_eq!(server.remote_key(), client_key);
+        write(&mut client, b"hello");
+        assert_eq!(read(&mut server, 5).unwrap(), b"hello");
+        write(&mut server, b"world");
+        assert_eq!(read(&mut client, 5).unwrap(), b"world");
+    }
+
+    #[test]
+    fn rejects_tampered_record() {
+        let (client, server) = connect(Noise::new([1; 32], entropy), Noise::new([2; 32], entropy));
+        let (mut client, mut server) = (client.unwrap(), server.unwrap());
+        write(&mut client, b"hello");
+        client.get_ref().flip(4);
+        assert!(matches!(read(&mut server, 5), Err(Error::Decrypt)));
+        write(&mut client, b"world");
+        let mut data = [0; 5];
+        let poll = poll_fn(|ctx| Pin::new(&mut server).poll_read(ctx, &mut data)).now_or_never();
+        assert!(matches!(poll, Some(Err(Error::Decrypt))));
+        assert_eq!(data, [0; 5]);
+    }
+
+    #[test]
+    fn rejects_mismatched_prologue() {
+        let (client, _) = connect(
+            Noise::new([1; 32], entropy).with_prologue(b"one"),
+            Noise::new([2; 32], entropy).with_prologue(b"two"),
+        );
+        assert!(matches!(client, Err(Error::Handshake)));
+    }
+
+    #[test]
+    fn rejects_unexpected_remote() {
+        let (_, server) = connect(
+            Noise::new([1; 32], entropy),
+            Noise::new([2; 32], entropy).with_remote([9; 32]),
+        );
+        assert!(matches!(server, Err(Error::Rejected)));
+    }
+}
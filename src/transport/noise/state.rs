@@ -0,0 +1,137 @@
+use chacha20poly1305::{
+    aead::{AeadInPlace, KeyInit},
+    ChaCha20Poly1305, Key, Nonce, Tag,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+pub const PROTOCOL: &[u8; 32] = b"Noise_XX_25519_ChaChaPoly_SHA256";
+pub const KEY: usize = 32;
+pub const TAG: usize = 16;
+
+fn hmac(key: &[u8; 32], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key)
+        .expect("violated invariant in Noise: rejected fixed-size key");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+fn hkdf(chaining: &[u8; 32], material: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let temp = hmac(chaining, &[material]);
+    let first = hmac(&temp, &[&[1]]);
+    let second = hmac(&temp, &[&first, &[2]]);
+    (first, second)
+}
+
+pub struct Cipher {
+    cipher: ChaCha20Poly1305,
+    nonce: u64,
+}
+
+impl Cipher {
+    fn new(key: &[u8; 32]) -> Self {
+        Cipher {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+            nonce: 0,
+        }
+    }
+
+    fn next_nonce(&mut self) -> Option<Nonce> {
+        if self.nonce == u64::MAX {
+            return None;
+        }
+        let mut nonce = [0; 12];
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        self.nonce += 1;
+        Some(*Nonce::from_slice(&nonce))
+    }
+
+    pub fn encrypt(&mut self, associated: &[u8], buffer: &mut [u8]) -> Option<()> {
+        let (plaintext, tag) = buffer.split_at_mut(buffer.len().checked_sub(TAG)?);
+        let nonce = self.next_nonce()?;
+        let computed = self
+            .cipher
+            .encrypt_in_place_detached(&nonce, associated, plaintext)
+            .ok()?;
+        tag.copy_from_slice(&computed);
+        Some(())
+    }
+
+    pub fn decrypt(&mut self, associated: &[u8], buffer: &mut [u8]) -> Option<()> {
+        let (ciphertext, tag) = buffer.split_at_mut(buffer.len().checked_sub(TAG)?);
+        let nonce = self.next_nonce()?;
+        self.cipher
+            .decrypt_in_place_detached(&nonce, associated, ciphertext, Tag::from_slice(tag))
+            .ok()
+    }
+}
+
+pub struct Symmetric {
+    chaining: [u8; 32],
+    hash: [u8; 32],
+    cipher: Option<Cipher>,
+}
+
+impl Symmetric {
+    pub fn new(prologue: &[u8]) -> Self {
+        let mut state = Symmetric {
+            chaining: *PROTOCOL,
+            hash: *PROTOCOL,
+            cipher: None,
+        };
+        state.mix_hash(prologue);
+        state
+    }
+
+    pub fn mix_hash(&mut self, data: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.update(self.hash);
+        hasher.update(data);
+        self.hash = hasher.finalize().into();
+    }
+
+    pub fn mix_key(&mut self, material: &[u8]) {
+        let (chaining, key) = hkdf(&self.chaining, material);
+        self.chaining = chaining;
+        self.cipher = Some(Cipher::new(&key));
+    }
+
+    pub fn mix_dh(&mut self, secret: &StaticSecret, public: &[u8; KEY]) -> Option<()> {
+        let shared = secret.diffie_hellman(&PublicKey::from(*public));
+        if !shared.was_contributory() {
+            return None;
+        }
+        self.mix_key(shared.as_bytes());
+        Some(())
+    }
+
+    pub fn encrypt_and_hash(&mut self, buffer: &mut [u8]) -> Option<()> {
+        let hash = self.hash;
+        self.cipher.as_mut()?.encrypt(&hash, buffer)?;
+        self.mix_hash(buffer);
+        Some(())
+    }
+
+    pub fn decrypt_and_hash(&mut self, buffer: &mut [u8]) -> Option<()> {
+        let hash = self.hash;
+        let mut ciphertext = [0; KEY + TAG];
+        let ciphertext = ciphertext.get_mut(..buffer.len())?;
+        ciphertext.copy_from_slice(buffer);
+        self.cipher.as_mut()?.decrypt(&hash, buffer)?;
+        self.mix_hash(ciphertext);
+        Some(())
+    }
+
+    pub fn split(self, initiator: bool) -> (Cipher, Cipher) {
+        let (first, second) = hkdf(&self.chaining, &[]);
+        let (first, second) = (Cipher::new(&first), Cipher::new(&second));
+        if initiator {
+            (first, second)
+        } else {
+            (second, first)
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use alloc::{collections::VecDeque, rc::Rc, vec, vec::Vec};
+#[cfg(any(feature = "noise", feature = "compression"))]
+use core::fmt::Debug;
+use core::{
+    cell::RefCell,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::{executor::block_on, future::poll_fn, Sink, Stream};
+use void::Void;
+
+#[derive(Default)]
+struct Buffer {
+    data: VecDeque<u8>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+pub struct Pipe {
+    read: Rc<RefCell<Buffer>>,
+    write: Rc<RefCell<Buffer>>,
+}
+
+pub fn pipe() -> (Pipe, Pipe) {
+    let (left, right) = (Rc::default(), Rc::default());
+    (
+        Pipe {
+            read: left.clone(),
+            write: right.clone(),
+        },
+        Pipe {
+            read: right,
+            write: left,
+        },
+    )
+}
+
+impl Pipe {
+    pub fn pending(&self) -> usize {
+        self.write.borrow().data.len()
+    }
+
+    pub fn flip(&self, offset: usize) {
+        self.write.borrow_mut().data[offset] ^= 0x01;
+    }
+}
+
+#[cfg(any(feature = "noise", feature = "compression"))]
+pub fn write<S: AsyncWrite + Unpin>(stream: &mut S, data: &[u8])
+where
+    S::WriteError: Debug,
+    S::FlushError: Debug,
+{
+    let mut written = 0;
+    while written < data.len() {
+        written += block_on(poll_fn(|ctx| {
+            Pin::new(&mut *stream).poll_write(ctx, &data[written..])
+        }))
+        .unwrap();
+    }
+    block_on(poll_fn(|ctx| Pin::new(&mut *stream).poll_flush(ctx))).unwrap();
+}
+
+pub fn read<S: AsyncRead + Unpin>(stream: &mut S, length: usize) -> Result<Vec<u8>, S::Error> {
+    let mut data = vec![0; length];
+    let mut filled = 0;
+    while filled < length {
+        let count = block_on(poll_fn(|ctx| {
+            Pin::new(&mut *stream).poll_read(ctx, &mut data[filled..])
+        }))?;
+        if count == 0 {
+            data.truncate(filled);
+            break;
+        }
+        filled += count;
+    }
+    Ok(data)
+}
+
+impl AsyncRead for Pipe {
+    type Error = Void;
+
+    fn poll_read(
+        self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Void>> {
+        let mut read = self.read.borrow_mut();
+        if read.data.is_empty() {
+            if read.closed {
+                return Poll::Ready(Ok(0));
+            }
+            read.waker = Some(ctx.waker().clone());
+            return Poll::Pending;
+        }
+        let count = buf.len().min(read.data.len());
+        for (slot, byte) in buf.iter_mut().zip(read.data.drain(..count)) {
+            *slot = byte;
+        }
+        Poll::Ready(Ok(count))
+    }
+}
+
+impl AsyncWrite for Pipe {
+    type WriteError = Void;
+    type FlushError = Void;
+    type CloseError = Void;
+
+    fn poll_write(self: Pin<&mut Self>, _: &mut Context, buf: &[u8]) -> Poll<Result<usize, Void>> {
+        let mut write = self.write.borrow_mut();
+        write.data.extend(buf);
+        if let Some(waker) = write.waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Void>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Void>> {
+        let mut write = self.write.borrow_mut();
+        write.closed = true;
+        if let Some(waker) = write.waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut write = self.write.borrow_mut();
+        write.closed = true;
+        if let Some(waker) = write.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct Queue<T> {
+    items: VecDeque<T>,
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Queue {
+            items: VecDeque::new(),
+            closed: false,
+            waker: None,
+        }
+    }
+}
+
+pub struct Channel<T, U = T> {
+    read: Rc<RefCell<Queue<U>>>,
+    write: Rc<RefCell<Queue<T>>>,
+}
+
+pub fn channel<T>() -> (Channel<T>, Channel<T>) {
+    duplex()
+}
+
+pub fn duplex<T, U>() -> (Channel<T, U>, Channel<U, T>) {
+    let (left, right) = (Rc::<RefCell<Queue<U>>>::default(), Rc::default());
+    (
+        Channel {
+            read: left.clone(),
+            write: right.clone(),
+        },
+        Channel {
+            read: right,
+            write: left,
+        },
+    )
+}
+
+impl<T, U> Channel<T, U> {
+    pub fn alter(&self, alter: impl FnOnce(&mut T)) {
+        if let Some(item) = self.write.borrow_mut().items.back_mut() {
+            alter(item);
+        }
+    }
+}
+
+impl<T, U> Stream for Channel<T, U> {
+    type Item = Result<U, Void>;
+
+    fn poll_next(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut read = self.read.borrow_mut();
+        match read.items.pop_front() {
+            Some(item) => Poll::Ready(Some(Ok(item))),
+            None if read.closed => Poll::Ready(None),
+            None => {
+                read.waker = Some(ctx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T, U> Sink<T> for Channel<T, U> {
+    type Error = Void;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Void>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Void> {
+        let mut write = self.write.borrow_mut();
+        write.items.push_back(item);
+        if let Some(waker) = write.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Void>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Void>> {
+        let mut write = self.write.borrow_mut();
+        write.closed = true;
+        if let Some(waker) = write.waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T, U> Drop for Channel<T, U> {
+    fn drop(&mut self) {
+        let mut write = self.write.borrow_mut();
+        write.closed = true;
+        if let Some(waker) = write.waker.take() {
+            waker.wake();
+        }
+    }
+}
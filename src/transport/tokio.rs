@@ -0,0 +1,186 @@
+use crate::director::{Spawner, Timer};
+use ::tokio::{
+    io::{self, DuplexStream, ReadBuf, Stdin, Stdout},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    process::{ChildStdin, ChildStdout},
+    task::spawn_local,
+    time::{sleep, Sleep},
+};
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::ready;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use ::tokio::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use void::Void;
+
+pub struct Compat<T>(T);
+
+impl<T> Compat<T> {
+    pub fn new(stream: T) -> Self {
+        Compat(stream)
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.0
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: io::AsyncRead + Unpin> AsyncRead for Compat<T> {
+    type Error = io::Error;
+
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let mut buf = ReadBuf::new(buf);
+        ready!(Pin::new(&mut self.0).poll_read(ctx, &mut buf))?;
+        Poll::Ready(Ok(buf.filled().len()))
+    }
+}
+
+impl<T: io::AsyncWrite + Unpin> AsyncWrite for Compat<T> {
+    type WriteError = io::Error;
+    type FlushError = io::Error;
+    type CloseError = io::Error;
+
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        Pin::new(&mut self.0).poll_write(ctx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.0).poll_flush(ctx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.0).poll_shutdown(ctx)
+    }
+}
+
+pub struct Stdio<R = ChildStdout, W = ChildStdin> {
+    reader: Compat<R>,
+    writer: Compat<W>,
+}
+
+impl<R, W> Stdio<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Stdio {
+            reader: Compat(reader),
+            writer: Compat(writer),
+        }
+    }
+
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader.0, self.writer.0)
+    }
+}
+
+impl Stdio<Stdin, Stdout> {
+    pub fn current() -> Self {
+        Stdio::new(io::stdin(), io::stdout())
+    }
+}
+
+impl<R: io::AsyncRead + Unpin, W: Unpin> AsyncRead for Stdio<R, W> {
+    type Error = io::Error;
+
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        Pin::new(&mut self.reader).poll_read(ctx, buf)
+    }
+}
+
+impl<R: Unpin, W: io::AsyncWrite + Unpin> AsyncWrite for Stdio<R, W> {
+    type WriteError = io::Error;
+    type FlushError = io::Error;
+    type CloseError = io::Error;
+
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        Pin::new(&mut self.writer).poll_write(ctx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.writer).poll_flush(ctx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.writer).poll_close(ctx)
+    }
+}
+
+pub async fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Compat<TcpStream>> {
+    let stream = TcpStream::connect(address).await?;
+    stream.set_nodelay(true)?;
+    Ok(Compat(stream))
+}
+
+pub async fn accept(listener: &TcpListener) -> io::Result<(Compat<TcpStream>, SocketAddr)> {
+    let (stream, address) = listener.accept().await?;
+    stream.set_nodelay(true)?;
+    Ok((Compat(stream), address))
+}
+
+#[cfg(unix)]
+pub async fn connect_unix<P: AsRef<Path>>(path: P) -> io::Result<Compat<UnixStream>> {
+    UnixStream::connect(path).await.map(Compat)
+}
+
+#[cfg(unix)]
+pub async fn accept_unix(listener: &UnixListener) -> io::Result<Compat<UnixStream>> {
+    let (stream, _) = listener.accept().await?;
+    Ok(Compat(stream))
+}
+
+pub fn duplex(capacity: usize) -> (Compat<DuplexStream>, Compat<DuplexStream>) {
+    let (a, b) = io::duplex(capacity);
+    (Compat(a), Compat(b))
+}
+
+pub struct Local;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Clock;
+
+impl Timer for Clock {
+    type Delay = Pin<Box<Sleep>>;
+
+    fn delay(&mut self, duration: Duration) -> Self::Delay {
+        Box::pin(sleep(duration))
+    }
+}
+
+impl<T: Future<Output = ()> + 'static> Spawner<T> for Local {
+    type Error = Void;
+
+    fn spawn(&mut self, future: T) -> Result<(), Void> {
+        spawn_local(future);
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use super::Descriptors;
+use ::tokio::{
+    io::{self, AsyncWrite as _, Interest},
+    net::{UnixListener, UnixStream},
+};
+use core::{
+    mem::{size_of, size_of_val, zeroed},
+    pin::Pin,
+    ptr::null_mut,
+    task::{Context, Poll},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::ready;
+use std::{
+    collections::BTreeMap,
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    path::Path,
+};
+
+const CAPACITY: usize = 253;
+const DEFAULT_LIMIT: usize = 64;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const SEND_FLAGS: libc::c_int = libc::MSG_NOSIGNAL;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const SEND_FLAGS: libc::c_int = 0;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const RECEIVE_FLAGS: libc::c_int = libc::MSG_CMSG_CLOEXEC;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+const RECEIVE_FLAGS: libc::c_int = 0;
+
+type Control = [u64; (CAPACITY * size_of::<RawFd>() + 64) / size_of::<u64>()];
+
+fn send(socket: RawFd, buf: &[u8], descriptors: &[OwnedFd]) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut _,
+        iov_len: buf.len(),
+    };
+    let mut control: Control = [0; size_of::<Control>() / size_of::<u64>()];
+    let mut message: libc::msghdr = unsafe { zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    if !descriptors.is_empty() {
+        let length = (descriptors.len() * size_of::<RawFd>()) as u32;
+        message.msg_control = control.as_mut_ptr() as *mut _;
+        message.msg_controllen = unsafe { libc::CMSG_SPACE(length) } as _;
+        unsafe {
+            let header = libc::CMSG_FIRSTHDR(&message);
+            (*header).cmsg_level = libc::SOL_SOCKET;
+            (*header).cmsg_type = libc::SCM_RIGHTS;
+            (*header).cmsg_len = libc::CMSG_LEN(length) as _;
+            let data = libc::CMSG_DATA(header) as *mut RawFd;
+            for (index, descriptor) in descriptors.iter().enumerate() {
+                data.add(index).write_unaligned(descriptor.as_raw_fd());
+            }
+        }
+    }
+    let sent = unsafe { libc::sendmsg(socket, &message, SEND_FLAGS) };
+    if sent < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(sent as usize)
+    }
+}
+
+fn receive(socket: RawFd, buf: &mut [u8], descriptors: &mut Vec<OwnedFd>) -> io::Result<usize> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut _,
+        iov_len: buf.len(),
+    };
+    let mut control: Control = [0; size_of::<Control>() / size_of::<u64>()];
+    let mut message: libc::msghdr = unsafe { zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut _;
+    message.msg_controllen = size_of_val(&control) as _;
+    let received = unsafe { libc::recvmsg(socket, &mut message, RECEIVE_FLAGS) };
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut header = if message.msg_controllen == 0 {
+        null_mut()
+    } else {
+        unsafe { libc::CMSG_FIRSTHDR(&message) }
+    };
+    while !header.is_null() {
+        unsafe {
+            if (*header).cmsg_level == libc::SOL_SOCKET && (*header).cmsg_type == libc::SCM_RIGHTS
+            {
+                let data = libc::CMSG_DATA(header) as *const RawFd;
+                let count = ((*header).cmsg_len as usize - libc::CMSG_LEN(0) as usize)
+                    / size_of::<RawFd>();
+                for index in 0..count {
+                    descriptors.push(OwnedFd::from_raw_fd(data.add(index).read_unaligned()));
+                }
+            }
+            header = libc::CMSG_NXTHDR(&message, header);
+        }
+    }
+    if message.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "truncated descriptors in Unix transport",
+        ));
+    }
+    Ok(received as usize)
+}
+
+pub struct Unix {
+    stream: UnixStream,
+    outgoing: Vec<OwnedFd>,
+    sent: u32,
+    incoming: BTreeMap<u32, OwnedFd>,
+    received: u32,
+    limit: usize,
+}
+
+impl Unix {
+    pub fn new(stream: UnixStream) -> Self {
+        Unix {
+            stream,
+            outgoing: Vec::new(),
+            sent: 0,
+            incoming: BTreeMap::new(),
+            received: 0,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub async fn connect<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        UnixStream::connect(path).await.map(Unix::new)
+    }
+
+    pub async fn accept(listener: &UnixListener) -> io::Result<Self> {
+        let (stream, _) = listener.accept().await?;
+        Ok(Unix::new(stream))
+    }
+
+    pub fn pair() -> io::Result<(Self, Self)> {
+        let (a, b) = UnixStream::pair()?;
+        Ok((Unix::new(a), Unix::new(b)))
+    }
+
+    pub fn get_ref(&self) -> &UnixStream {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> UnixStream {
+        self.stream
+    }
+}
+
+impl Descriptors for Unix {
+    fn attach(&mut self, descriptor: OwnedFd) -> u32 {
+        let index = self.sent;
+        self.sent = self.sent.wrapping_add(1);
+        self.outgoing.push(descriptor);
+        index
+    }
+
+    fn detach(&mut self, index: u32) -> Option<OwnedFd> {
+        self.incoming.remove(&index)
+    }
+}
+
+impl AsyncRead for Unix {
+    type Error = io::Error;
+
+    fn poll_read(
+        self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let socket = this.stream.as_raw_fd();
+        let mut descriptors = Vec::new();
+        loop {
+            ready!(this.stream.poll_read_ready(ctx))?;
+            match this.stream.try_io(Interest::READABLE, || {
+                receive(socket, buf, &mut descriptors)
+            }) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                result => {
+                    if this.incoming.len() + descriptors.len() > this.limit {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "too many pending descriptors in Unix transport",
+                        )));
+                    }
+                    for descriptor in descriptors {
+                        this.incoming.insert(this.received, descriptor);
+                        this.received = this.received.wrapping_add(1);
+                    }
+                    return Poll::Ready(result);
+                }
+            }
+        }
+    }
+}
+
+impl AsyncWrite for Unix {
+    type WriteError = io::Error;
+    type FlushError = io::Error;
+    type CloseError = io::Error;
+
+    fn poll_write(
+        self: Pin<&mut Self>,
+        ctx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        let socket = this.stream.as_raw_fd();
+        let count = this.outgoing.len().min(CAPACITY);
+        loop {
+            ready!(this.stream.poll_write_ready(ctx))?;
+            let descriptors = &this.outgoing[..count];
+            match this
+                .stream
+                .try_io(Interest::WRITABLE, || send(socket, buf, descriptors))
+            {
+                Ok(sent) => {
+                    this.outgoing.drain(..count);
+                    return Poll::Ready(Ok(sent));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.stream).poll_shutdown(ctx)
+    }
+}
@@ -0,0 +1,113 @@
+use super::Violation;
+use alloc::vec::Vec;
+
+pub const CONTINUATION: u8 = 0x0;
+pub const TEXT: u8 = 0x1;
+pub const BINARY: u8 = 0x2;
+pub const CLOSE: u8 = 0x8;
+pub const PING: u8 = 0x9;
+pub const PONG: u8 = 0xA;
+
+pub struct Header {
+    pub fin: bool,
+    pub opcode: u8,
+    pub mask: Option<[u8; 4]>,
+    pub length: usize,
+}
+
+impl Header {
+    pub fn is_control(&self) -> bool {
+        self.opcode & 0x8 != 0
+    }
+}
+
+pub fn decode(buf: &[u8], limit: usize) -> Result<Option<(Header, usize)>, Violation> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    if buf[0] & 0x70 != 0 {
+        return Err(Violation::Reserved);
+    }
+    let fin = buf[0] & 0x80 != 0;
+    let opcode = buf[0] & 0x0F;
+    match opcode {
+        CONTINUATION | TEXT | BINARY | CLOSE | PING | PONG => {}
+        opcode => return Err(Violation::Opcode(opcode)),
+    }
+    let masked = buf[1] & 0x80 != 0;
+    let (length, mut offset) = match buf[1] & 0x7F {
+        126 => {
+            if buf.len() < 4 {
+                return Ok(None);
+            }
+            (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4)
+        }
+        127 => {
+            if buf.len() < 10 {
+                return Ok(None);
+            }
+            let mut length = [0; 8];
+            length.copy_from_slice(&buf[2..10]);
+            (u64::from_be_bytes(length), 10)
+        }
+        length => (length as u64, 2),
+    };
+    if length > limit as u64 {
+        return Err(Violation::Length);
+    }
+    let mask = if masked {
+        if buf.len() < offset + 4 {
+            return Ok(None);
+        }
+        let mut mask = [0; 4];
+        mask.copy_from_slice(&buf[offset..offset + 4]);
+        offset += 4;
+        Some(mask)
+    } else {
+        None
+    };
+    let header = Header {
+        fin,
+        opcode,
+        mask,
+        length: length as usize,
+    };
+    if header.is_control() && (!fin || header.length > 125) {
+        return Err(Violation::Control);
+    }
+    Ok(Some((header, offset)))
+}
+
+pub fn encode(buf: &mut Vec<u8>, opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) {
+    buf.push(0x80 | opcode);
+    let masked = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        length if length < 126 => buf.push(masked | length as u8),
+        length if length <= u16::MAX as usize => {
+            buf.push(masked | 126);
+            buf.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            buf.push(masked | 127);
+            buf.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            buf.extend_from_slice(&mask);
+            buf.extend(
+                payload
+                    .iter()
+                    .enumerate()
+                    .map(|(index, byte)| byte ^ mask[index % 4]),
+            );
+        }
+        None => buf.extend_from_slice(payload),
+    }
+}
+
+pub fn unmask(payload: &mut [u8], mask: [u8; 4]) {
+    for (index, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[index % 4];
+    }
+}
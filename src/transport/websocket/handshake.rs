@@ -0,0 +1,301 @@
+use super::{Error, Role, Violation, WebSocket};
+use alloc::{format, string::String, vec::Vec};
+use core::{
+    future::Future,
+    pin::Pin,
+    str,
+    task::{Context, Poll},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::ready;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const HEAD_LIMIT: usize = 8192;
+
+fn sha1(message: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut padded = Vec::from(message);
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((message.len() as u64) * 8).to_be_bytes());
+    for block in padded.chunks(64) {
+        let mut words = [0u32; 80];
+        for (index, word) in block.chunks(4).enumerate() {
+            words[index] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for index in 16..80 {
+            words[index] = (words[index - 3] ^ words[index - 8] ^ words[index - 14]
+                ^ words[index - 16])
+                .rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (index, word) in words.iter().enumerate() {
+            let (f, k) = match index {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *word = word.wrapping_add(*value);
+        }
+    }
+    let mut digest = [0; 20];
+    for (chunk, word) in digest.chunks_mut(4).zip(state.iter()) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut output = String::with_capacity((input.len() + 2) / 3 * 4);
+    for chunk in input.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for index in 0..4 {
+            if index <= chunk.len() {
+                output.push(ALPHABET[(group >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
+
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, GUID).as_bytes()))
+}
+
+struct Head<'a> {
+    start: &'a str,
+    headers: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Head<'a> {
+    fn parse(head: &'a [u8]) -> Result<Self, Violation> {
+        let head = str::from_utf8(head).map_err(|_| Violation::Handshake)?;
+        let mut lines = head.split("\r\n").filter(|line| !line.is_empty());
+        let start = lines.next().ok_or(Violation::Handshake)?;
+        let headers = lines
+            .map(|line| {
+                let mut parts = line.splitn(2, ':');
+                match (parts.next(), parts.next()) {
+                    (Some(name), Some(value)) => Ok((name.trim(), value.trim())),
+                    _ => Err(Violation::Handshake),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Head { start, headers })
+    }
+
+    fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+
+    fn contains(&self, name: &str, token: &str) -> bool {
+        self.header(name).map_or(false, |value| {
+            value
+                .split(',')
+                .any(|item| item.trim().eq_ignore_ascii_case(token))
+        })
+    }
+}
+
+fn poll_write_all<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    ctx: &mut Context,
+    buf: &[u8],
+    written: &mut usize,
+) -> Poll<Result<(), Error<S>>> {
+    while *written < buf.len() {
+        let count =
+            ready!(Pin::new(&mut *stream).poll_write(ctx, &buf[*written..])).map_err(Error::Write)?;
+        if count == 0 {
+            return Poll::Ready(Err(Error::Terminated));
+        }
+        *written += count;
+    }
+    Pin::new(stream).poll_flush(ctx).map_err(Error::Flush)
+}
+
+fn poll_read_head<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    ctx: &mut Context,
+    buf: &mut Vec<u8>,
+) -> Poll<Result<usize, Error<S>>> {
+    let mut chunk = [0; 1024];
+    loop {
+        if let Some(end) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            return Poll::Ready(Ok(end + 4));
+        }
+        if buf.len() > HEAD_LIMIT {
+            return Poll::Ready(Err(Error::Protocol(Violation::Handshake)));
+        }
+        let count = ready!(Pin::new(&mut *stream).poll_read(ctx, &mut chunk)).map_err(Error::Read)?;
+        if count == 0 {
+            return Poll::Ready(Err(Error::Terminated));
+        }
+        buf.extend_from_slice(&chunk[..count]);
+    }
+}
+
+enum State {
+    Write(Vec<u8>, usize),
+    Read(Vec<u8>),
+    Done,
+}
+
+pub struct Connect<S> {
+    stream: Option<S>,
+    state: State,
+    accept: String,
+    entropy: fn() -> [u8; 16],
+}
+
+impl<S> Connect<S> {
+    pub(super) fn new(stream: S, host: &str, path: &str, entropy: fn() -> [u8; 16]) -> Self {
+        let key = base64(&entropy());
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            path, host, key
+        );
+        Connect {
+            stream: Some(stream),
+            state: State::Write(request.into_bytes(), 0),
+            entropy,
+            accept: accept_key(&key),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Future for Connect<S> {
+    type Output = Result<WebSocket<S>, Error<S>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let stream = this
+            .stream
+            .as_mut()
+            .expect("Connect polled after completion");
+        loop {
+            match &mut this.state {
+                State::Write(request, written) => {
+                    ready!(poll_write_all(stream, ctx, request, written))?;
+                    this.state = State::Read(Vec::new());
+                }
+                State::Read(buf) => {
+                    let end = ready!(poll_read_head(stream, ctx, buf))?;
+                    let head = Head::parse(&buf[..end]).map_err(Error::Protocol)?;
+                    let switched = head.start.starts_with("HTTP/1.1 101");
+                    if !switched
+                        || !head.contains("Upgrade", "websocket")
+                        || !head.contains("Connection", "upgrade")
+                        || head.header("Sec-WebSocket-Accept") != Some(&this.accept)
+                    {
+                        return Poll::Ready(Err(Error::Protocol(Violation::Handshake)));
+                    }
+                    let remainder = buf.split_off(end);
+                    this.state = State::Done;
+                    return Poll::Ready(Ok(WebSocket::new(
+                        this.stream.take().unwrap(),
+                        Role::Client,
+                        remainder,
+                        Some(this.entropy),
+                    )));
+                }
+                State::Done => panic!("Connect polled after completion"),
+            }
+        }
+    }
+}
+
+pub struct Accept<S> {
+    stream: Option<S>,
+    state: State,
+    remainder: Vec<u8>,
+}
+
+impl<S> Accept<S> {
+    pub(super) fn new(stream: S) -> Self {
+        Accept {
+            stream: Some(stream),
+            state: State::Read(Vec::new()),
+            remainder: Vec::new(),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Future for Accept<S> {
+    type Output = Result<WebSocket<S>, Error<S>>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let stream = this
+            .stream
+            .as_mut()
+            .expect("Accept polled after completion");
+        loop {
+            match &mut this.state {
+                State::Read(buf) => {
+                    let end = ready!(poll_read_head(stream, ctx, buf))?;
+                    let response = {
+                        let head = Head::parse(&buf[..end]).map_err(Error::Protocol)?;
+                        let key = match head.header("Sec-WebSocket-Key") {
+                            Some(key)
+                                if head.start.starts_with("GET ")
+                                    && head.contains("Upgrade", "websocket")
+                                    && head.contains("Connection", "upgrade")
+                                    && head.header("Sec-WebSocket-Version") == Some("13") =>
+                            {
+                                key
+                            }
+                            _ => return Poll::Ready(Err(Error::Protocol(Violation::Handshake))),
+                        };
+                        format!(
+                            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                            accept_key(key)
+                        )
+                    };
+                    this.remainder = buf.split_off(end);
+                    this.state = State::Write(response.into_bytes(), 0);
+                }
+                State::Write(response, written) => {
+                    ready!(poll_write_all(stream, ctx, response, written))?;
+                    this.state = State::Done;
+                    let remainder = core::mem::take(&mut this.remainder);
+                    return Poll::Ready(Ok(WebSocket::new(
+                        this.stream.take().unwrap(),
+                        Role::Server,
+                        remainder,
+                        None,
+                    )));
+                }
+                State::Done => panic!("Accept polled after completion"),
+            }
+        }
+    }
+}
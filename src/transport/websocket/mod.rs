@@ -0,0 +1,464 @@
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    pin::Pin,
+    task::{Context, Poll},
+};
+use core_futures_io::{AsyncRead, AsyncWrite};
+use futures::{ready, Sink, Stream};
+
+mod frame;
+mod handshake;
+pub use handshake::{Accept, Connect};
+
+const DEFAULT_LIMIT: usize = 1 << 24;
+const CHUNK: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    Handshake,
+    Reserved,
+    Opcode(u8),
+    Control,
+    Continuation,
+    Masking,
+    Length,
+    Utf8,
+    Status,
+}
+
+pub enum Error<S: AsyncRead + AsyncWrite> {
+    Read(S::Error),
+    Write(S::WriteError),
+    Flush(S::FlushError),
+    Close(S::CloseError),
+    Protocol(Violation),
+    Terminated,
+    Closed,
+}
+
+impl<S: AsyncRead + AsyncWrite> Debug for Error<S>
+where
+    S::Error: Debug,
+    S::WriteError: Debug,
+    S::FlushError: Debug,
+    S::CloseError: Debug,
+{
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Read(e) => f.debug_tuple("Read").field(e).finish(),
+            Error::Write(e) => f.debug_tuple("Write").field(e).finish(),
+            Error::Flush(e) => f.debug_tuple("Flush").field(e).finish(),
+            Error::Close(e) => f.debug_tuple("Close").field(e).finish(),
+            Error::Protocol(e) => f.debug_tuple("Protocol").field(e).finish(),
+            Error::Terminated => f.write_str("Terminated"),
+            Error::Closed => f.write_str("Closed"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Client,
+    Server,
+}
+
+pub struct WebSocket<S> {
+    stream: S,
+    role: Role,
+    read: Vec<u8>,
+    write: Vec<u8>,
+    partial: Option<(u8, Vec<u8>)>,
+    entropy: Option<fn() -> [u8; 16]>,
+    limit: usize,
+    sent_close: bool,
+    received_close: bool,
+}
+
+impl<S> WebSocket<S> {
+    fn new(stream: S, role: Role, read: Vec<u8>, entropy: Option<fn() -> [u8; 16]>) -> Self {
+        WebSocket {
+            stream,
+            role,
+            read,
+            write: Vec::new(),
+            partial: None,
+            entropy,
+            limit: DEFAULT_LIMIT,
+            sent_close: false,
+            received_close: false,
+        }
+    }
+
+    pub fn connect(stream: S, host: &str, path: &str, entropy: fn() -> [u8; 16]) -> Connect<S> {
+        Connect::new(stream, host, path, entropy)
+    }
+
+    pub fn accept(stream: S) -> Accept<S> {
+        Accept::new(stream)
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    fn mask(&mut self) -> Option<[u8; 4]> {
+        match (self.role, self.entropy) {
+            (Role::Client, Some(entropy)) => {
+                let entropy = entropy();
+                Some([entropy[0], entropy[1], entropy[2], entropy[3]])
+            }
+            _ => None,
+        }
+    }
+
+    fn queue(&mut self, opcode: u8, payload: &[u8]) {
+        let mask = self.mask();
+        frame::encode(&mut self.write, opcode, payload, mask);
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WebSocket<S> {
+    pub fn ping(&mut self, payload: &[u8]) -> Result<(), Error<S>> {
+        if self.sent_close || self.received_close {
+            return Err(Error::Closed);
+        }
+        self.queue(frame::PING, &payload[..payload.len().min(125)]);
+        Ok(())
+    }
+
+    fn poll_write_buffer(&mut self, ctx: &mut Context) -> Poll<Result<(), Error<S>>> {
+        while !self.write.is_empty() {
+            let count = ready!(Pin::new(&mut self.stream).poll_write(ctx, &self.write))
+                .map_err(Error::Write)?;
+            if count == 0 {
+                return Poll::Ready(Err(Error::Terminated));
+            }
+            self.write.drain(..count);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn next_frame(&mut self) -> Result<Option<(frame::Header, Vec<u8>)>, Error<S>> {
+        let (header, offset) = match frame::decode(&self.read, self.limit) {
+            Ok(Some(decoded)) => decoded,
+            Ok(None) => return Ok(None),
+            Err(violation) => return Err(Error::Protocol(violation)),
+        };
+        if header.mask.is_some() != (self.role == Role::Server) {
+            return Err(Error::Protocol(Violation::Masking));
+        }
+        if self.read.len() < offset + header.length {
+            return Ok(None);
+        }
+        let mut payload: Vec<u8> = self.read.drain(..offset + header.length).skip(offset).collect();
+        if let Some(mask) = header.mask {
+            frame::unmask(&mut payload, mask);
+        }
+        Ok(Some((header, payload)))
+    }
+
+    fn handle(
+        &mut self,
+        header: frame::Header,
+        payload: Vec<u8>,
+    ) -> Result<Option<Message>, Error<S>> {
+        match header.opcode {
+            frame::PING => {
+                if !self.sent_close {
+                    self.queue(frame::PONG, &payload);
+                }
+                Ok(None)
+            }
+            frame::PONG => Ok(None),
+            frame::CLOSE => {
+                let status = match payload.len() {
+                    0 => None,
+                    1 => return Err(Error::Protocol(Violation::Status)),
+                    _ => Some(u16::from_be_bytes([payload[0], payload[1]])),
+                };
+                if let Some(status) = status {
+                    if !valid_status(status) {
+                        return Err(Error::Protocol(Violation::Status));
+                    }
+                }
+                self.received_close = true;
+                if !self.sent_close {
+                    self.sent_close = true;
+                    match status {
+                        Some(status) => self.queue(frame::CLOSE, &status.to_be_bytes()),
+                        None => self.queue(frame::CLOSE, &[]),
+                    }
+                }
+                Ok(None)
+            }
+            frame::CONTINUATION => {
+                let (opcode, mut message) = self
+                    .partial
+                    .take()
+                    .ok_or(Error::Protocol(Violation::Continuation))?;
+                if message.len() + payload.len() > self.limit {
+                    return Err(Error::Protocol(Violation::Length));
+                }
+                message.extend_from_slice(&payload);
+                if header.fin {
+                    complete(opcode, message).map(Some)
+                } else {
+                    self.partial = Some((opcode, message));
+                    Ok(None)
+                }
+            }
+            opcode => {
+                if self.partial.is_some() {
+                    return Err(Error::Protocol(Violation::Continuation));
+                }
+                if header.fin {
+                    complete(opcode, payload).map(Some)
+                } else {
+                    self.partial = Some((opcode, payload));
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+fn valid_status(status: u16) -> bool {
+    match status {
+        1000..=1003 | 1007..=1011 | 3000..=4999 => true,
+        _ => false,
+    }
+}
+
+fn complete<S: AsyncRead + AsyncWrite>(opcode: u8, payload: Vec<u8>) -> Result<Message, Error<S>> {
+    if opcode == frame::TEXT {
+        String::from_utf8(payload)
+            .map(Message::Text)
+            .map_err(|_| Error::Protocol(Violation::Utf8))
+    } else {
+        Ok(Message::Binary(payload))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Stream for WebSocket<S> {
+    type Item = Result<Message, Error<S>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let mut chunk = [0; CHUNK];
+        loop {
+            if let Poll::Ready(Err(e)) = this.poll_write_buffer(ctx) {
+                return Poll::Ready(Some(Err(e)));
+            }
+            if this.received_close {
+                return Poll::Ready(None);
+            }
+            match this.next_frame() {
+                Ok(Some((header, payload))) => match this.handle(header, payload) {
+                    Ok(Some(message)) => return Poll::Ready(Some(Ok(message))),
+                    Ok(None) => continue,
+                    Err(e) => return Poll::Ready(Some(Err(e))),
+                },
+                Ok(None) => {}
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+            let count = match ready!(Pin::new(&mut this.stream).poll_read(ctx, &mut chunk)) {
+                Ok(count) => count,
+                Err(e) => return Poll::Ready(Some(Err(Error::Read(e)))),
+            };
+            if count == 0 {
+                return Poll::Ready(if this.read.is_empty() && this.partial.is_none() {
+                    None
+                } else {
+                    Some(Err(Error::Terminated))
+                });
+            }
+            this.read.extend_from_slice(&chunk[..count]);
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Sink<Message> for WebSocket<S> {
+    type Error = Error<S>;
+
+    fn poll_ready(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        if self.write.len() >= CHUNK {
+            ready!(self.poll_write_buffer(ctx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        if self.sent_close {
+            return Err(Error::Closed);
+        }
+        match item {
+            Message::Text(text) => self.queue(frame::TEXT, text.as_bytes()),
+            Message::Binary(data) => self.queue(frame::BINARY, &data),
+        }
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.poll_write_buffer(ctx))?;
+        Pin::new(&mut self.stream)
+            .poll_flush(ctx)
+            .map_err(Error::Flush)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        if !self.sent_close {
+            self.sent_close = true;
+            self.queue(frame::CLOSE, &1000u16.to_be_bytes());
+        }
+        ready!(self.poll_write_buffer(ctx))?;
+        ready!(Pin::new(&mut self.stream).poll_flush(ctx)).map_err(Error::Flush)?;
+        while !self.received_close {
+            match ready!(self.as_mut().poll_next(ctx)) {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Poll::Ready(Err(e)),
+                None => break,
+            }
+        }
+        Pin::new(&mut self.stream)
+            .poll_close(ctx)
+            .map_err(Error::Close)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame, Error, Message, Violation, WebSocket};
+    use crate::transport::pipe::{pipe, read, Pipe};
+    use alloc::vec;
+    use core::sync::atomic::{AtomicU8, Ordering};
+    use futures::{executor::block_on, future::join, SinkExt, StreamExt};
+
+    fn entropy() -> [u8; 16] {
+        static COUNTER: AtomicU8 = AtomicU8::new(0);
+        [COUNTER.fetch_add(1, Ordering::Relaxed).wrapping_mul(37) | 1; 16]
+    }
+
+    fn connected() -> (WebSocket<Pipe>, WebSocket<Pipe>) {
+        let (client, server) = pipe();
+        let (client, server) = block_on(join(
+            WebSocket::connect(client, "localhost", "/", entropy),
+            WebSocket::accept(server),
+        ));
+        (client.unwrap(), server.unwrap())
+    }
+
+    #[test]
+    fn loopback() {
+        let (client, server) = pipe();
+        block_on(async {
+            let (client, server) = join(
+                WebSocket::connect(client, "localhost", "/", entropy),
+                WebSocket::accept(server),
+            )
+            .await;
+            let (mut client, mut server) = (client.unwrap(), server.unwrap());
+            client.send(Message::Text("hello".into())).await.unwrap();
+            assert_eq!(
+                server.next().await.unwrap().unwrap(),
+                Message::Text("hello".into())
+            );
+            server.send(Message::Binary(vec![1, 2, 3])).await.unwrap();
+            assert_eq!(
+                client.next().await.unwrap().unwrap(),
+                Message::Binary(vec![1, 2, 3])
+            );
+            let (closed, next) = join(client.close(), server.next()).await;
+            closed.unwrap();
+            assert!(next.is_none());
+        });
+    }
+
+    #[test]
+    fn fresh_mask_per_frame() {
+        let (client, _server) = pipe();
+        let mut socket = WebSocket::new(client, super::Role::Client, vec![], Some(entropy));
+        let first = socket.mask().unwrap();
+        let second = socket.mask().unwrap();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn server_rejects_unmasked_frames() {
+        let (client, server) = pipe();
+        block_on(async {
+            let (client, server) = join(
+                WebSocket::connect(client, "localhost", "/", entropy),
+                WebSocket::accept(server),
+            )
+            .await;
+            let (mut client, mut server) = (client.unwrap(), server.unwrap());
+            client.entropy = None;
+            client.send(Message::Text("hello".into())).await.unwrap();
+            assert!(server.next().await.unwrap().is_err());
+        });
+    }
+
+    #[test]
+    fn refuses_pings_once_closing() {
+        let (mut client, mut server) = connected();
+        block_on(async {
+            let (closed, next) = join(client.close(), server.next()).await;
+            closed.unwrap();
+            assert!(next.is_none());
+        });
+        assert!(matches!(client.ping(b"ping"), Err(Error::Closed)));
+        assert!(matches!(server.ping(b"ping"), Err(Error::Closed)));
+    }
+
+    #[test]
+    fn echoes_close_status() {
+        let (mut client, mut server) = connected();
+        client.sent_close = true;
+        client.queue(frame::CLOSE, &[0x03, 0xE9, b'b', b'y', b'e']);
+        block_on(client.flush()).unwrap();
+        assert!(block_on(server.next()).is_none());
+        assert_eq!(
+            read(&mut client.stream, 4).unwrap(),
+            [0x88, 0x02, 0x03, 0xE9]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_close_status() {
+        let (mut client, mut server) = connected();
+        client.sent_close = true;
+        client.queue(frame::CLOSE, &1005u16.to_be_bytes());
+        block_on(client.flush()).unwrap();
+        assert!(matches!(
+            block_on(server.next()),
+            Some(Err(Error::Protocol(Violation::Status)))
+        ));
+    }
+
+    #[test]
+    fn close_awaits_peer_close() {
+        let (mut client, mut server) = connected();
+        block_on(async {
+            let (closed, next) = join(client.close(), async {
+                server.send(Message::Text("late".into())).await.unwrap();
+                server.next().await
+            })
+            .await;
+            closed.unwrap();
+            assert!(next.is_none());
+        });
+        assert!(client.received_close);
+    }
+}